@@ -0,0 +1,80 @@
+//! 単位時間あたりのアロケーション回数を、作業領域を使い回す場合と毎回作り直す場合で比べる
+//! `cargo bench --bench alloc`で実行する
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::*;
+use hakoniwa::{run, GeneratedData, RunBuffers};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// 確保と再確保の回数を数えるアロケータ
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    System.alloc(layout)
+  }
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    System.dealloc(ptr, layout)
+  }
+  unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    System.realloc(ptr, layout, new_size)
+  }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const OBJECTS: u64 = 200;
+const TICKS: usize = 200;
+
+/// 全ての木を毎回少しずつ弱らせる世界で、単位時間あたりの平均アロケーション回数を測る
+/// `reuse`が`false`の場合は単位時間ごとに作業領域を捨て、使い回さない場合を再現する
+fn allocations_per_tick(reuse: bool) -> f64 {
+  let mut ctx = world([]);
+  ctx.memory_capacity = Some(0);
+  for i in 0..OBJECTS {
+    ctx
+      .objects
+      .insert(format!("t{i}"), Arc::new(object(tree("oak", i, i))));
+  }
+  ctx.rebuild_name_index();
+  ctx.rebuild_spatial_index();
+  let weaken = generator(|ctx| GeneratedData {
+    events: ctx
+      .objects
+      .keys()
+      .map(|id| Act {
+        patch: Some((id.clone(), -1)),
+        ..act(id)
+      })
+      .collect(),
+    ..GeneratedData::empty()
+  });
+  // 作業領域の容量を確保するための一回
+  run(&mut ctx, vec![weaken.clone()]).unwrap();
+  let before = ALLOCATIONS.load(Ordering::Relaxed);
+  for _ in 0..TICKS {
+    if !reuse {
+      ctx.buffers = RunBuffers::default();
+    }
+    run(&mut ctx, vec![weaken.clone()]).unwrap();
+  }
+  (ALLOCATIONS.load(Ordering::Relaxed) - before) as f64 / TICKS as f64
+}
+
+fn main() {
+  let fresh = allocations_per_tick(false);
+  let reused = allocations_per_tick(true);
+  println!("objects: {OBJECTS}, ticks: {TICKS}");
+  println!("作業領域を毎回作り直す: {fresh:.1} allocations/tick");
+  println!("作業領域を使い回す:     {reused:.1} allocations/tick");
+  println!("削減: {:.1} allocations/tick", fresh - reused);
+}
@@ -0,0 +1,56 @@
+//! `Context::objects_within`を、索引を使わずに全てのオブジェクトを調べる場合と、格子の索引を使う場合で比べる
+//! `cargo bench --bench spatial`で実行する
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::*;
+use num_bigint::BigUint;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const OBJECTS: u64 = 50_000;
+const SIZE: u64 = 10_000;
+const QUERIES: u32 = 200;
+const RADIUS: u32 = 50;
+
+/// 一辺`SIZE`の範囲に散らばった木の世界
+fn scattered() -> World {
+  let mut rng = StdRng::seed_from_u64(0);
+  let mut ctx = world([]);
+  for i in 0..OBJECTS {
+    let (x, y) = (rng.gen_range(0..SIZE), rng.gen_range(0..SIZE));
+    ctx
+      .objects
+      .insert(format!("t{i}"), Arc::new(object(tree("oak", x, y))));
+  }
+  ctx
+}
+
+/// 一回の問い合わせにかかる平均の時間と、見つかったオブジェクトの合計
+fn time_per_query(ctx: &World) -> (Duration, usize) {
+  let mut rng = StdRng::seed_from_u64(1);
+  let radius = BigUint::from(RADIUS);
+  let centers = (0..QUERIES)
+    .map(|_| pt(rng.gen_range(0..SIZE), rng.gen_range(0..SIZE)))
+    .collect::<Vec<_>>();
+  let start = Instant::now();
+  let found = centers
+    .iter()
+    .map(|center| ctx.objects_within(center, &radius).len())
+    .sum();
+  (start.elapsed() / QUERIES, found)
+}
+
+fn main() {
+  let mut ctx = scattered();
+  let (linear, linear_found) = time_per_query(&ctx);
+  ctx.set_spatial_index(Some(BigUint::from(64u32)));
+  let (grid, grid_found) = time_per_query(&ctx);
+  assert_eq!(linear_found, grid_found);
+  println!("objects: {OBJECTS}, queries: {QUERIES}, radius: {RADIUS}");
+  println!("全てを調べる: {linear:?}/query");
+  println!("格子の索引:   {grid:?}/query");
+}
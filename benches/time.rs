@@ -0,0 +1,45 @@
+//! 時刻と地点の計算を、`u64`を使う場合と`BigUint`を使う場合で比べる
+//! 世界の中の時刻と座標は常に`BigUint`なので、`run`の一回分の速さは変わらない
+//! 比べているのは世界の外で単独に`Time`と`Point`を使った場合の計算だけである
+//! `cargo bench --bench time`で実行する
+
+use hakoniwa::{Point, Time, TimeInt, WorldInt};
+use num_bigint::BigUint;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const TICKS: u32 = 100_000;
+
+/// 時刻を`TICKS`回進め、そのたびに日と年を読む時間
+fn advance<N: TimeInt>(one_day_of_time: N, one_year_of_day: N) -> Duration {
+  let mut time = Time::zero(one_day_of_time, one_year_of_day);
+  let start = Instant::now();
+  for _ in 0..TICKS {
+    time.plus_one();
+    black_box((time.day(), time.year()));
+  }
+  start.elapsed()
+}
+
+/// 地点を`TICKS`回ずらし、そのたびに原点からの距離の二乗を求める時間
+fn measure<N: WorldInt + From<u32>>() -> Duration {
+  let origin = Point::new(N::from(0u32), N::from(0u32));
+  let start = Instant::now();
+  for i in 0..TICKS {
+    let point = Point::new(N::from(i), N::from(TICKS - i));
+    black_box(point.squared_euclidean_distance(&origin));
+  }
+  start.elapsed()
+}
+
+fn main() {
+  let time_u64 = advance(24u64, 365u64);
+  let time_big = advance(BigUint::from(24u32), BigUint::from(365u32));
+  let point_u64 = measure::<u64>();
+  let point_big = measure::<BigUint>();
+  println!("ticks: {TICKS}");
+  println!("Time<u64>:     {time_u64:?}");
+  println!("Time<BigUint>: {time_big:?}");
+  println!("Point<u64>:     {point_u64:?}");
+  println!("Point<BigUint>: {point_big:?}");
+}
@@ -0,0 +1,47 @@
+//! 速度による移動を、座標が`u64`に収まってまとめて計算される場合と、
+//! 収まらずに`BigUint`で一つずつ計算される場合で比べる
+//! `cargo bench --bench velocity`で実行する
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::*;
+use hakoniwa::{run, Point};
+use num_bigint::BigUint;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const OBJECTS: u64 = 50_000;
+const TICKS: u32 = 20;
+
+/// 全ての木が速度を持つ世界で、単位時間あたりの平均の処理時間を測る
+/// 全ての座標に`base`を足しておき、`base`が大きければ`BigUint`での計算になる
+fn time_per_tick(base: &BigUint) -> Duration {
+  let mut ctx = world([]);
+  ctx.memory_capacity = Some(0);
+  for i in 0..OBJECTS {
+    let mut object = object(Tree {
+      velocity: Some((1, -1)),
+      ..tree("oak", i, i + TICKS as u64 + 1)
+    });
+    object.point = Point::new(base + i, base + i + TICKS + 1u32);
+    ctx.objects.insert(format!("t{i}"), Arc::new(object));
+  }
+  ctx.rebuild_name_index();
+  ctx.rebuild_spatial_index();
+  // 作業領域の容量を確保するための一回
+  run(&mut ctx, vec![]).unwrap();
+  let start = Instant::now();
+  for _ in 0..TICKS {
+    run(&mut ctx, vec![]).unwrap();
+  }
+  start.elapsed() / TICKS
+}
+
+fn main() {
+  let batched = time_per_tick(&BigUint::from(0u32));
+  let fallback = time_per_tick(&(BigUint::from(1u32) << 64));
+  println!("objects: {OBJECTS}, ticks: {TICKS}");
+  println!("u64でまとめて計算:    {batched:?}/tick");
+  println!("BigUintで一つずつ計算: {fallback:?}/tick");
+}
@@ -0,0 +1,124 @@
+//! `Context`のオブジェクトをBevyのEntityとして扱うためのブリッジ
+//!
+//! `Context`はイベントとオブジェクトの型が`Send + Sync`であれば`Resource`になるので、`World::insert_resource`で登録して使う。
+//! `sync_objects_system`を毎フレーム走らせると、前回の同期からの差分（生成・削除・移動）だけが`Commands`に反映される。
+
+use crate::{to_f64_saturating, Context, EventContents, ObjectType, Point};
+use bevy_ecs::prelude::*;
+use bevy_transform::components::Transform;
+use rustc_hash::FxHashMap;
+
+/// `Context`上のオブジェクトに対応するEntityに付けられるComponent
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct HakoniwaObject {
+  /// オブジェクトのID
+  pub id: String,
+}
+
+/// オブジェクトのIDとEntityの対応を保持するResource
+#[derive(Resource, Debug)]
+pub struct ObjectBridge {
+  /// 地点の座標に掛けて`Transform`の座標にする倍率
+  pub scale: f32,
+  /// オブジェクトのIDと、対応するEntityと最後に反映した地点
+  entities: FxHashMap<String, (Entity, Point)>,
+}
+
+impl Default for ObjectBridge {
+  fn default() -> Self {
+    ObjectBridge {
+      scale: 1.0,
+      entities: FxHashMap::default(),
+    }
+  }
+}
+
+impl ObjectBridge {
+  /// オブジェクトに対応するEntity
+  pub fn entity(&self, id: &str) -> Option<Entity> {
+    self.entities.get(id).map(|(entity, _)| *entity)
+  }
+
+  /// 対応を保持しているオブジェクトの数
+  pub fn len(&self) -> usize {
+    self.entities.len()
+  }
+
+  /// 対応を一つも保持していないかどうか
+  pub fn is_empty(&self) -> bool {
+    self.entities.is_empty()
+  }
+
+  /// 前回の同期からの差分を`Commands`に反映する
+  /// - 消滅したオブジェクトのEntityはdespawnする
+  /// - 世界での位置が変わったオブジェクトのEntityは`Transform`を差し替える
+  /// - 新たに生成されたオブジェクトは`HakoniwaObject`と`Transform`を持つEntityとしてspawnする
+  pub fn sync<T: EventContents<U>, U: ObjectType>(
+    &mut self,
+    ctx: &Context<T, U>,
+    commands: &mut Commands,
+  ) {
+    self.entities.retain(|id, (entity, _)| {
+      if ctx.objects.contains_key(id) {
+        true
+      } else {
+        commands.entity(*entity).despawn();
+        false
+      }
+    });
+    for (id, object) in ctx.objects.iter() {
+      // 親に付随するオブジェクトは世界での位置に置く
+      let world_point = ctx.located_point(id, object).into_owned();
+      match self.entities.get_mut(id) {
+        Some((entity, point)) => {
+          if *point != world_point {
+            commands
+              .entity(*entity)
+              .insert(point_to_transform(&world_point, self.scale));
+            *point = world_point;
+          }
+        }
+        None => {
+          let entity = commands
+            .spawn((
+              HakoniwaObject { id: id.clone() },
+              point_to_transform(&world_point, self.scale),
+            ))
+            .id();
+          self.entities.insert(id.clone(), (entity, world_point));
+        }
+      }
+    }
+  }
+}
+
+/// 地点を`Transform`に変換する
+/// x座標とy座標に`scale`を掛けたものがそのまま平行移動量になり、z座標は`0.0`になる
+pub fn point_to_transform(point: &Point, scale: f32) -> Transform {
+  Transform::from_xyz(
+    to_f64_saturating(&point.x) as f32 * scale,
+    to_f64_saturating(&point.y) as f32 * scale,
+    0.0,
+  )
+}
+
+impl<T, U> Resource for Context<T, U>
+where
+  T: EventContents<U> + Send + Sync + 'static,
+  U: ObjectType + Send + Sync + 'static,
+  U::Patch: Send + Sync,
+{
+}
+
+/// リソースとして登録された`Context`の状態をEntityに反映するsystem
+pub fn sync_objects_system<T, U>(
+  ctx: Res<Context<T, U>>,
+  mut bridge: ResMut<ObjectBridge>,
+  mut commands: Commands,
+) where
+  T: EventContents<U> + Send + Sync + 'static,
+  U: ObjectType + Send + Sync + 'static,
+  U::Patch: Send + Sync,
+{
+  bridge.sync(&ctx, &mut commands);
+}
@@ -0,0 +1,203 @@
+//! 時刻とオブジェクトを圧縮してファイルなどに書き出す仕組み
+
+use crate::Versioned;
+use crate::{Context, EventContents, Migrate, Object, ObjectType, Point, SavedObject, Time};
+use bincode::Options;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use num_bigint::BigUint;
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+/// 書き出したデータの先頭に置く識別子
+const MAGIC: [u8; 4] = *b"HKNW";
+/// 書き出す形式の版
+/// 版2で時刻に`day_periods`が加わった
+/// 版3でオブジェクトに`parent_frame`が加わった
+/// 版4でオブジェクトに`caused_by`が加わった
+const FORMAT_VERSION: u32 = 4;
+/// 読み込む前に確保しておくオブジェクトの数の上限
+/// 壊れたデータの個数をそのまま信じて巨大な領域を確保しないようにする
+const MAX_PREALLOCATED_OBJECTS: usize = 1 << 16;
+
+/// 版1の時刻
+/// bincodeは欠けたフィールドを既定値で埋めないので、`day_periods`を持たない並びとして読む
+#[derive(Deserialize)]
+struct LegacyTime {
+  all: BigUint,
+  one_day_of_time: BigUint,
+  _day: BigUint,
+  _remainder_time: BigUint,
+  one_year_of_day: BigUint,
+  _year: BigUint,
+  _remainder_day: BigUint,
+}
+
+impl From<LegacyTime> for Time {
+  fn from(time: LegacyTime) -> Self {
+    Time::new(time.all, time.one_day_of_time, time.one_year_of_day)
+  }
+}
+
+/// 整数を可変長で書き出す設定
+fn options() -> impl Options + Copy {
+  bincode::DefaultOptions::new()
+}
+
+/// bincodeのエラーを入出力のエラーにする
+fn to_io_error(error: bincode::ErrorKind) -> io::Error {
+  match error {
+    bincode::ErrorKind::Io(error) => error,
+    error => io::Error::new(io::ErrorKind::InvalidData, error),
+  }
+}
+
+impl<T: EventContents<U>, U: ObjectType + Migrate> Context<T, U> {
+  /// 時刻とオブジェクトをgzipで圧縮して書き出す
+  /// オブジェクトはIDの辞書順に一つずつ書き出されるので、全体を一度に複製することはない
+  /// `BigUint`は可変長で書き出されるので、小さい値ほど短くなる
+  /// イベントの記憶や検査などは含まれない
+  pub fn save_compressed<W: Write>(&self, writer: W) -> io::Result<()> {
+    let mut encoder = GzEncoder::new(writer, Compression::default());
+    let options = options();
+    let header = (MAGIC, FORMAT_VERSION, &self.time, self.objects.len() as u64);
+    options
+      .serialize_into(&mut encoder, &header)
+      .map_err(|error| to_io_error(*error))?;
+    let mut ids = self.objects.keys().collect::<Vec<_>>();
+    ids.sort_unstable();
+    for id in ids {
+      let object = &self.objects[id];
+      // `SavedObject`と同じ並びで書き出すことで、オブジェクト全体を複製せずに済ませる
+      let saved = (
+        id,
+        &object.generated_time,
+        &object.last_modified,
+        &object.point,
+        U::VERSION,
+        &object.object_type,
+        &object.parent_frame,
+        &object.caused_by,
+      );
+      options
+        .serialize_into(&mut encoder, &saved)
+        .map_err(|error| to_io_error(*error))?;
+    }
+    encoder.finish()?;
+    Ok(())
+  }
+
+  /// `save_compressed`で書き出したものから時刻とオブジェクトを読み込み、今の時刻とオブジェクトを置き換える
+  /// 古い版のオブジェクトは`Migrate::migrate`で変換される
+  /// 読み込みに失敗した場合は何も置き換えない
+  /// イベントの記憶や検査などはそのまま残る
+  pub fn load_compressed<R: Read>(&mut self, reader: R) -> io::Result<()> {
+    let mut decoder = GzDecoder::new(reader);
+    let options = options();
+    let (magic, format_version): ([u8; 4], u32) = options
+      .deserialize_from(&mut decoder)
+      .map_err(|error| to_io_error(*error))?;
+    if magic != MAGIC {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "not a hakoniwa checkpoint",
+      ));
+    }
+    if format_version == 0 || format_version > FORMAT_VERSION {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("unsupported checkpoint format version {format_version}"),
+      ));
+    }
+    let time = if format_version == 1 {
+      options
+        .deserialize_from::<_, LegacyTime>(&mut decoder)
+        .map(Time::from)
+    } else {
+      options.deserialize_from(&mut decoder)
+    }
+    .map_err(|error| to_io_error(*error))?;
+    let len: u64 = options
+      .deserialize_from(&mut decoder)
+      .map_err(|error| to_io_error(*error))?;
+    let len =
+      usize::try_from(len).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    let mut objects = FxHashMap::default();
+    objects.reserve(len.min(MAX_PREALLOCATED_OBJECTS));
+    for _ in 0..len {
+      let saved: SavedObject<U> = match format_version {
+        1 => {
+          // 版1には時刻の`day_periods`と、`parent_frame`と`caused_by`がない
+          let (id, generated_time, last_modified, point, object_type): (
+            String,
+            LegacyTime,
+            LegacyTime,
+            Point,
+            Versioned<U>,
+          ) = options
+            .deserialize_from(&mut decoder)
+            .map_err(|error| to_io_error(*error))?;
+          SavedObject {
+            id,
+            generated_time: generated_time.into(),
+            last_modified: last_modified.into(),
+            point,
+            object_type,
+            parent_frame: None,
+            caused_by: None,
+          }
+        }
+        2 => {
+          // 版2には`parent_frame`と`caused_by`がない
+          let (id, generated_time, last_modified, point, object_type) = options
+            .deserialize_from(&mut decoder)
+            .map_err(|error| to_io_error(*error))?;
+          SavedObject {
+            id,
+            generated_time,
+            last_modified,
+            point,
+            object_type,
+            parent_frame: None,
+            caused_by: None,
+          }
+        }
+        3 => {
+          // 版3には`caused_by`がない
+          let (id, generated_time, last_modified, point, object_type, parent_frame) = options
+            .deserialize_from(&mut decoder)
+            .map_err(|error| to_io_error(*error))?;
+          SavedObject {
+            id,
+            generated_time,
+            last_modified,
+            point,
+            object_type,
+            parent_frame,
+            caused_by: None,
+          }
+        }
+        _ => options
+          .deserialize_from(&mut decoder)
+          .map_err(|error| to_io_error(*error))?,
+      };
+      let object = Object {
+        generated_time: saved.generated_time,
+        last_modified: saved.last_modified,
+        point: saved.point,
+        object_type: saved.object_type.0,
+        parent_frame: saved.parent_frame,
+        caused_by: saved.caused_by,
+      };
+      objects.insert(saved.id, Arc::new(object));
+    }
+    self.time = time;
+    self.objects = objects;
+    self.rebuild_name_index();
+    self.rebuild_spatial_index();
+    Ok(())
+  }
+}
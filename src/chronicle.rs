@@ -0,0 +1,137 @@
+//! 大きな出来事を拾い集めて作る世界の年表
+
+use crate::{Context, EventContents, ObjectType, Time};
+use rustc_hash::FxHashMap;
+use std::fmt;
+
+/// 年表に載せる出来事を選ぶ基準
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChronicleConfig {
+  /// `EventContents::magnitude`がこの値以上のイベントを載せる
+  pub magnitude_threshold: f64,
+  /// ある種類のオブジェクトの数が前の単位時間からこの割合以上増減した時に載せる
+  /// 例えば`0.5`なら1.5倍以上で大量発生、半分以下で激減とする
+  pub population_change: f64,
+  /// 数の増減を調べる種類の最小の数
+  /// 増えた場合は増えた後、減った場合は減る前の数がこれ未満なら載せない
+  pub min_population: usize,
+}
+
+/// 年表の一項目
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChronicleEntry {
+  /// 出来事が起きた時刻
+  pub time: Time,
+  /// 出来事の概要
+  pub summary: String,
+  /// 出来事に関わったオブジェクトのID
+  /// 数の増減による項目では空になる
+  pub objects: Vec<String>,
+}
+
+impl fmt::Display for ChronicleEntry {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}年目：{}", &self.time.year + 1u32, self.summary)
+  }
+}
+
+/// 世界の年表を記録するもの
+/// `config`を設定すると`run`ごとに出来事を拾い集める
+#[derive(Debug, Clone, Default)]
+pub struct Chronicler {
+  /// 年表に載せる出来事を選ぶ基準
+  /// `None`の場合は何も記録しない
+  pub config: Option<ChronicleConfig>,
+  /// 記録された項目
+  /// 古いものほど前にある
+  pub(crate) entries: Vec<ChronicleEntry>,
+  /// 前の単位時間での種類ごとのオブジェクトの数
+  pub(crate) counts: Option<FxHashMap<String, usize>>,
+}
+
+/// 一行に一項目ずつ並べた年表
+impl fmt::Display for Chronicler {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for entry in self.entries.iter() {
+      writeln!(f, "{entry}")?;
+    }
+    Ok(())
+  }
+}
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// 記録された年表
+  pub fn chronicle(&self) -> &[ChronicleEntry] {
+    &self.chronicler.entries
+  }
+
+  /// 単位時間を進めた後の世界から年表に載せる出来事を拾う
+  /// `first_sequence`以降の通し番号を持つ記憶がこの単位時間に起きたイベントである
+  pub(crate) fn update_chronicle(&mut self, first_sequence: u64) {
+    let Some(config) = &self.chronicler.config else {
+      return;
+    };
+    let mut entries = Vec::new();
+    // 新しく記憶されたイベントは記憶の末尾に並んでいる
+    let start = self
+      .memory
+      .iter()
+      .rposition(|e| e.sequence < first_sequence)
+      .map_or(0, |i| i + 1);
+    for e in self.memory.range(start..) {
+      if e.contents.magnitude() >= config.magnitude_threshold {
+        let mut objects = vec![e.do_object.clone()];
+        objects.extend(e.target_object.iter().cloned());
+        objects.extend(e.affected_objects.iter().cloned());
+        objects.sort_unstable();
+        objects.dedup();
+        entries.push(ChronicleEntry {
+          time: self.time.clone(),
+          summary: e.contents.summary(),
+          objects,
+        });
+      }
+    }
+    let counts = self
+      .name_index
+      .counts()
+      .map(|(name, count)| (name.clone(), count))
+      .collect::<FxHashMap<_, _>>();
+    if let Some(previous) = &self.chronicler.counts {
+      let mut names = previous.keys().chain(counts.keys()).collect::<Vec<_>>();
+      names.sort_unstable();
+      names.dedup();
+      for name in names {
+        let before = previous.get(name).copied().unwrap_or(0);
+        let after = counts.get(name).copied().unwrap_or(0);
+        let summary = if after >= config.min_population
+          && after as f64 >= before as f64 * (1.0 + config.population_change)
+          && after > before
+        {
+          format!("{name}が大量発生")
+        } else if before >= config.min_population
+          && after as f64 <= before as f64 * (1.0 - config.population_change)
+          && after < before
+        {
+          if after == 0 {
+            format!("{name}が絶滅")
+          } else {
+            format!("{name}が激減")
+          }
+        } else {
+          continue;
+        };
+        entries.push(ChronicleEntry {
+          time: self.time.clone(),
+          summary,
+          objects: Vec::new(),
+        });
+      }
+    }
+    let previous = self.chronicler.counts.replace(counts);
+    if let Some(undo) = &mut self.undo {
+      undo.chronicle_counts = Some(previous);
+    }
+    self.chronicler.entries.extend(entries);
+  }
+}
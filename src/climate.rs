@@ -0,0 +1,142 @@
+//! 世界全体で共有される季節と天候
+
+use crate::noise::{hash_biguint, mix};
+use crate::{Context, EventContents, ObjectType, Time};
+use num_traits::{ToPrimitive, Zero};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// 天候の遷移に使う乱数の系列
+const WEATHER_STREAM: u64 = 0x0057_4541_5448_4552;
+
+/// 季節
+/// 一年を四等分し、年の初めを春とする
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Season {
+  /// 春
+  Spring,
+  /// 夏
+  Summer,
+  /// 秋
+  Autumn,
+  /// 冬
+  Winter,
+}
+
+impl Season {
+  /// 時刻が属する季節
+  pub fn of(time: &Time) -> Self {
+    let quarter = (&time.remainder_day * 4u32) / &time.one_year_of_day;
+    match quarter.to_u8() {
+      Some(0) => Season::Spring,
+      Some(1) => Season::Summer,
+      Some(2) => Season::Autumn,
+      _ => Season::Winter,
+    }
+  }
+}
+
+/// 天候
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Weather {
+  /// 晴れ
+  Clear,
+  /// 曇り
+  Cloudy,
+  /// 雨
+  Rain,
+  /// 嵐
+  Storm,
+}
+
+impl Weather {
+  /// 全ての天候
+  pub const ALL: [Weather; 4] = [
+    Weather::Clear,
+    Weather::Cloudy,
+    Weather::Rain,
+    Weather::Storm,
+  ];
+}
+
+/// ある天候から次の日の天候への遷移の重み
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct WeatherTable {
+  /// `weights[from][to]`が`from`から`to`へ遷移する重み
+  /// 添字は`Weather::ALL`での位置
+  weights: [[f64; 4]; 4],
+}
+
+impl WeatherTable {
+  /// 遷移の重みを全て指定した表の新たな生成
+  /// 行ごとの重みは合計が1でなくても良い
+  pub fn new(weights: [[f64; 4]; 4]) -> Self {
+    WeatherTable { weights }
+  }
+
+  /// `from`から`to`へ遷移する重みを変える
+  pub fn set(&mut self, from: Weather, to: Weather, weight: f64) {
+    self.weights[from as usize][to as usize] = weight;
+  }
+
+  /// `[0, 1)`の値`r`に従って`from`の次の天候を選ぶ
+  /// 重みが全て0以下の場合は天候は変わらない
+  pub fn next(&self, from: Weather, r: f64) -> Weather {
+    let row = &self.weights[from as usize];
+    let total: f64 = row.iter().map(|w| w.max(0.0)).sum();
+    if total <= 0.0 {
+      return from;
+    }
+    let mut rest = r * total;
+    for (weather, weight) in Weather::ALL.into_iter().zip(row) {
+      rest -= weight.max(0.0);
+      if rest < 0.0 {
+        return weather;
+      }
+    }
+    // 丸め誤差で選ばれなかった場合は重みを持つ最後の天候にする
+    Weather::ALL
+      .into_iter()
+      .zip(row)
+      .rev()
+      .find(|(_, weight)| **weight > 0.0)
+      .map_or(from, |(weather, _)| weather)
+  }
+}
+
+impl Default for WeatherTable {
+  fn default() -> Self {
+    WeatherTable::new([
+      [0.6, 0.3, 0.1, 0.0],
+      [0.3, 0.4, 0.25, 0.05],
+      [0.2, 0.4, 0.3, 0.1],
+      [0.1, 0.3, 0.4, 0.2],
+    ])
+  }
+}
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// 現在の季節
+  pub fn season(&self) -> Season {
+    Season::of(&self.time)
+  }
+
+  /// 現在の天候
+  pub fn weather(&self) -> Weather {
+    self.weather
+  }
+
+  /// 日が変わった時に天候を遷移させる
+  /// 遷移は`seed`と時刻だけから決まるので、同じ設定で実行すれば同じ天候の列になる
+  pub(crate) fn advance_weather(&mut self) {
+    if !self.time.remainder_time.is_zero() {
+      return;
+    }
+    let h = mix(self.seed ^ hash_biguint(&self.time.all, WEATHER_STREAM));
+    let r = (h >> 11) as f64 / (1u64 << 53) as f64;
+    self.weather = self.weather_table.next(self.weather, r);
+  }
+}
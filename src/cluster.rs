@@ -0,0 +1,87 @@
+//! 近くにあるオブジェクト同士を繋いでできる塊の検出
+
+use crate::grid::SpatialGrid;
+use crate::{Context, EventContents, ObjectType};
+use num_bigint::BigUint;
+use rustc_hash::FxHashMap;
+
+/// 要素の集合を併合していくための素集合データ構造
+struct UnionFind {
+  parent: Vec<usize>,
+}
+
+impl UnionFind {
+  fn new(len: usize) -> Self {
+    UnionFind {
+      parent: (0..len).collect(),
+    }
+  }
+
+  fn find(&mut self, i: usize) -> usize {
+    let mut root = i;
+    while self.parent[root] != root {
+      root = self.parent[root];
+    }
+    // 辿った要素を根に直接繋ぎ直して、次からの探索を短くする
+    let mut i = i;
+    while self.parent[i] != root {
+      let next = self.parent[i];
+      self.parent[i] = root;
+      i = next;
+    }
+    root
+  }
+
+  fn union(&mut self, a: usize, b: usize) {
+    let (a, b) = (self.find(a), self.find(b));
+    if a != b {
+      self.parent[a.max(b)] = a.min(b);
+    }
+  }
+}
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// 距離が`adjacency`以下のオブジェクト同士を繋ぎ、繋がったオブジェクトの塊ごとにIDをまとめる
+  /// 直接は近くなくても、間にあるオブジェクトを通して繋がっていれば同じ塊になる
+  /// 塊の中のIDは辞書順に並び、塊は最も小さいIDの辞書順に並ぶ
+  /// 一辺が`adjacency`のセルに区切った索引を使い、近くのセルにあるオブジェクトとだけ距離を比べる
+  /// 親を持つオブジェクトは`Context::world_point`で求めた世界での位置で測る
+  /// 端と端がつながった世界では`Context::distance`と同じく端をまたいだ近い方で測る
+  pub fn connected_components(&self, adjacency: &BigUint) -> Vec<Vec<String>> {
+    let mut ids = self.objects.keys().collect::<Vec<_>>();
+    ids.sort();
+    let index = ids
+      .iter()
+      .enumerate()
+      .map(|(i, id)| (*id, i))
+      .collect::<FxHashMap<_, _>>();
+    let points = ids
+      .iter()
+      .map(|id| self.located_point(id, &self.objects[*id]))
+      .collect::<Vec<_>>();
+    let mut grid = SpatialGrid::new(adjacency.clone());
+    for (id, point) in ids.iter().zip(points.iter()) {
+      grid.insert((*id).clone(), point);
+    }
+    let squared_adjacency = adjacency * adjacency;
+    let mut sets = UnionFind::new(ids.len());
+    for (i, point) in points.iter().enumerate() {
+      for other in self.grid_candidates(&grid, point, adjacency) {
+        let j = index[other];
+        if i < j && self.squared_distance(&points[j], point) <= squared_adjacency {
+          sets.union(i, j);
+        }
+      }
+    }
+    let mut components = FxHashMap::<usize, Vec<String>>::default();
+    for (i, id) in ids.iter().enumerate() {
+      components
+        .entry(sets.find(i))
+        .or_default()
+        .push((*id).clone());
+    }
+    let mut components = components.into_values().collect::<Vec<_>>();
+    components.sort();
+    components
+  }
+}
@@ -0,0 +1,171 @@
+//! 世界を作るための設定をまとめたもの
+
+use crate::{Context, EventContents, EventOrder, MoveLimit, Object, ObjectType};
+use crate::{PopulationController, SpawnPlacement, Terrain, TerrainMask, Time, Torus};
+use num_bigint::BigUint;
+use num_traits::Zero;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rustc_hash::FxHashSet;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// 世界を作るための設定
+/// `serde`機能を有効にするとファイルに保存して共有できる
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SimConfig {
+  /// 開始時点で経過している単位時間
+  /// 開始前に生成されたことにしたいオブジェクトがある場合は、その生成時刻以上にする
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub start_time: BigUint,
+  /// 一日にかかる単位時間
+  pub one_day_of_time: BigUint,
+  /// 一年にかかる日数
+  pub one_year_of_day: BigUint,
+  /// 記憶しておけるイベントの数の上限
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub memory_capacity: Option<usize>,
+  /// オブジェクトの数を目標値に近づけるための制御器の設定
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub population: Option<PopulationConfig>,
+  /// 地形
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub terrain: Option<TerrainMask>,
+  /// generate関数が使う乱数の種の元
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub seed: u64,
+  /// 端と端がつながった世界の大きさ
+  /// `None`の場合は果てのない世界
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub torus: Option<Torus>,
+  /// 近くにあるオブジェクトを探すための索引のセルの一辺の長さ
+  /// `None`の場合は索引を作らない
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub spatial_cell_size: Option<BigUint>,
+  /// 生成先のセルが既に埋まっている場合の配置の設定
+  /// `None`の場合は埋まっていてもそのまま重ねて生成する
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub spawn_placement: Option<SpawnPlacement>,
+  /// generate関数が返したイベントを処理する順序
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub event_order: EventOrder,
+  /// `run`で不整合が起きた時に単位時間を進めずに失敗させるかどうか
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub strict: bool,
+  /// `ObjectType::max_move_per_tick`を超える移動の扱い
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub move_limit: MoveLimit,
+}
+
+/// オブジェクトの数を目標値に近づけるための制御器の設定
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PopulationConfig {
+  /// 目標とするオブジェクトの数
+  pub target: usize,
+  /// 比例ゲイン
+  pub kp: f64,
+  /// 積分ゲイン
+  pub ki: f64,
+  /// 微分ゲイン
+  pub kd: f64,
+}
+
+impl SimConfig {
+  /// 暦だけを決めた設定の新たな生成
+  /// 他の設定は無効になっている
+  pub fn new(one_day_of_time: BigUint, one_year_of_day: BigUint) -> Self {
+    SimConfig {
+      start_time: BigUint::zero(),
+      one_day_of_time,
+      one_year_of_day,
+      memory_capacity: None,
+      population: None,
+      terrain: None,
+      seed: 0,
+      torus: None,
+      spatial_cell_size: None,
+      spawn_placement: None,
+      event_order: EventOrder::Registration,
+      strict: false,
+      move_limit: MoveLimit::Reject,
+    }
+  }
+
+  /// 設定が正しいかどうかを確かめ、正しくない場合はその理由を返す
+  pub fn validate(&self) -> Result<(), String> {
+    if self.one_day_of_time.is_zero() {
+      return Err("one_day_of_time must be positive".to_string());
+    }
+    if self.one_year_of_day.is_zero() {
+      return Err("one_year_of_day must be positive".to_string());
+    }
+    if self
+      .spatial_cell_size
+      .as_ref()
+      .is_some_and(|size| size.is_zero())
+    {
+      return Err("spatial_cell_size must be positive".to_string());
+    }
+    if let Some(population) = &self.population {
+      if ![population.kp, population.ki, population.kd]
+        .iter()
+        .all(|gain| gain.is_finite())
+      {
+        return Err("population controller gains must be finite".to_string());
+      }
+    }
+    Ok(())
+  }
+
+  /// 暦と開始時刻以外の設定を既にある世界に反映する
+  /// 乱数生成器は`seed`から作り直される
+  pub fn apply<T: EventContents<U>, U: ObjectType>(&self, ctx: &mut Context<T, U>) {
+    ctx.memory_capacity = self.memory_capacity;
+    ctx.population_controller = self.population.as_ref().map(|population| {
+      PopulationController::new(
+        population.target,
+        population.kp,
+        population.ki,
+        population.kd,
+      )
+    });
+    ctx.terrain = self.terrain.clone().map(Terrain::Mask);
+    ctx.seed = self.seed;
+    ctx.rng = StdRng::seed_from_u64(self.seed);
+    ctx.set_torus(self.torus.clone());
+    ctx.set_spatial_index(self.spatial_cell_size.clone());
+    ctx.spawn_placement = self.spawn_placement.clone();
+    ctx.event_order = self.event_order;
+    ctx.strict = self.strict;
+    ctx.move_limit = self.move_limit;
+  }
+}
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// 設定と最初に存在するオブジェクトから世界を作る
+  /// 設定が正しくない場合や、オブジェクトのIDが重複している場合はその理由を返す
+  pub fn from_config(
+    config: SimConfig,
+    initial_objects: impl IntoIterator<Item = (String, Object<U>)>,
+  ) -> Result<Self, String> {
+    config.validate()?;
+    let mut ids = FxHashSet::default();
+    let mut objects = Vec::new();
+    for (id, object) in initial_objects {
+      if !ids.insert(id.clone()) {
+        return Err(format!("object id {id} is duplicated"));
+      }
+      objects.push((id, object));
+    }
+    let time = Time::new(
+      config.start_time.clone(),
+      config.one_day_of_time.clone(),
+      config.one_year_of_day.clone(),
+    );
+    let mut ctx = Context::new(time, objects);
+    config.apply(&mut ctx);
+    Ok(ctx)
+  }
+}
@@ -0,0 +1,102 @@
+//! 単位時間を進める途中で起きた不整合
+
+use num_bigint::BigUint;
+use std::fmt;
+
+/// 単位時間を進める途中で起きた不整合の一つ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunError {
+  /// イベントが検査で弾かれた
+  RejectedEvent {
+    /// イベントを発生させた主体のオブジェクトのID
+    do_object: String,
+    /// 弾かれた理由
+    reason: String,
+  },
+  /// オブジェクトの生成が拒否された
+  RejectedObject {
+    /// オブジェクトの種類の名前
+    name: String,
+    /// 拒否された理由
+    reason: String,
+  },
+  /// 削除の対象のオブジェクトが存在しなかった
+  RemoveTargetMissing {
+    /// 対象のオブジェクトのID
+    id: String,
+  },
+  /// 移動の対象のオブジェクトが存在しなかった
+  MoveTargetMissing {
+    /// 対象のオブジェクトのID
+    id: String,
+  },
+  /// 一回の単位時間に移動できる距離の上限を超えた移動が拒否された
+  MoveTooFar {
+    /// 対象のオブジェクトのID
+    id: String,
+    /// 移動しようとした距離の小数点以下を切り捨てたもの
+    distance: BigUint,
+    /// 移動できる距離の上限
+    limit: BigUint,
+  },
+  /// 中身の置き換えや部分的な変更の対象のオブジェクトが存在しなかった
+  UpdateTargetMissing {
+    /// 対象のオブジェクトのID
+    id: String,
+  },
+  /// 生成したオブジェクトのIDが既存のオブジェクトと衝突した
+  DuplicateId {
+    /// 衝突したID
+    id: String,
+  },
+}
+
+impl fmt::Display for RunError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      RunError::RejectedEvent { do_object, reason } => {
+        write!(f, "event by {do_object} was rejected: {reason}")
+      }
+      RunError::RejectedObject { name, reason } => {
+        write!(f, "object {name} was rejected: {reason}")
+      }
+      RunError::RemoveTargetMissing { id } => {
+        write!(f, "object {id} to remove does not exist")
+      }
+      RunError::MoveTargetMissing { id } => write!(f, "object {id} to move does not exist"),
+      RunError::MoveTooFar {
+        id,
+        distance,
+        limit,
+      } => write!(
+        f,
+        "object {id} tried to move {distance} but can move at most {limit} per tick"
+      ),
+      RunError::UpdateTargetMissing { id } => {
+        write!(f, "object {id} to update does not exist")
+      }
+      RunError::DuplicateId { id } => write!(f, "object id {id} is already used"),
+    }
+  }
+}
+
+impl std::error::Error for RunError {}
+
+/// 一回の単位時間で起きた全ての不整合
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TickError {
+  /// 起きた順に並んだ不整合
+  pub errors: Vec<RunError>,
+}
+
+impl fmt::Display for TickError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} error(s) occurred in the tick", self.errors.len())?;
+    for error in self.errors.iter() {
+      write!(f, "\n- {error}")?;
+    }
+    Ok(())
+  }
+}
+
+impl std::error::Error for TickError {}
@@ -0,0 +1,147 @@
+//! 世界が使っているメモリ量の見積もり
+
+use crate::{
+  Context, Event, EventContents, InjectedEvent, Object, ObjectType, Point, RejectedEvent,
+  RejectedObject, Time, WatchRecord,
+};
+use num_bigint::BigUint;
+use std::mem::size_of;
+use std::sync::Arc;
+
+/// 世界が使っているメモリ量の見積もりの内訳（バイト数）
+/// ヒープ上の確保量は長さや桁数から概算したものなので、実際の値とはずれる
+/// `T`や`U`が自身で確保しているヒープ領域は数えない
+/// `fork`した世界同士で共有しているオブジェクトやイベントは、それぞれの世界で数える
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MemoryUsage {
+  /// 時刻
+  pub time: usize,
+  /// 存在するオブジェクト
+  pub objects: usize,
+  /// 記憶されているイベント
+  pub memory: usize,
+  /// 弾かれたイベントと拒否されたオブジェクトの記録
+  pub rejected: usize,
+  /// 注目しているオブジェクトとその記録
+  pub watch: usize,
+  /// 予約されたイベントと差し込みの記録
+  pub injected: usize,
+}
+
+impl MemoryUsage {
+  /// 全体の見積もり
+  pub fn total(&self) -> usize {
+    self.time + self.objects + self.memory + self.rejected + self.watch + self.injected
+  }
+}
+
+/// `Arc`が中身と一緒に確保する参照カウント
+const RC_COUNTERS: usize = 2 * size_of::<usize>();
+
+/// 多倍長整数が確保しているヒープ領域
+fn biguint_heap(n: &BigUint) -> usize {
+  n.bits().div_ceil(64) as usize * size_of::<u64>()
+}
+
+fn time_heap(time: &Time) -> usize {
+  [
+    &time.all,
+    &time.one_day_of_time,
+    &time.day,
+    &time.remainder_time,
+    &time.one_year_of_day,
+    &time.year,
+    &time.remainder_day,
+  ]
+  .into_iter()
+  .chain(&time.day_periods)
+  .map(biguint_heap)
+  .sum::<usize>()
+    + time.day_periods.capacity() * size_of::<BigUint>()
+}
+
+fn point_heap(point: &Point) -> usize {
+  biguint_heap(&point.x) + biguint_heap(&point.y)
+}
+
+fn event_heap<T>(event: &Event<T>) -> usize {
+  time_heap(&event.generated_time)
+    + event.lifetime.as_ref().map_or(0, time_heap)
+    + event.do_object.capacity()
+    + event.target_object.as_ref().map_or(0, String::capacity)
+    + event.location.as_ref().map_or(0, point_heap)
+    + event.affected_objects.capacity() * size_of::<String>()
+    + event
+      .affected_objects
+      .iter()
+      .map(String::capacity)
+      .sum::<usize>()
+}
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// 世界が使っているメモリ量のおおよそのバイト数
+  pub fn estimated_memory_bytes(&self) -> usize {
+    self.memory_usage().total()
+  }
+
+  /// 世界が使っているメモリ量の見積もりを種類ごとに分けたもの
+  /// どこがメモリを多く使っているのかを調べるのに使う
+  pub fn memory_usage(&self) -> MemoryUsage {
+    // ハッシュマップは要素ごとに1バイトの制御情報を持つ
+    let objects = self.objects.capacity() * (size_of::<(String, Arc<Object<U>>)>() + 1)
+      + self
+        .objects
+        .iter()
+        .map(|(id, object)| {
+          RC_COUNTERS
+            + size_of::<Object<U>>()
+            + id.capacity()
+            + time_heap(&object.generated_time)
+            + time_heap(&object.last_modified)
+            + point_heap(&object.point)
+        })
+        .sum::<usize>();
+    let memory = self.memory.capacity() * size_of::<Arc<Event<T>>>()
+      + self
+        .memory
+        .iter()
+        .map(|event| RC_COUNTERS + size_of::<Event<T>>() + event_heap(event))
+        .sum::<usize>();
+    let rejected = self.rejected_events.capacity() * size_of::<RejectedEvent<T>>()
+      + self
+        .rejected_events
+        .iter()
+        .map(|rejected| time_heap(&rejected.time) + rejected.reason.capacity())
+        .sum::<usize>()
+      + self.rejected_objects.capacity() * size_of::<RejectedObject<U>>()
+      + self
+        .rejected_objects
+        .iter()
+        .map(|rejected| time_heap(&rejected.time) + rejected.reason.capacity())
+        .sum::<usize>();
+    let watch = self.watched.capacity() * (size_of::<String>() + 1)
+      + self.watched.iter().map(String::capacity).sum::<usize>()
+      + self.watch_log.capacity() * size_of::<WatchRecord<T>>()
+      + self
+        .watch_log
+        .iter()
+        .map(|record| time_heap(&record.time) + record.object_id.capacity())
+        .sum::<usize>();
+    let injected = (self.scheduled_events.capacity() + self.injection_log.capacity())
+      * size_of::<InjectedEvent<T>>()
+      + self
+        .scheduled_events
+        .iter()
+        .chain(self.injection_log.iter())
+        .map(|injected| time_heap(&injected.injected_at) + time_heap(&injected.fire_at))
+        .sum::<usize>();
+    MemoryUsage {
+      time: size_of::<Time>() + time_heap(&self.time),
+      objects,
+      memory,
+      rejected,
+      watch,
+      injected,
+    }
+  }
+}
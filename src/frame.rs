@@ -0,0 +1,119 @@
+//! 親のオブジェクトに付随して動くオブジェクトの座標
+
+use crate::{Context, EventContents, Object, ObjectType, Point};
+use rustc_hash::FxHashSet;
+use std::borrow::Cow;
+use std::sync::Arc;
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// オブジェクトの世界での絶対座標
+  /// `parent_frame`を辿り、親の座標を順に足し合わせる
+  /// 端と端がつながった世界では足し合わせた座標を範囲内に折り返す
+  /// オブジェクトか辿った先の親が存在しない場合や、親を辿ると循環している場合は`None`を返す
+  pub fn world_point(&self, id: &str) -> Option<Point> {
+    let mut object = self.objects.get(id)?;
+    let mut x = object.point.x.clone();
+    let mut y = object.point.y.clone();
+    let mut visited = FxHashSet::default();
+    visited.insert(id);
+    while let Some(parent_id) = &object.parent_frame {
+      if !visited.insert(parent_id.as_str()) {
+        return None;
+      }
+      object = self.objects.get(parent_id)?;
+      x += &object.point.x;
+      y += &object.point.y;
+    }
+    Some(self.wrap_point(Point { x, y }))
+  }
+
+  /// 近さや重なりを調べるのに使う、オブジェクトの世界での位置
+  /// 親を持たないオブジェクトは`point`そのもので、親を辿れない場合も`point`を使う
+  pub(crate) fn located_point<'a>(&self, id: &str, object: &'a Object<U>) -> Cow<'a, Point> {
+    match &object.parent_frame {
+      None => Cow::Borrowed(&object.point),
+      Some(_) => self
+        .world_point(id)
+        .map_or(Cow::Borrowed(&object.point), Cow::Owned),
+    }
+  }
+
+  /// オブジェクトの座標の基準にする親を付け替える
+  /// `point`は書き換えないので、新しい親からの相対座標として扱われ、世界での位置はそれに合わせて変わる
+  /// `None`の場合は親から外し、`point`をそのまま世界での座標とする
+  /// オブジェクトか親が存在しない場合や、付け替えると親を辿って循環する場合は何もせずに理由を返す
+  pub fn set_parent_frame(&mut self, id: &str, parent: Option<&str>) -> Result<(), String> {
+    if !self.objects.contains_key(id) {
+      return Err(format!("object {id} does not exist"));
+    }
+    if let Some(parent) = parent {
+      if !self.objects.contains_key(parent) {
+        return Err(format!("parent object {parent} does not exist"));
+      }
+      // 新しい親から辿って自分に戻ってくるなら循環する
+      let mut visited = FxHashSet::default();
+      let mut current = Some(parent);
+      while let Some(ancestor) = current {
+        if ancestor == id {
+          return Err(format!(
+            "setting {parent} as the parent of {id} makes a cycle"
+          ));
+        }
+        if !visited.insert(ancestor) {
+          break;
+        }
+        current = self
+          .objects
+          .get(ancestor)
+          .and_then(|object| object.parent_frame.as_deref());
+      }
+    }
+    let Some(object) = self.objects.get_mut(id) else {
+      return Ok(());
+    };
+    if object.parent_frame.as_deref() == parent {
+      return Ok(());
+    }
+    let object = Arc::make_mut(object);
+    object.parent_frame = parent.map(str::to_string);
+    object.last_modified = self.time.clone();
+    self.spatial_update(id);
+    self.spatial_update_framed();
+    Ok(())
+  }
+
+  /// 親を辿ると循環しているオブジェクトのID
+  /// IDの辞書順に並ぶ
+  pub fn frame_cycles(&self) -> Vec<String> {
+    let mut ids = self
+      .objects
+      .iter()
+      .filter(|(_, object)| object.parent_frame.is_some())
+      .filter(|(id, _)| self.in_frame_cycle(id))
+      .map(|(id, _)| id.clone())
+      .collect::<Vec<_>>();
+    ids.sort_unstable();
+    ids
+  }
+
+  /// 親を辿ると自分自身に戻ってくるかどうか
+  fn in_frame_cycle(&self, id: &str) -> bool {
+    let mut visited = FxHashSet::default();
+    let mut current = id;
+    while let Some(parent_id) = self
+      .objects
+      .get(current)
+      .and_then(|object| object.parent_frame.as_deref())
+    {
+      if parent_id == id {
+        return true;
+      }
+      if !visited.insert(parent_id) {
+        // 自分を含まない循環に入った
+        return false;
+      }
+      current = parent_id;
+    }
+    false
+  }
+}
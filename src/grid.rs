@@ -0,0 +1,145 @@
+//! オブジェクトを一定の大きさのセルに振り分けて、近くにあるものだけを素早く探すための索引
+
+use crate::Point;
+use num_bigint::BigUint;
+use num_traits::identities::{One, Zero};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+/// 平面を一辺が`cell_size`の正方形のセルに区切り、セルごとにそこにあるオブジェクトのIDを保持する
+#[derive(Debug, Clone)]
+pub(crate) struct SpatialGrid {
+  /// セルの一辺の長さ
+  cell_size: BigUint,
+  /// セルの位置とそこにあるオブジェクトのID
+  cells: FxHashMap<(BigUint, BigUint), Vec<String>>,
+  /// オブジェクトのIDとそれがあるセルの位置
+  positions: FxHashMap<String, (BigUint, BigUint)>,
+  /// 親を持つため、親が動くと位置が変わるオブジェクトのID
+  framed: FxHashSet<String>,
+}
+
+impl SpatialGrid {
+  /// 空の索引の新たな生成
+  /// `cell_size`が0の場合は1として扱う
+  pub(crate) fn new(cell_size: BigUint) -> Self {
+    let cell_size = if cell_size.is_zero() {
+      BigUint::one()
+    } else {
+      cell_size
+    };
+    SpatialGrid {
+      cell_size,
+      cells: FxHashMap::default(),
+      positions: FxHashMap::default(),
+      framed: FxHashSet::default(),
+    }
+  }
+
+  /// セルの一辺の長さ
+  pub(crate) fn cell_size(&self) -> &BigUint {
+    &self.cell_size
+  }
+
+  /// 地点が含まれるセルの位置
+  fn cell_of(&self, point: &Point) -> (BigUint, BigUint) {
+    (&point.x / &self.cell_size, &point.y / &self.cell_size)
+  }
+
+  /// オブジェクトを`point`を含むセルに置く
+  /// 既に索引にある場合は移し、同じセルの中での移動なら何もしない
+  pub(crate) fn insert(&mut self, id: String, point: &Point) {
+    let cell = self.cell_of(point);
+    match self.positions.get(&id) {
+      Some(old) if *old == cell => return,
+      Some(_) => self.remove(&id),
+      None => {}
+    }
+    self.cells.entry(cell.clone()).or_default().push(id.clone());
+    self.positions.insert(id, cell);
+  }
+
+  /// オブジェクトを索引から取り除く
+  pub(crate) fn remove(&mut self, id: &str) {
+    self.framed.remove(id);
+    let Some(cell) = self.positions.remove(id) else {
+      return;
+    };
+    if let Some(ids) = self.cells.get_mut(&cell) {
+      if let Some(index) = ids.iter().position(|other| other == id) {
+        ids.swap_remove(index);
+      }
+      if ids.is_empty() {
+        self.cells.remove(&cell);
+      }
+    }
+  }
+
+  /// オブジェクトが親を持つかどうかを記録する
+  pub(crate) fn set_framed(&mut self, id: &str, framed: bool) {
+    if framed {
+      self.framed.insert(id.to_string());
+    } else {
+      self.framed.remove(id);
+    }
+  }
+
+  /// 親を持つオブジェクトのID
+  pub(crate) fn framed(&self) -> &FxHashSet<String> {
+    &self.framed
+  }
+
+  /// `center`を中心とする一辺`2 * radius`の正方形と重なるセルにあるオブジェクトのIDを全て返す
+  /// 円の範囲内にあるかどうかは呼び出し側で確かめる必要がある
+  pub(crate) fn candidates(&self, center: &Point, radius: &BigUint) -> Vec<&String> {
+    let min = Point {
+      x: if &center.x > radius {
+        &center.x - radius
+      } else {
+        BigUint::zero()
+      },
+      y: if &center.y > radius {
+        &center.y - radius
+      } else {
+        BigUint::zero()
+      },
+    };
+    let max = Point {
+      x: &center.x + radius,
+      y: &center.y + radius,
+    };
+    self.candidates_in_rect(&min, &max)
+  }
+
+  /// `min`と`max`を対角とする矩形（境界を含む）と重なるセルにあるオブジェクトのIDを全て返す
+  /// 矩形に含まれるセルがオブジェクトのあるセルより多い場合は、オブジェクトのあるセルだけを調べる
+  pub(crate) fn candidates_in_rect(&self, min: &Point, max: &Point) -> Vec<&String> {
+    let (min_x, min_y) = self.cell_of(min);
+    let (max_x, max_y) = self.cell_of(max);
+    let mut ids = Vec::new();
+    if max_x < min_x || max_y < min_y {
+      return ids;
+    }
+    let width = &max_x + 1u32 - &min_x;
+    let height = &max_y + 1u32 - &min_y;
+    if width * height > BigUint::from(self.cells.len()) {
+      for ((x, y), cell) in &self.cells {
+        if (&min_x..=&max_x).contains(&x) && (&min_y..=&max_y).contains(&y) {
+          ids.extend(cell.iter());
+        }
+      }
+      return ids;
+    }
+    let mut x = min_x;
+    while x <= max_x {
+      let mut y = min_y.clone();
+      while y <= max_y {
+        if let Some(cell) = self.cells.get(&(x.clone(), y.clone())) {
+          ids.extend(cell.iter());
+        }
+        y += 1u32;
+      }
+      x += 1u32;
+    }
+    ids
+  }
+}
@@ -0,0 +1,60 @@
+//! 外部から時刻を指定してイベントを差し込む仕組み
+
+use crate::{Context, EventContents, ObjectType, Time};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// 外部から差し込まれたイベント
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct InjectedEvent<T> {
+  /// 差し込まれた時点の時刻
+  pub injected_at: Time,
+  /// イベントを起こす時刻
+  pub fire_at: Time,
+  /// イベントの中身
+  pub contents: T,
+}
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// 指定した時刻にイベントを起こすよう予約する
+  /// 予約されたイベントはその時刻の`run`でgenerate関数の結果より先に集められ、
+  /// 検査・記憶・注目の記録など通常のイベントと同じ扱いを受ける
+  /// 指定した時刻が現在時刻より後でない場合は何もせずにイベントを返す
+  ///
+  /// 予約は全て`injection_log`にも記録される
+  /// 同じ初期状態に記録を順に`inject_at`し直して`run`すれば、同じ結果を再現できる
+  pub fn inject_at(&mut self, time: Time, event: T) -> Result<(), T> {
+    if time.all <= self.time.all {
+      return Err(event);
+    }
+    let injected = InjectedEvent {
+      injected_at: self.time.clone(),
+      fire_at: time,
+      contents: event,
+    };
+    self.injection_log.push(injected.clone());
+    self.scheduled_events.push(injected);
+    Ok(())
+  }
+
+  /// 現在時刻までに起こすべき予約イベントを予約した順に取り出す
+  pub(crate) fn take_due_events(&mut self) -> Vec<T> {
+    let now = &self.time.all;
+    if let Some(undo) = &mut self.undo {
+      if undo.scheduled_events.is_none()
+        && self
+          .scheduled_events
+          .iter()
+          .any(|injected| &injected.fire_at.all <= now)
+      {
+        undo.scheduled_events = Some(self.scheduled_events.clone());
+      }
+    }
+    let (due, pending) = std::mem::take(&mut self.scheduled_events)
+      .into_iter()
+      .partition::<Vec<_>, _>(|injected| &injected.fire_at.all <= now);
+    self.scheduled_events = pending;
+    due.into_iter().map(|injected| injected.contents).collect()
+  }
+}
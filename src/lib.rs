@@ -22,37 +22,155 @@
 //!
 //! がある
 
+#[cfg(feature = "bevy")]
+pub mod bevy;
+#[cfg(feature = "compression")]
+mod checkpoint;
+mod chronicle;
+mod climate;
+mod cluster;
+mod config;
+mod error;
+mod footprint;
+mod frame;
+mod grid;
+mod inject;
+mod metrics;
+#[cfg(feature = "serde")]
+mod migrate;
+mod name_index;
+mod noise;
+mod order;
+#[cfg(feature = "rayon")]
+mod par;
+mod path;
+mod phase;
+mod placement;
+mod seed;
+mod sight;
+mod snapshot;
+mod spatial;
+mod speed;
+mod stats;
+#[cfg(feature = "tokio")]
+mod stream;
+mod system;
+mod terrain;
+mod timeline;
+mod torus;
+mod undo;
+mod velocity;
+#[cfg(feature = "serde")]
+mod viewer;
+mod watch;
+
+use grid::SpatialGrid;
 use num_bigint::BigUint;
-use num_traits::identities::One;
-use rustc_hash::FxHashMap;
-use std::time::SystemTime;
+use num_traits::{NumRef, ToPrimitive, Unsigned};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rustc_hash::{FxHashMap, FxHashSet};
+use seed::as_generator;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fmt::Write as _;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use velocity::VelocityBatch;
+
+pub use chronicle::{ChronicleConfig, ChronicleEntry, Chronicler};
+pub use climate::{Season, Weather, WeatherTable};
+pub use config::{PopulationConfig, SimConfig};
+pub use error::{RunError, TickError};
+pub use footprint::MemoryUsage;
+pub use inject::InjectedEvent;
+pub use metrics::TickMetrics;
+#[cfg(feature = "serde")]
+pub use migrate::{Migrate, SavedObject, Versioned, WorldSave, WorldState};
+pub use name_index::NameIndex;
+pub use order::EventOrder;
+#[cfg(feature = "rayon")]
+pub use par::par_run;
+pub use path::PathOptions;
+pub use phase::{run_phased, Phase};
+pub use placement::SpawnPlacement;
+pub use snapshot::{ContextDiff, ObjectChange, PointDelta, SnapshotDiff};
+pub use speed::MoveLimit;
+pub use stats::SpeciesStats;
+#[cfg(feature = "tokio")]
+pub use stream::{EventChannel, EventFilter};
+pub use system::{SystemEvent, SystemEventRecord};
+pub use terrain::{Terrain, TerrainMask};
+pub use timeline::Timeline;
+pub use torus::Torus;
+pub use undo::{InverseEffect, TickUndo};
+pub use watch::{WatchKind, WatchRecord};
+
+/// 時間を数えるのに使える符号なし整数型
+/// `BigUint`であれば上限を気にせずに数えられ、`u64`などの固定長の型であれば高速に数えられる
+/// ただし`Context`や`Object`が持つ時刻は常に`Time<BigUint>`であり、固定長の型が使えるのは
+/// 世界の外で単独に`Time`を使って計算する場合に限られる
+pub trait TimeInt: Unsigned + NumRef + Clone + Ord + ToPrimitive {}
+
+impl<N: Unsigned + NumRef + Clone + Ord + ToPrimitive> TimeInt for N {}
+
+/// 座標に使える符号なし整数型
+/// `BigUint`であれば上限を気にせずに扱え、`u64`であればヒープを使わずに高速に計算できる
+/// ただし世界の中のオブジェクトやイベントの地点、問い合わせの引数は常に`Point<BigUint>`であり、
+/// `u64`が使えるのは世界の外で単独に`Point`を使って計算する場合に限られる
+pub trait WorldInt: TimeInt + fmt::Debug + std::hash::Hash {
+  /// 平方根の小数点以下を切り捨てたもの
+  fn isqrt(&self) -> Self;
+}
+
+impl WorldInt for BigUint {
+  fn isqrt(&self) -> Self {
+    self.sqrt()
+  }
+}
+
+impl WorldInt for u64 {
+  fn isqrt(&self) -> Self {
+    u64::isqrt(*self)
+  }
+}
 
 /// 時間に関するデータ
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Time {
+/// 時間を数える型は`N`で選べるが、世界の中の時刻には`BigUint`が使われる
+/// 固定長の型を使った場合、その範囲を超えるまで時間を進めると溢れる
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Time<N = BigUint> {
   /// 単位時間がどれくらいたったのかを計算する
-  all: BigUint,
+  all: N,
   /// 一日にかかる単位時間
-  one_day_of_time: BigUint,
+  one_day_of_time: N,
   /// 何日目か
-  day: BigUint,
+  day: N,
   /// 一日に満たない余りの単位時間数
-  remainder_time: BigUint,
+  remainder_time: N,
   /// 一年にかかる日数
-  one_year_of_day: BigUint,
+  one_year_of_day: N,
   /// 何年目か
-  year: BigUint,
+  year: N,
   /// 一年に満たない余りの日数
-  remainder_day: BigUint,
+  remainder_day: N,
+  /// 日と年の間に週や月などの単位を挟む場合の、日から年までの各段の周期
+  /// 小さい単位から順に並び、全ての積が`one_year_of_day`になる
+  /// 空の場合は日の次が年になる
+  #[cfg_attr(feature = "serde", serde(default))]
+  day_periods: Vec<N>,
 }
 
-impl Time {
+impl<N: TimeInt> Time<N> {
   /// 時間の新たな生成
-  pub fn new(all: BigUint, one_day_of_time: BigUint, one_year_of_day: BigUint) -> Self {
-    let day = &all / &one_day_of_time;
-    let remainder_time = &all % &one_day_of_time;
-    let year = &day / &one_year_of_day;
-    let remainder_day = &all % &one_year_of_day;
+  pub fn new(all: N, one_day_of_time: N, one_year_of_day: N) -> Self {
+    let day = all.clone() / &one_day_of_time;
+    let remainder_time = all.clone() % &one_day_of_time;
+    let year = day.clone() / &one_year_of_day;
+    let remainder_day = day.clone() % &one_year_of_day;
     Time {
       all,
       one_day_of_time,
@@ -61,19 +179,130 @@ impl Time {
       one_year_of_day,
       year,
       remainder_day,
+      day_periods: Vec::new(),
+    }
+  }
+
+  /// 経過した単位時間が`0`の時間の新たな生成
+  ///
+  /// ```
+  /// use hakoniwa::Time;
+  /// use num_bigint::BigUint;
+  ///
+  /// let time = Time::zero(BigUint::from(24u32), BigUint::from(365u32));
+  /// assert_eq!(*time.all(), BigUint::from(0u32));
+  /// assert_eq!(*time.day(), BigUint::from(0u32));
+  /// assert_eq!(*time.year(), BigUint::from(0u32));
+  /// ```
+  pub fn zero(one_day_of_time: N, one_year_of_day: N) -> Self {
+    Time::new(N::zero(), one_day_of_time, one_year_of_day)
+  }
+
+  /// 各段の周期を指定した階層を持つ時間の新たな生成
+  /// `periods[0]`が一日にかかる単位時間、それ以降が日から年までの各段の周期で、
+  /// 例えば単位時間を一時間とし`[24, 7, 4, 12]`とすれば「時・日・週・月・年」の暦になる
+  /// `periods`が二つ以下の場合は`Time::new`と同じになる
+  ///
+  /// `periods`が空の場合はパニックする
+  pub fn with_periods(all: N, periods: Vec<N>) -> Self {
+    let mut periods = periods.into_iter();
+    let one_day_of_time = periods
+      .next()
+      .expect("periods must contain at least the length of a day");
+    let day_periods = periods.collect::<Vec<_>>();
+    let one_year_of_day = day_periods
+      .iter()
+      .fold(N::one(), |product, period| product * period);
+    let mut time = Time::new(all, one_day_of_time, one_year_of_day);
+    if day_periods.len() > 1 {
+      time.day_periods = day_periods;
+    }
+    time
+  }
+
+  /// 経過した単位時間の合計
+  pub fn all(&self) -> &N {
+    &self.all
+  }
+
+  /// 何日目か
+  pub fn day(&self) -> &N {
+    &self.day
+  }
+
+  /// 何年目か
+  pub fn year(&self) -> &N {
+    &self.year
+  }
+
+  /// 一日に満たない余りの単位時間数
+  pub fn time_of_day(&self) -> &N {
+    &self.remainder_time
+  }
+
+  /// 一年に満たない余りの日数
+  pub fn day_of_year(&self) -> &N {
+    &self.remainder_day
+  }
+
+  /// 経過した単位時間だけを比べる
+  /// 暦の規則が異なっていても、経過した単位時間が同じなら`Equal`になる
+  pub fn cmp_ticks(&self, other: &Self) -> std::cmp::Ordering {
+    self.all.cmp(&other.all)
+  }
+
+  /// `earlier`から経過した単位時間
+  /// `earlier`の方が後の場合は`None`を返す
+  pub fn duration_since(&self, earlier: &Self) -> Option<N> {
+    (self.all >= earlier.all).then(|| self.all.clone() - &earlier.all)
+  }
+
+  /// 経過した単位時間の差
+  /// `other`の方が後の場合は`0`になる
+  pub fn saturating_sub(&self, other: &Self) -> N {
+    self.duration_since(other).unwrap_or_else(N::zero)
+  }
+
+  /// 階層の段の数
+  /// 一日に満たない単位時間の段と年の段を含む
+  pub fn levels(&self) -> usize {
+    self.day_periods.len().max(1) + 2
+  }
+
+  /// `level`段目の単位の現在値
+  /// `0`段目は一日に満たない余りの単位時間、最後の段は何年目かで、その間は日から年までの各段の一つ上の単位に満たない余り
+  /// 例えば`Time::new`で作った時間では`0`が余りの単位時間、`1`が余りの日数、`2`が何年目かになる
+  /// `level`が段の数以上の場合は`None`を返す
+  pub fn unit(&self, level: usize) -> Option<N> {
+    let last = self.levels() - 1;
+    if level == 0 {
+      return Some(self.remainder_time.clone());
     }
+    if level == last {
+      return Some(self.year.clone());
+    }
+    if level > last {
+      return None;
+    }
+    if self.day_periods.is_empty() {
+      return Some(self.remainder_day.clone());
+    }
+    let below = self.day_periods[..level - 1]
+      .iter()
+      .fold(N::one(), |product, period| product * period);
+    Some((self.remainder_day.clone() / &below) % &self.day_periods[level - 1])
   }
 
   /// 時間を任意の量進める
-  pub fn plus(&mut self, time: BigUint) {
-    let all = &self.all + &time;
-    let new_remainder_time = &self.remainder_time + &time;
-    let plus_day = &new_remainder_time / &self.one_day_of_time;
-    let day = &self.day + &plus_day;
-    let remainder_time = &new_remainder_time % &self.one_day_of_time;
-    let new_remainder_day = &self.remainder_day + &plus_day;
-    let year = &self.year + (&new_remainder_day / &self.one_year_of_day);
-    let remainder_day = &new_remainder_day % &self.one_year_of_day;
+  pub fn plus(&mut self, time: N) {
+    let all = self.all.clone() + &time;
+    let new_remainder_time = self.remainder_time.clone() + &time;
+    let plus_day = new_remainder_time.clone() / &self.one_day_of_time;
+    let day = self.day.clone() + &plus_day;
+    let remainder_time = new_remainder_time % &self.one_day_of_time;
+    let new_remainder_day = self.remainder_day.clone() + &plus_day;
+    let year = self.year.clone() + &(new_remainder_day.clone() / &self.one_year_of_day);
+    let remainder_day = new_remainder_day % &self.one_year_of_day;
     *self = Time {
       all,
       day,
@@ -81,82 +310,515 @@ impl Time {
       year,
       remainder_day,
       ..self.clone()
-    }
+    };
+    debug_assert!(self.is_consistent());
   }
 
   /// 時間を一単位時間進める
   pub fn plus_one(&mut self) {
-    self.plus(BigUint::one())
+    self.plus(N::one())
   }
 
-  /// 年や日数にかかる単位時間を変化させられる
-  pub fn change_rule(&mut self, one_day_of_time: BigUint, one_year_of_day: BigUint) {
-    let plus_day = &self.remainder_time / &one_day_of_time;
-    let day = &self.day + &plus_day;
-    let remainder_time = &self.remainder_time % &one_day_of_time;
-    let new_remainder_day = &self.remainder_day + &plus_day;
-    let year = &self.year + (&new_remainder_day / &one_year_of_day);
-    let remainder_day = new_remainder_day % &one_year_of_day;
+  /// 時間を任意の量戻す
+  /// 日数や年数などは`Time::new`と同じく経過した単位時間から計算し直される
+  /// 経過した単位時間より多く戻そうとした場合はpanicする
+  pub fn minus(&mut self, time: N) {
+    self
+      .checked_minus(time)
+      .expect("cannot rewind the time before zero");
+  }
+
+  /// 時間を任意の量戻す
+  /// 経過した単位時間より多く戻そうとした場合は何も変えずに`None`を返す
+  pub fn checked_minus(&mut self, time: N) -> Option<()> {
+    if time > self.all {
+      return None;
+    }
     *self = Time {
-      one_day_of_time,
-      day,
-      remainder_time,
-      one_year_of_day,
-      year,
-      remainder_day,
-      ..self.clone()
+      day_periods: self.day_periods.clone(),
+      ..Time::new(
+        self.all.clone() - &time,
+        self.one_day_of_time.clone(),
+        self.one_year_of_day.clone(),
+      )
+    };
+    Some(())
+  }
+
+  /// 年や日数にかかる単位時間を変化させられる
+  /// 経過した単位時間はそのままに、日数や年数は新しい規則で数え直される
+  /// 日と年の間の段は取り除かれる
+  pub fn change_rule(&mut self, one_day_of_time: N, one_year_of_day: N) {
+    *self = Time::new(self.all.clone(), one_day_of_time, one_year_of_day);
+    debug_assert!(self.is_consistent());
+  }
+
+  /// 日数や年数などの値が、経過した単位時間から`Time::new`で計算し直したものと一致しているかどうか
+  /// `plus`などの差分計算に誤りが無いかを確かめるために使う
+  pub fn is_consistent(&self) -> bool {
+    let rebuilt = Time {
+      day_periods: self.day_periods.clone(),
+      ..Time::new(
+        self.all.clone(),
+        self.one_day_of_time.clone(),
+        self.one_year_of_day.clone(),
+      )
+    };
+    *self == rebuilt
+  }
+
+  /// 経過した単位時間を`f64`に近似変換する
+  /// `f64`で表せないほど大きい場合は`f64::INFINITY`になる
+  pub fn as_ticks_f64(&self) -> f64 {
+    to_f64_saturating(&self.all)
+  }
+
+  /// 経過した日数を一日に満たない端数も含めて`f64`に近似変換する
+  /// `f64`で表せないほど大きい場合は`f64::INFINITY`になる
+  pub fn as_days_f64(&self) -> f64 {
+    to_f64_saturating(&self.day)
+      + to_f64_saturating(&self.remainder_time) / to_f64_saturating(&self.one_day_of_time)
+  }
+}
+
+/// 経過した単位時間`all`の順に並べる
+/// 暦の規則は前後関係に関わらず、経過した単位時間が同じで規則だけが異なる場合に限り、
+/// `Eq`と矛盾しないように規則の大小で順序を決める
+impl<N: Ord> Ord for Time<N> {
+  fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    self
+      .all
+      .cmp(&other.all)
+      .then_with(|| self.one_day_of_time.cmp(&other.one_day_of_time))
+      .then_with(|| self.one_year_of_day.cmp(&other.one_year_of_day))
+      .then_with(|| self.day_periods.cmp(&other.day_periods))
+  }
+}
+
+impl<N: Ord> PartialOrd for Time<N> {
+  fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+/// 経過した単位時間を文字列にする時の書式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeFormat {
+  /// 全ての桁をそのまま並べる（`1234567 ticks`）
+  Exact,
+  /// 3桁ごとに`,`で区切る（`1,234,567 ticks`）
+  Grouped,
+  /// 仮数部の小数点以下を`precision`桁にした指数表記（`1.23e6 ticks`）
+  /// 切り捨てられた桁は0に向かって丸められるので、表示される値は正確な値以下になる
+  Scientific {
+    /// 仮数部の小数点以下の桁数
+    precision: usize,
+  },
+}
+
+impl<N: TimeInt + fmt::Display> Time<N> {
+  /// 経過した単位時間を書式に従って文字列にする
+  pub fn format(&self, format: TimeFormat) -> String {
+    let digits = self.all.to_string();
+    let value = match format {
+      TimeFormat::Exact => digits,
+      TimeFormat::Grouped => group_digits(&digits),
+      TimeFormat::Scientific { precision } => {
+        let exponent = digits.len() - 1;
+        let (head, tail) = digits.split_at(1);
+        let mut fraction = tail.chars().take(precision).collect::<String>();
+        // 桁数が足りない場合は0で埋めて、常に指定した桁数を表示する
+        while fraction.len() < precision {
+          fraction.push('0');
+        }
+        if fraction.is_empty() {
+          format!("{head}e{exponent}")
+        } else {
+          format!("{head}.{fraction}e{exponent}")
+        }
+      }
+    };
+    format!("{value} ticks")
+  }
+}
+
+/// 何年目か・一年に満たない余りの日数・一日に満たない余りの単位時間を並べる（`Year 3, Day 12, 7/24`）
+/// 余りの単位時間は一日にかかる単位時間を分母にした分数で表す
+/// 年と日は`0`から数える
+impl<N: TimeInt + fmt::Display> fmt::Display for Time<N> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "Year {}, Day {}, {}/{}",
+      self.year, self.remainder_day, self.remainder_time, self.one_day_of_time
+    )
+  }
+}
+
+/// 10進数の数字列を3桁ごとに`,`で区切る
+fn group_digits(digits: &str) -> String {
+  let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+  for (i, c) in digits.chars().enumerate() {
+    if i > 0 && (digits.len() - i).is_multiple_of(3) {
+      grouped.push(',');
     }
+    grouped.push(c);
   }
+  grouped
+}
+
+/// 整数を`f64`に変換する
+/// 精度が足りない分は丸められ、`f64`の範囲を超える場合は`f64::INFINITY`になる
+fn to_f64_saturating<N: ToPrimitive>(n: &N) -> f64 {
+  n.to_f64().unwrap_or(f64::INFINITY)
 }
 
 /// 地図上での「地点」を表す。
 /// どの座標系を採用しているかは実装者に任せるが、一応右手系を想定している
+/// 座標の型は`N`で選べ、世界の中のオブジェクトやイベントの地点には`BigUint`が使われる
+/// 固定長の型を使った場合、その範囲を超える座標や距離を計算すると溢れる
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct Point {
-  x: BigUint,
-  y: BigUint,
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Point<N = BigUint> {
+  x: N,
+  y: N,
+}
+
+impl<N: WorldInt> Point<N> {
+  /// 地点の新たな生成
+  ///
+  /// ```
+  /// use hakoniwa::Point;
+  /// use num_bigint::BigUint;
+  ///
+  /// let point = Point::new(BigUint::from(3u32), BigUint::from(4u32));
+  /// assert_eq!(*point.x(), BigUint::from(3u32));
+  /// assert_eq!(*point.y(), BigUint::from(4u32));
+  /// ```
+  pub fn new(x: N, y: N) -> Self {
+    Point { x, y }
+  }
+
+  /// x座標
+  pub fn x(&self) -> &N {
+    &self.x
+  }
+
+  /// y座標
+  pub fn y(&self) -> &N {
+    &self.y
+  }
+
+  /// `min`と`max`を対角とする矩形の範囲内（境界を含む）にあるかどうか
+  fn is_in_rect(&self, min: &Self, max: &Self) -> bool {
+    min.x <= self.x && self.x <= max.x && min.y <= self.y && self.y <= max.y
+  }
+
+  /// 二点間のマンハッタン距離
+  /// 符号なし整数は負にならないので、軸ごとに大きい方から小さい方を引いて足し合わせる
+  pub fn manhattan_distance(&self, other: &Self) -> N {
+    abs_diff(&self.x, &other.x) + abs_diff(&self.y, &other.y)
+  }
+
+  /// 二点間のユークリッド距離の二乗
+  /// 平方根を取らないので誤差がなく、距離を比べるだけなら半径の二乗と比べれば済む
+  pub fn squared_euclidean_distance(&self, other: &Self) -> N {
+    let dx = abs_diff(&self.x, &other.x);
+    let dy = abs_diff(&self.y, &other.y);
+    dx.clone() * &dx + dy.clone() * &dy
+  }
+
+  /// 二点間のユークリッド距離の小数点以下を切り捨てたもの
+  pub fn euclidean_distance(&self, other: &Self) -> N {
+    self.squared_euclidean_distance(other).isqrt()
+  }
+
+  /// 二点間のユークリッド距離を指定した方法で整数に丸めたもの
+  pub fn euclidean_distance_rounded(&self, other: &Self, rounding: Rounding) -> N {
+    let squared = self.squared_euclidean_distance(other);
+    let floor = squared.isqrt();
+    let floor_squared = floor.clone() * &floor;
+    let round_up = match rounding {
+      Rounding::Floor => false,
+      Rounding::Ceil => floor_squared < squared,
+      // (floor + 0.5)^2 = floor^2 + floor + 0.25 なので、整数の範囲では squared > floor^2 + floor のときに切り上がる
+      Rounding::Nearest => squared > floor_squared + &floor,
+    };
+    if round_up {
+      floor + N::one()
+    } else {
+      floor
+    }
+  }
+
+  /// 二点間を結ぶ線分が通るセルの列
+  /// ブレゼンハムのアルゴリズムで求め、`self`から`other`までの両端を含む
+  pub fn line_to(&self, other: &Self) -> Vec<Self> {
+    let dx = abs_diff(&self.x, &other.x);
+    let dy = abs_diff(&self.y, &other.y);
+    // 差の大きい方の軸を一つずつ進め、もう一方の軸は誤差が半セルを超えた時だけ進める
+    let x_major = dx >= dy;
+    let (major, minor) = if x_major { (&dx, &dy) } else { (&dy, &dx) };
+    let twice_major = major.clone() + major;
+    let twice_minor = minor.clone() + minor;
+    let mut error = major.clone();
+    let mut current = self.clone();
+    let mut cells = vec![current.clone()];
+    let mut step = N::zero();
+    while &step < major {
+      error = error + &twice_minor;
+      let minor_step = error >= twice_major;
+      if minor_step {
+        error = error - &twice_major;
+      }
+      let (step_x, step_y) = if x_major {
+        (true, minor_step)
+      } else {
+        (minor_step, true)
+      };
+      if step_x {
+        step_toward(&mut current.x, &other.x);
+      }
+      if step_y {
+        step_toward(&mut current.y, &other.y);
+      }
+      cells.push(current.clone());
+      step = step + N::one();
+    }
+    cells
+  }
+}
+
+/// 距離などを整数に丸める方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rounding {
+  /// 切り捨て
+  Floor,
+  /// 切り上げ
+  Ceil,
+  /// 四捨五入
+  Nearest,
+}
+
+/// `value`を`target`に向けて一つ進める
+fn step_toward<N: WorldInt>(value: &mut N, target: &N) {
+  if *value < *target {
+    *value = value.clone() + N::one();
+  } else if *value > *target {
+    *value = value.clone() - N::one();
+  }
+}
+
+/// 二つの符号なし整数の差の絶対値
+fn abs_diff<N: WorldInt>(a: &N, b: &N) -> N {
+  if a > b {
+    a.clone() - b
+  } else {
+    b.clone() - a
+  }
 }
 
 /// オブジェクトの種類やオブジェクトそのものの情報
-pub trait ObjectType: Clone {
+/// 中身の比較は、置き換えや部分的な変更が実際に中身を変えるかどうかを調べるのに使う
+pub trait ObjectType: Clone + PartialEq {
+  /// イベントが中身に加える部分的な変更
+  /// 使わない場合は`()`にすれば良い
+  type Patch: Clone;
   /// オブジェクトの種類の名前
   fn name(&self) -> String;
   /// そのオブジェクトが生み出された場所
   fn generated_point(&self) -> Point;
+  /// 一回の単位時間に移動できる距離の上限
+  /// 超える移動は`Context::move_limit`に従って扱われる
+  /// `None`の場合は制限しない
+  fn max_move_per_tick(&self) -> Option<BigUint> {
+    None
+  }
+  /// 一回の単位時間ごとに自動で動く量
+  /// 単位時間の始めに、イベントによる移動より先に移動として反映される
+  /// 負の方向へは`0`で止まり、端と端がつながった世界では折り返される
+  /// `None`の場合は動かない
+  fn velocity(&self) -> Option<(i64, i64)> {
+    None
+  }
+  /// イベントによる部分的な変更を中身に反映する
+  /// 同じ単位時間に同じオブジェクトへの変更が複数ある場合は、処理された順に全て反映される
+  /// 既定では何もしない
+  fn apply_patch(&mut self, _patch: Self::Patch) {}
+}
+
+/// 既存のオブジェクトを元にして、複製を別の場所に生み出せるオブジェクト
+pub trait Replicable: ObjectType {
+  /// そのオブジェクトが生み出される場所を設定する
+  fn set_generated_point(&mut self, point: Point);
+  /// 複製元のオブジェクトのIDを記録する
+  /// 親子関係を持たせたい場合に実装する
+  fn set_parent(&mut self, _parent_id: &str) {}
+}
+
+/// 年齢に応じて「幼体→成体→老体」のような段階を持つオブジェクト
+pub trait LifeStage: ObjectType {
+  /// 生成されてからの経過時間`age`における段階
+  /// `0`から始まり、`stage_count()`未満の値を返す
+  fn stage(&self, age: &BigUint) -> u32;
+  /// 段階の数
+  fn stage_count(&self) -> u32;
 }
 
 /// 世界に存在する「モノ」
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Object<T: ObjectType + ?Sized> {
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Object<T: ObjectType> {
   /// 生成時刻
+  /// 世界の開始時刻より前にもできるので、開始時点で歳をとったオブジェクトを置ける
   pub generated_time: Time,
+  /// 最後に移動したり中身が置き換えられたりした時刻
+  /// 変化がなかった単位時間では据え置かれる
+  pub last_modified: Time,
   /// 現在地
+  /// `parent_frame`がある場合は親の現在地からの相対座標
   pub point: Point,
   /// オブジェクトの種類
   pub object_type: T,
+  /// 座標の基準にする親のオブジェクトのID
+  /// 親が動くと、`point`を変えなくても世界での位置が一緒に動く
+  /// 世界での位置は`Context::world_point`で求める
+  /// 存在しているオブジェクトの親は`Context::set_parent_frame`で付け替える
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub parent_frame: Option<String>,
+  /// このオブジェクトを生成したイベントのID
+  /// イベントの効果以外で生成された場合は`None`
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub caused_by: Option<String>,
+}
+
+impl<T: ObjectType> Object<T> {
+  /// 生成から`now`までに経過した単位時間
+  /// 生成時刻が`now`より後の場合は`0`
+  pub fn age(&self, now: &Time) -> BigUint {
+    now.saturating_sub(&self.generated_time)
+  }
+
+  /// 生成から`now`までに経過した年数
+  /// 一年の長さは`now`の暦の規則で数え、一年に満たない端数は切り捨てる
+  pub fn age_years(&self, now: &Time) -> BigUint {
+    self.age(now) / (&now.one_day_of_time * &now.one_year_of_day)
+  }
+}
+
+/// イベントが世界に及ぼす効果
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Effect<U: ObjectType> {
+  /// オブジェクトを生成する
+  Spawn(U),
+  /// IDのオブジェクトを削除する
+  Remove(String),
+  /// IDのオブジェクトを地点に移動させる
+  Move(String, Point),
+  /// IDのオブジェクトの中身を置き換える
+  Update(String, U),
+  /// IDのオブジェクトの中身に`ObjectType::apply_patch`で部分的な変更を加える
+  Patch(String, U::Patch),
 }
 
 /// イベントを生成するために必要な情報
-pub trait EventContents: Clone {
-  // /// イベントの発生により生成されるオブジェクトがある場合はそのオブジェクトを返す
-  fn generate_object_opt(&self) -> Option<String>;
+/// - `U`は「オブジェクトの具体的な中身」
+pub trait EventContents<U: ObjectType>: Clone {
+  /// 使われていない
+  /// 生成するオブジェクトは`generate_objects`で返す
+  #[deprecated(note = "use `generate_objects` instead")]
+  fn generate_object_opt(&self) -> Option<String> {
+    None
+  }
+  /// イベントの発生により生成されるオブジェクト
+  /// 一つのイベントで複数のオブジェクトを生成できる
+  fn generate_objects(&self) -> Vec<U> {
+    Vec::new()
+  }
   /// イベントの発生により削除されるオブジェクトがある場合はそのID
   fn remove_object_opt(&self) -> Option<String>;
+  /// イベントの発生により削除されるオブジェクトのID
+  /// 一つのイベントで複数のオブジェクトを削除できる
+  /// 既定では`remove_object_opt`を使う
+  fn remove_objects(&self) -> Vec<String> {
+    self.remove_object_opt().into_iter().collect()
+  }
   /// オブジェクトを移動させる場合に発生する
   /// 対象のオブジェクトのIDと移動先の地点
   fn move_object_opt(&self) -> Option<(String, Point)>;
+  /// オブジェクトの中身に部分的な変更を加える場合に発生する
+  /// 対象のオブジェクトのIDと`ObjectType::apply_patch`に渡す変更
+  fn update_object_opt(&self) -> Option<(String, U::Patch)> {
+    None
+  }
   /// eventの寿命
+  /// 生成されてから寿命の分だけ経った単位時間までは記憶に残り、その次の単位時間に忘れられる
   /// Noneの場合は永久
   fn lifetime(&self) -> Option<Time>;
   /// イベントを発生させた主体のオブジェクトのID
   fn do_object(&self) -> String;
   /// オブジェクト間に起こるイベントの場合に、そのイベントの対象となったオブジェクトのID
   fn target_object_opt(&self) -> Option<String>;
+  /// イベントが起きた地点
+  /// `None`の場合はイベントを発生させた主体のオブジェクトの現在地が使われる
+  fn location(&self) -> Option<Point> {
+    None
+  }
+  /// 広い範囲に影響を及ぼすイベントの場合に、その範囲の中心と半径
+  /// 中心からの距離が半径以下のオブジェクトが影響を受ける
+  /// 範囲を持つイベントの`Effect::Update`と`Effect::Patch`は、指定したIDのオブジェクトではなく範囲内の全てのオブジェクトに加わる
+  /// 同じ単位時間に削除されるオブジェクトには加わらない
+  fn area_of_effect(&self) -> Option<(Point, BigUint)> {
+    None
+  }
+  /// 処理順を`EventOrder::Priority`にした場合の優先度
+  /// 大きいものほど先に処理される
+  fn priority(&self) -> i64 {
+    0
+  }
+  /// 出来事としての大きさ
+  /// 年表を記録している場合、閾値以上のイベントが年表に載る
+  fn magnitude(&self) -> f64 {
+    0.0
+  }
+  /// 年表に載せる時の概要
+  /// 既定では主体のオブジェクトのIDを使う
+  fn summary(&self) -> String {
+    format!("{}の出来事", self.do_object())
+  }
+  /// 統計の時系列でイベントを数える時の種別
+  fn kind(&self) -> String {
+    "event".to_string()
+  }
+  /// イベントが世界に及ぼす効果の列
+  /// 既定では`remove_objects`と`move_object_opt`と`update_object_opt`と`generate_objects`から作る
+  fn effects(&self) -> Vec<Effect<U>> {
+    let removes = self.remove_objects().into_iter().map(Effect::Remove);
+    let moves = self
+      .move_object_opt()
+      .map(|(id, point)| Effect::Move(id, point));
+    let patches = self
+      .update_object_opt()
+      .map(|(id, patch)| Effect::Patch(id, patch));
+    let spawns = self.generate_objects().into_iter().map(Effect::Spawn);
+    removes.chain(moves).chain(patches).chain(spawns).collect()
+  }
+  /// 寿命を迎えて忘れられる時に起こる情報
+  /// 忘れられた直後の世界を見て呼ばれ、結果はその単位時間のgenerate関数の結果と同じように反映される
+  /// 既定では何も起こさない
+  fn on_forget(&self, _ctx: &Context<Self, U>) -> GeneratedData<Self, U>
+  where
+    Self: Sized,
+  {
+    GeneratedData::default()
+  }
 }
 
 /// 起きるイベント
 #[derive(Debug, Clone)]
-pub struct Event<T: EventContents> {
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Event<T> {
   /// イベントが起きた時刻
   pub generated_time: Time,
   /// イベントの寿命
@@ -167,116 +829,1603 @@ pub struct Event<T: EventContents> {
   pub do_object: String,
   /// オブジェクト間に起こるイベントの場合に、そのイベントの対象となったオブジェクトのID
   pub target_object: Option<String>,
+  /// イベントが起きた地点
+  /// 地点が指定されておらず、主体のオブジェクトも存在しない場合は`None`
+  pub location: Option<Point>,
+  /// 範囲に影響を及ぼすイベントの場合に、イベントが起きた時点でその範囲内にいたオブジェクトのID
+  /// IDは辞書順に並ぶ
+  pub affected_objects: Vec<String>,
+  /// 記録された順に振られる通し番号
+  /// 単位時間をまたいでも増え続けるので、同じ時刻に起きたイベントも含めて前後関係が決まる
+  pub sequence: u64,
+  /// イベントのID
+  /// 主体のオブジェクトのIDと起きた単位時間と通し番号から作られるので、同じ世界から同じように進めれば同じIDになる
+  /// 他のイベントやログからイベントを指すのに使う
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub id: String,
 }
 
-/// 世界の状態を保持しているもの
+/// イベントが世界の法則に反していないかを検査する関数
+/// 反している場合はその理由を返す
+pub type EventValidator<T, U> = Arc<dyn Fn(&T, &Context<T, U>) -> Result<(), String> + Send + Sync>;
+
+/// 検査によって弾かれたイベント
 #[derive(Debug, Clone)]
-pub struct Context<T: EventContents, U: ObjectType> {
+pub struct RejectedEvent<T> {
+  /// 弾かれた時刻
+  pub time: Time,
+  /// 弾かれたイベントの中身
+  pub contents: T,
+  /// 弾かれた理由
+  pub reason: String,
+}
+
+/// 地形によって生成が拒否されたオブジェクト
+#[derive(Debug, Clone)]
+pub struct RejectedObject<U: ObjectType> {
+  /// 拒否された時刻
+  pub time: Time,
+  /// 生成されるはずだったオブジェクトの中身
+  pub object_type: U,
+  /// 拒否された理由
+  pub reason: String,
+}
+
+/// 世界の状態を保持しているもの
+#[derive(Clone)]
+pub struct Context<T: EventContents<U>, U: ObjectType> {
   /// 現在の時刻
   pub time: Time,
   /// 記憶されているイベント
-  pub memory: Vec<Event<T>>,
+  /// 古いものほど前にある
+  /// 記録された後のイベントは変化しないので、`fork`した世界同士で共有される
+  pub memory: VecDeque<Arc<Event<T>>>,
+  /// 記憶しておけるイベントの数の上限
+  /// 上限を超えると古いイベントから忘れる
+  /// `None`の場合は寿命を迎えるまで全て記憶する
+  pub memory_capacity: Option<usize>,
   /// 現在存在する全てのオブジェクト
-  pub objects: FxHashMap<String, Object<U>>,
+  /// `fork`した世界同士で共有され、書き換える時に初めて複製される
+  /// 書き換える場合は`Arc::make_mut`を使う
+  /// 直接追加・削除・移動した場合は`rebuild_name_index`と`rebuild_spatial_index`で索引を作り直す
+  /// 走査する順序は追加や削除の履歴によって変わるので、順序に結果が左右される場合は`objects_sorted`を使う
+  pub objects: FxHashMap<String, Arc<Object<U>>>,
+  /// オブジェクトの種類の名前からIDを引くための索引
+  /// 通常は`NameIndex::default()`を与えれば良い
+  pub name_index: NameIndex,
+  /// 新たに起きたイベントを記録する前に通す検査
+  /// 一つでも検査に通らなかったイベントは記録されず、世界にも反映されない
+  pub event_validators: Vec<EventValidator<T, U>>,
+  /// 検査によって弾かれたイベントの記録
+  pub rejected_events: Vec<RejectedEvent<T>>,
+  /// オブジェクトの数を目標値に近づけるための制御器
+  pub population_controller: Option<PopulationController>,
+  /// `run`が単位時間ごとに使い回す作業領域
+  /// 通常は`RunBuffers::default()`を与えれば良い
+  pub buffers: RunBuffers<T, U>,
+  /// オブジェクトが入れる領域を決める地形
+  /// `None`の場合はどこにでも入れる
+  /// 通行できない地点への移動イベントは`rejected_events`に、生成は`rejected_objects`に記録される
+  pub terrain: Option<Terrain>,
+  /// 地形によって生成が拒否されたオブジェクトの記録
+  pub rejected_objects: Vec<RejectedObject<U>>,
+  /// 注目しているオブジェクトのID
+  pub watched: FxHashSet<String>,
+  /// 注目しているオブジェクトに起きた出来事の記録
+  pub watch_log: Vec<WatchRecord<T>>,
+  /// 外部から差し込まれ、まだ起きていないイベント
+  pub scheduled_events: Vec<InjectedEvent<T>>,
+  /// 外部からイベントを差し込んだ操作の記録
+  pub injection_log: Vec<InjectedEvent<T>>,
+  /// 次に記録されるイベントに振る通し番号
+  pub next_event_sequence: u64,
+  /// 次に生成されるオブジェクトに振る通し番号
+  /// オブジェクトのIDの元になるので、同じ世界から同じように進めれば同じIDが振られる
+  /// `wall-clock-ids`機能を有効にした場合は実世界の時刻も混ぜるため、実行ごとに異なるIDになる
+  pub next_object_number: u64,
+  /// generate関数が使う乱数の種の元
+  /// 書き換えても`Context::rng`の乱数生成器は作り直されない
+  pub seed: u64,
+  /// 記録されたイベントの購読者
+  /// 購読者を加える場合は`Context::subscribe`を使う
+  #[cfg(feature = "tokio")]
+  pub subscribers: Vec<Arc<EventChannel<T>>>,
+  /// 直近の単位時間ごとの処理時間
+  /// 通常は`TickMetrics::default()`を与えれば良い
+  pub tick_metrics: TickMetrics,
+  /// 直前の単位時間を巻き戻すための記録
+  /// 巻き戻す場合は`Context::undo_last_tick`を使う
+  pub undo: Option<TickUndo<T, U>>,
+  /// 現在の天候
+  /// 日が変わるごとに`weather_table`に従って遷移する
+  pub weather: Weather,
+  /// 天候の遷移の重み
+  pub weather_table: WeatherTable,
+  /// 生成先のセルが既に埋まっている場合の配置の設定
+  /// `None`の場合は埋まっていてもそのまま重ねて生成する
+  /// 近くに空いているセルがなければ生成は`rejected_objects`に記録される
+  pub spawn_placement: Option<SpawnPlacement>,
+  /// 生成の失敗を`system_events`に記録するかどうか
+  pub record_spawn_failures: bool,
+  /// 世界の仕組みそのものが起こしたイベントの記録
+  /// `memory`に記憶されるユーザのイベントとは別に、古い順に並ぶ
+  pub system_events: Vec<SystemEventRecord>,
+  /// generate関数が返したイベントを処理する順序
+  /// `step_debug`ではgenerate関数ごとに反映するため、常に登録順になる
+  pub event_order: EventOrder,
+  /// 世界の年表を記録するもの
+  /// 記録する場合は`chronicler.config`を設定する
+  pub chronicler: Chronicler,
+  /// 単位時間ごとの統計の時系列
+  /// 記録する場合は`timeline.enabled`を`true`にする
+  pub timeline: Timeline,
+  /// 地点の近くにあるオブジェクトを探すための索引
+  /// `Context::set_spatial_index`で作る
+  pub(crate) spatial_index: Option<SpatialGrid>,
+  /// 端と端がつながった世界の大きさ
+  /// `Context::set_torus`で設定する
+  pub(crate) torus: Option<Torus>,
+  /// `ObjectType::max_move_per_tick`を超える移動の扱い
+  /// 距離は`Context::distance`と同じく測り、親を持つオブジェクトでは親からの相対座標で測る
+  pub move_limit: MoveLimit,
+  /// `seed`から作られた乱数生成器
+  /// `Context::rng`で使う
+  pub(crate) rng: StdRng,
+  /// `run`で不整合が起きた時に単位時間を進めずに失敗させるかどうか
+  pub strict: bool,
+  /// 直前の`run`で起きたが飛ばされた不整合
+  /// 起きた順に並ぶ
+  pub run_warnings: Vec<RunError>,
 }
 
-/// 世界の状態に応じて変化する情報
+/// `run`が単位時間ごとに使い回す作業領域
+/// 中身は一回の`run`の中でしか使われず、確保済みの容量だけが次の`run`に引き継がれる
+#[derive(Clone)]
+pub struct RunBuffers<T: EventContents<U>, U: ObjectType> {
+  /// 新たに起きたイベント
+  new_events: Vec<Event<T>>,
+  /// 移動させるオブジェクトのIDと移動先の地点
+  moves: Vec<(String, Point)>,
+  /// 中身を置き換えるオブジェクトのIDと新しい中身
+  updates: Vec<(String, U)>,
+  /// 中身に部分的な変更を加えるオブジェクトのIDと変更
+  patches: Vec<(String, U::Patch)>,
+  /// 範囲を持つイベントによる置き換え
+  /// 積んだ時点での`updates`の長さと、`new_events`でのイベントの位置と新しい中身
+  area_updates: Vec<(usize, usize, U)>,
+  /// 範囲を持つイベントによる部分的な変更
+  /// 積んだ時点での`patches`の長さと、`new_events`でのイベントの位置と変更
+  area_patches: Vec<(usize, usize, U::Patch)>,
+  /// 新たに生成されたオブジェクトとそのID
+  new_objects: Vec<(String, Object<U>)>,
+  /// 新たに消滅したオブジェクトのID
+  remove_object_id: Vec<String>,
+  /// 起きた不整合
+  errors: Vec<RunError>,
+  /// 生成先の重なりを調べるための、オブジェクトがあるセルの集合
+  /// 必要になった時に作られ、世界に反映するたびに捨てられる
+  occupied: Option<FxHashSet<Point>>,
+  /// 速度で動くオブジェクトの座標をまとめて計算するための配列
+  velocity: VelocityBatch,
+  /// イベントのIDを組み立てるための文字列
+  event_id: EventIdBuffer,
+  /// 単位時間が確定するまで購読者への配信を待っているイベント
+  #[cfg(feature = "tokio")]
+  unpublished: Vec<Arc<Event<T>>>,
+}
+
+impl<T: EventContents<U>, U: ObjectType> Default for RunBuffers<T, U> {
+  fn default() -> Self {
+    RunBuffers {
+      new_events: Vec::new(),
+      moves: Vec::new(),
+      updates: Vec::new(),
+      patches: Vec::new(),
+      area_updates: Vec::new(),
+      area_patches: Vec::new(),
+      new_objects: Vec::new(),
+      remove_object_id: Vec::new(),
+      errors: Vec::new(),
+      occupied: None,
+      velocity: VelocityBatch::default(),
+      event_id: EventIdBuffer::default(),
+      #[cfg(feature = "tokio")]
+      unpublished: Vec::new(),
+    }
+  }
+}
+
+impl<T: EventContents<U> + fmt::Debug, U: ObjectType + fmt::Debug> fmt::Debug for Context<T, U> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut debug = f.debug_struct("Context");
+    debug
+      .field("time", &self.time)
+      .field("memory", &self.memory)
+      .field("memory_capacity", &self.memory_capacity)
+      .field("objects", &self.objects)
+      .field("event_validators", &self.event_validators.len())
+      .field("rejected_events", &self.rejected_events)
+      .field("population_controller", &self.population_controller)
+      .field("terrain", &self.terrain)
+      .field("rejected_objects", &self.rejected_objects)
+      .field("watched", &self.watched)
+      .field("watch_log", &self.watch_log)
+      .field("scheduled_events", &self.scheduled_events)
+      .field("injection_log", &self.injection_log)
+      .field("next_event_sequence", &self.next_event_sequence)
+      .field("next_object_number", &self.next_object_number)
+      .field("seed", &self.seed)
+      .field("tick_metrics", &self.tick_metrics)
+      .field("undo", &self.undo)
+      .field("weather", &self.weather)
+      .field("weather_table", &self.weather_table)
+      .field("spawn_placement", &self.spawn_placement)
+      .field("record_spawn_failures", &self.record_spawn_failures)
+      .field("system_events", &self.system_events)
+      .field("event_order", &self.event_order)
+      .field("chronicler", &self.chronicler)
+      .field("timeline", &self.timeline)
+      .field("spatial_index", &self.spatial_index)
+      .field("torus", &self.torus)
+      .field("move_limit", &self.move_limit)
+      .field("strict", &self.strict)
+      .field("run_warnings", &self.run_warnings);
+    #[cfg(feature = "tokio")]
+    debug.field("subscribers", &self.subscribers.len());
+    debug.finish_non_exhaustive()
+  }
+}
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// 時刻と最初に存在するオブジェクトから世界を作る
+  /// 記憶は空で、検査・制御器・地形などは全て設定されていない状態から始まる
+  /// 同じIDのオブジェクトが複数ある場合は後のもので上書きされる
+  /// 重複を誤りとして扱いたい場合は`Context::from_config`を使う
+  pub fn new(time: Time, objects: impl IntoIterator<Item = (String, Object<U>)>) -> Self {
+    let objects = objects
+      .into_iter()
+      .map(|(id, object)| (id, Arc::new(object)))
+      .collect();
+    let mut ctx = Context {
+      time,
+      memory: VecDeque::new(),
+      memory_capacity: None,
+      objects,
+      name_index: NameIndex::default(),
+      event_validators: Vec::new(),
+      rejected_events: Vec::new(),
+      population_controller: None,
+      buffers: RunBuffers::default(),
+      terrain: None,
+      rejected_objects: Vec::new(),
+      watched: FxHashSet::default(),
+      watch_log: Vec::new(),
+      scheduled_events: Vec::new(),
+      injection_log: Vec::new(),
+      next_event_sequence: 0,
+      next_object_number: 0,
+      #[cfg(feature = "tokio")]
+      subscribers: Vec::new(),
+      seed: 0,
+      tick_metrics: TickMetrics::default(),
+      undo: None,
+      weather: Weather::Clear,
+      weather_table: WeatherTable::default(),
+      spawn_placement: None,
+      record_spawn_failures: false,
+      system_events: Vec::new(),
+      event_order: EventOrder::Registration,
+      chronicler: Chronicler::default(),
+      timeline: Timeline::default(),
+      spatial_index: None,
+      torus: None,
+      move_limit: MoveLimit::Reject,
+      rng: StdRng::seed_from_u64(0),
+      strict: false,
+      run_warnings: Vec::new(),
+    };
+    ctx.rebuild_name_index();
+    ctx
+  }
+
+  /// `Context::new`と同じく世界を作り、乱数の種を`seed`にする
+  /// 同じ種から作った世界は`Context::rng`から同じ乱数列を返す
+  pub fn new_seeded(
+    time: Time,
+    objects: impl IntoIterator<Item = (String, Object<U>)>,
+    seed: u64,
+  ) -> Self {
+    let mut ctx = Context::new(time, objects);
+    ctx.seed = seed;
+    ctx.rng = StdRng::seed_from_u64(seed);
+    ctx
+  }
+
+  /// 暦の規則はそのままに、経過した単位時間を`start_time`にする
+  /// 時刻は負にできないので、開始前から存在していたことにしたいオブジェクトがある場合は、
+  /// 開始時刻を大きくとり、その分だけ前の時刻を`Object::generated_time`にする
+  /// 既にあるオブジェクトの時刻は変えない
+  pub fn with_start_time(mut self, start_time: BigUint) -> Self {
+    self.time = Time {
+      day_periods: self.time.day_periods.clone(),
+      ..Time::new(
+        start_time,
+        self.time.one_day_of_time.clone(),
+        self.time.one_year_of_day.clone(),
+      )
+    };
+    self
+  }
+
+  /// `seed`から作られた乱数生成器
+  /// 世界の準備や単位時間の合間に使う
+  /// generate関数は世界を書き換えられないので、`Context::stream_seed`から作った種で自分の乱数生成器を作る
+  pub fn rng(&mut self) -> &mut StdRng {
+    &mut self.rng
+  }
+
+  /// オブジェクトの生成量に掛けるべき補正係数
+  /// 制御器が設定されていない場合は常に`1.0`
+  pub fn population_factor(&self) -> f64 {
+    self
+      .population_controller
+      .as_ref()
+      .map_or(1.0, |controller| controller.factor())
+  }
+
+  /// 既存のオブジェクトの中身を複製して、generate関数から生成させるためのオブジェクトを作る
+  /// 複製には複製元のIDが記録されて`at`が生み出される場所として設定され、その後に`mutate`が適用される
+  /// 複製元が存在しない場合は`None`
+  pub fn clone_object(&self, id: &str, mutate: impl Fn(&mut U), at: Point) -> Option<U>
+  where
+    U: Replicable,
+  {
+    let mut child = self.objects.get(id)?.object_type.clone();
+    child.set_parent(id);
+    child.set_generated_point(at);
+    mutate(&mut child);
+    Some(child)
+  }
+
+  /// オブジェクトの生成からの経過時間
+  /// 生成時刻が現在時刻より後の場合は`0`
+  pub fn age_of(&self, object: &Object<U>) -> BigUint {
+    object.age(&self.time)
+  }
+
+  /// 段階ごとのオブジェクトの数
+  /// `i`番目の要素が段階`i`にいるオブジェクトの数で、長さは最も多い段階の数に揃う
+  pub fn stage_distribution(&self) -> Vec<usize>
+  where
+    U: LifeStage,
+  {
+    let mut distribution = Vec::new();
+    for object in self.objects.values() {
+      let stage = object.object_type.stage(&self.age_of(object)) as usize;
+      let len = (object.object_type.stage_count() as usize).max(stage + 1);
+      if distribution.len() < len {
+        distribution.resize(len, 0);
+      }
+      distribution[stage] += 1;
+    }
+    distribution
+  }
+
+  /// 全てのオブジェクト
+  /// IDの辞書順に並ぶので、同じ種から同じように進めた世界では常に同じ順序になる
+  /// generate関数がこの順序で走査すれば、生成されるイベントの列も同じになる
+  pub fn objects_sorted(&self) -> Vec<(&String, &Object<U>)> {
+    let mut objects = self
+      .objects
+      .iter()
+      .map(|(id, object)| (id, object.as_ref()))
+      .collect::<Vec<_>>();
+    objects.sort_unstable_by_key(|(id, _)| *id);
+    objects
+  }
+
+  /// 時刻`t`より後に生成・移動・置き換えのいずれかが起きたオブジェクトのID
+  /// IDは辞書順に並ぶ
+  pub fn objects_modified_since(&self, t: &Time) -> Vec<&String> {
+    let mut ids = self
+      .objects
+      .iter()
+      .filter(|(_, object)| object.last_modified.all > t.all)
+      .map(|(id, _)| id)
+      .collect::<Vec<_>>();
+    ids.sort();
+    ids
+  }
+
+  /// `key`で評価した値が大きい順に、上位`n`個のオブジェクトを返す
+  /// 値が等しい場合はIDの辞書順に並ぶ
+  /// 全体を並べ替えず、上位`n`個を選び出してからそれだけを並べ替える
+  pub fn top_by<K: Ord>(
+    &self,
+    n: usize,
+    key: impl Fn(&Object<U>) -> K,
+  ) -> Vec<(&String, &Object<U>)> {
+    if n == 0 {
+      return Vec::new();
+    }
+    let mut scored = self
+      .objects
+      .iter()
+      .map(|(id, object)| (key(object), id, object.as_ref()))
+      .collect::<Vec<_>>();
+    let compare = |a: &(K, &String, &Object<U>), b: &(K, &String, &Object<U>)| {
+      b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1))
+    };
+    if n < scored.len() {
+      scored.select_nth_unstable_by(n - 1, compare);
+      scored.truncate(n);
+    }
+    scored.sort_unstable_by(compare);
+    scored
+      .into_iter()
+      .map(|(_, id, object)| (id, object))
+      .collect()
+  }
+
+  /// その地点にオブジェクトが入れるかどうか
+  pub fn is_passable(&self, point: &Point) -> bool {
+    self
+      .terrain
+      .as_ref()
+      .is_none_or(|terrain| terrain.is_passable(point))
+  }
+
+  /// オブジェクトを一つ削除する
+  /// 存在しなかった場合は`None`を返す
+  pub fn remove_object(&mut self, id: &str) -> Option<Object<U>> {
+    let object = self.objects.remove(id)?;
+    self.name_index.remove(&object.object_type.name(), id);
+    self.spatial_update(id);
+    self.spatial_update_framed();
+    self.record_watch(id, WatchKind::Removed);
+    Some(Arc::unwrap_or_clone(object))
+  }
+
+  /// 世界を分岐させる
+  /// オブジェクトと記憶されているイベントは分岐元と共有され、
+  /// どちらかの世界で書き換えられた時に初めてそのオブジェクトだけが複製される
+  /// 分岐後はそれぞれ独立に`run`で進めることができ、`diff`で比べることができる
+  pub fn fork(&self) -> Context<T, U> {
+    self.clone()
+  }
+
+  /// イベントを記憶に加え、`events`を空にする
+  /// 記憶の上限がある場合は、超えた分だけ古いイベントから忘れる
+  /// 上限までの領域は最初に確保されるので、以降は記憶が増減しても確保し直すことはない
+  fn remember(&mut self, events: &mut Vec<Event<T>>) {
+    let Some(capacity) = self.memory_capacity else {
+      self.memory.extend(events.drain(..).map(Arc::new));
+      return;
+    };
+    self
+      .memory
+      .reserve_exact(capacity.saturating_sub(self.memory.len()));
+    let skip = events.len().saturating_sub(capacity);
+    for event in events.drain(..).skip(skip) {
+      if self.memory.len() >= capacity {
+        self.evict_front();
+      }
+      self.memory.push_back(Arc::new(event));
+    }
+    while self.memory.len() > capacity {
+      self.evict_front();
+    }
+  }
+
+  /// 記憶の先頭のイベントを忘れ、巻き戻すための記録に残す
+  fn evict_front(&mut self) {
+    if let Some(event) = self.memory.pop_front() {
+      if let Some(undo) = &mut self.undo {
+        undo.evicted.push(event);
+      }
+    }
+  }
+
+  /// 記憶されているイベントのうち、範囲の影響を指定したオブジェクトに及ぼしたもの
+  pub fn area_effects_on(&self, id: &str) -> Vec<&Event<T>> {
+    self
+      .memory
+      .iter()
+      .filter(|e| {
+        e.affected_objects
+          .binary_search_by(|affected| affected.as_str().cmp(id))
+          .is_ok()
+      })
+      .map(|e| e.as_ref())
+      .collect()
+  }
+
+  /// 記憶されているイベントのうち、指定したオブジェクトが主体となったもの
+  /// 記憶された順に並ぶ
+  pub fn events_by_actor(&self, id: &str) -> Vec<&Event<T>> {
+    self
+      .memory
+      .iter()
+      .filter(|e| e.do_object == id)
+      .map(|e| e.as_ref())
+      .collect()
+  }
+
+  /// 記憶されているイベントのうち、指定したオブジェクトが対象となったもの
+  /// 記憶された順に並ぶ
+  pub fn events_targeting(&self, id: &str) -> Vec<&Event<T>> {
+    self
+      .memory
+      .iter()
+      .filter(|e| e.target_object.as_deref() == Some(id))
+      .map(|e| e.as_ref())
+      .collect()
+  }
+
+  /// 記憶されているイベントのうち、`start`以降`end`より前に起きたもの
+  /// 起きた時刻は経過した単位時間だけで比べる
+  /// 記憶は起きた順に並んでいるので、境界を二分探索で求める
+  /// 記憶されているイベントの数を`n`、返すイベントの数を`k`として`O(log n + k)`
+  pub fn events_between(&self, start: &Time, end: &Time) -> Vec<&Event<T>> {
+    let first = self
+      .memory
+      .partition_point(|e| e.generated_time.all < start.all);
+    let last = self
+      .memory
+      .partition_point(|e| e.generated_time.all < end.all);
+    self
+      .memory
+      .range(first..last.max(first))
+      .map(|e| e.as_ref())
+      .collect()
+  }
+
+  /// `min`と`max`を対角とする矩形の範囲内（境界を含む）にあるオブジェクトを全て削除し、削除したオブジェクトのIDを返す
+  /// 親を持つオブジェクトは`Context::world_point`で求めた世界での位置で判定する
+  /// IDは辞書順に並ぶ
+  /// 記憶されているイベントは過去に起きた出来事であるため、削除されたオブジェクトに関するものもそのまま残る
+  /// 空間索引がある場合は矩形と重なるセルにあるオブジェクトだけを調べ、索引は最後にまとめて直す
+  pub fn remove_in_rect(&mut self, min: &Point, max: &Point) -> Vec<String> {
+    let in_rect = |id: &String| {
+      let object = &self.objects[id];
+      self.located_point(id, object).is_in_rect(min, max)
+    };
+    let mut ids = match &self.spatial_index {
+      Some(grid) => grid
+        .candidates_in_rect(min, max)
+        .into_iter()
+        .filter(|id| in_rect(id))
+        .cloned()
+        .collect::<Vec<_>>(),
+      None => self
+        .objects
+        .keys()
+        .filter(|id| in_rect(id))
+        .cloned()
+        .collect(),
+    };
+    ids.sort();
+    for id in ids.iter() {
+      let object = self.objects.remove(id).expect("listed objects exist");
+      self.name_index.remove(&object.object_type.name(), id);
+      if let Some(grid) = &mut self.spatial_index {
+        grid.remove(id);
+      }
+      self.record_watch(id, WatchKind::Removed);
+    }
+    self.spatial_update_framed();
+    ids
+  }
+}
+
+/// オブジェクトの数を目標値に近づけるためのPID制御器
+/// 目標値との差に応じて補正係数を計算し、generate関数はこれを生成確率や生成量に掛けて使う
 #[derive(Debug, Clone)]
-pub struct GeneratedData<T: EventContents, U: ObjectType> {
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PopulationController {
+  /// 目標とするオブジェクトの数
+  pub target: usize,
+  /// 比例ゲイン
+  pub kp: f64,
+  /// 積分ゲイン
+  pub ki: f64,
+  /// 微分ゲイン
+  pub kd: f64,
+  /// 現在の補正係数
+  factor: f64,
+  /// 偏差の積分値
+  integral: f64,
+  /// 前回の偏差
+  previous_error: Option<f64>,
+  /// 補正係数の履歴
+  history: Vec<(Time, f64)>,
+}
+
+impl PopulationController {
+  /// 制御器の新たな生成
+  /// 補正係数は`1.0`から始まる
+  pub fn new(target: usize, kp: f64, ki: f64, kd: f64) -> Self {
+    PopulationController {
+      target,
+      kp,
+      ki,
+      kd,
+      factor: 1.0,
+      integral: 0.0,
+      previous_error: None,
+      history: Vec::new(),
+    }
+  }
+
+  /// 現在の補正係数
+  pub fn factor(&self) -> f64 {
+    self.factor
+  }
+
+  /// これまでの補正係数の履歴
+  pub fn history(&self) -> &[(Time, f64)] {
+    &self.history
+  }
+
+  /// 現在のオブジェクトの数から補正係数を更新する
+  /// 偏差は目標値に対する割合で計算し、補正係数は負にならない
+  fn update(&mut self, now: &Time, population: usize) {
+    let target = self.target.max(1) as f64;
+    let error = (self.target as f64 - population as f64) / target;
+    self.integral += error;
+    let derivative = self.previous_error.map_or(0.0, |previous| error - previous);
+    self.previous_error = Some(error);
+    self.factor = (1.0 + self.kp * error + self.ki * self.integral + self.kd * derivative).max(0.0);
+    self.history.push((now.clone(), self.factor));
+  }
+}
+
+/// 世界の状態に応じて変化する情報
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedData<T: EventContents<U>, U: ObjectType> {
   /// 新たに起きたイベント
   pub events: Vec<T>,
   /// 新たに生成されたオブジェクト
+  /// IDは`Context::next_object_number`から振られるので、同じ単位時間に同じ地点で同じ種類のものを生成しても重ならない
   pub generate_objects: Vec<U>,
   /// 新たに消滅したオブジェクト
   pub remove_objects: Vec<String>,
 }
 
+impl<T: EventContents<U>, U: ObjectType> Default for GeneratedData<T, U> {
+  fn default() -> Self {
+    GeneratedData {
+      events: Vec::new(),
+      generate_objects: Vec::new(),
+      remove_objects: Vec::new(),
+    }
+  }
+}
+
+impl<T: EventContents<U>, U: ObjectType> GeneratedData<T, U> {
+  /// 何も起きなかったことを表す空の情報
+  pub fn empty() -> Self {
+    GeneratedData::default()
+  }
+
+  /// `other`のイベント・生成・消滅をそれぞれ後ろに加える
+  pub fn merge(&mut self, other: GeneratedData<T, U>) {
+    self.events.extend(other.events);
+    self.generate_objects.extend(other.generate_objects);
+    self.remove_objects.extend(other.remove_objects);
+  }
+}
+
 /// 新たな情報を生成するための関数
-pub type Generater<T, U> = fn(&Context<T, U>) -> GeneratedData<T, U>;
+/// クロージャを使えるので、生成の割合などの設定を取り込んだgenerate関数を作れる
+/// 例えば`Arc::new(move |ctx: &Context<T, U>| ...)`のように作る
+pub type Generater<T, U> = Arc<dyn Fn(&Context<T, U>) -> GeneratedData<T, U> + Send + Sync>;
+
+/// 同じ世界に対して二回呼んだ結果が異なるgenerate関数の番号を返す
+/// generate関数は世界を読むだけの純粋な関数であることが前提となっている
+/// 乱数や現在時刻を内部で使っているものは、種を固定しない限りここで見つかる
+pub fn impure_generators<T, U>(
+  ctx: &Context<T, U>,
+  generate_functions: &[Generater<T, U>],
+) -> Vec<usize>
+where
+  T: EventContents<U> + PartialEq,
+  U: ObjectType,
+{
+  generate_functions
+    .iter()
+    .enumerate()
+    .filter(|(i, f)| as_generator(*i, || f(ctx)) != as_generator(*i, || f(ctx)))
+    .map(|(i, _)| i)
+    .collect()
+}
+
+/// デバッグビルドでのみ、全てのgenerate関数が純粋であることを確かめる
+/// 純粋でないものがあった場合はその番号を示してpanicする
+/// リリースビルドでは何もしない
+pub fn assert_pure_generators<T, U>(ctx: &Context<T, U>, generate_functions: &[Generater<T, U>])
+where
+  T: EventContents<U> + PartialEq,
+  U: ObjectType,
+{
+  if cfg!(debug_assertions) {
+    let impure = impure_generators(ctx, generate_functions);
+    assert!(
+      impure.is_empty(),
+      "generate functions {impure:?} returned different results for the same context"
+    );
+  }
+}
 
 /// 単位時間を一つだけ進め、その結果起こるイベントをすべて記録し、世界を更新する
 /// - `T`は「イベントの具体的な中身」
 /// - `U`は「オブジェクトの具体的な中身」
-pub fn run<T: EventContents, U: ObjectType>(
+///
+/// 全てのgenerate関数は同じ世界を見て実行され、その結果とイベントの効果は種類ごとにまとめられて以下の順に反映される
+/// 同じ種類の中では集められた順に反映される
+/// 1. オブジェクトの削除
+/// 2. オブジェクトの生成
+/// 3. オブジェクトの移動
+/// 4. オブジェクトの中身の置き換え
+///
+/// 削除は一度だけまとめて行われ、生成はその後に行われるので、
+/// 同じ単位時間に生成されたオブジェクトが削除の要求に巻き込まれることはない
+/// 移動と置き換えは生成の後に行われるので、同じ単位時間に生成されたオブジェクトにも効果が及ぶ
+///
+/// 存在しないオブジェクトの削除などの不整合が起きた場合の扱いは`Context::strict`で決まる
+/// - `false`の場合は不整合を飛ばして進め、起きた不整合を`Context::run_warnings`に残して`Ok`を返す
+/// - `true`の場合は`try_run`と同じく世界を進める前の状態に戻し、起きた全ての不整合を`Err`で返す
+pub fn run<T: EventContents<U>, U: ObjectType>(
+  ctx: &mut Context<T, U>,
+  generate_functions: Vec<Generater<T, U>>,
+) -> Result<Vec<GeneratedData<T, U>>, TickError> {
+  run_with(ctx, |ctx| {
+    generate_functions
+      .iter()
+      .enumerate()
+      .map(|(i, f)| as_generator(i, || f(ctx)))
+      .collect()
+  })
+}
+
+/// `run`と同じく`Context::strict`に従って単位時間を一つだけ進める
+/// generate関数の実行は`generate`に任せる
+fn run_with<T: EventContents<U>, U: ObjectType>(
+  ctx: &mut Context<T, U>,
+  generate: impl FnOnce(&Context<T, U>) -> Vec<GeneratedData<T, U>>,
+) -> Result<Vec<GeneratedData<T, U>>, TickError> {
+  let strict = ctx.strict;
+  commit_tick(ctx, strict, |ctx| run_tick_with(ctx, generate))
+}
+
+/// `tick`で単位時間を一つ進め、起きた不整合を`strict`に従って扱う
+/// - `false`の場合は起きた不整合を`Context::run_warnings`に残して`Ok`を返す
+/// - `true`の場合は不整合が一つでも起きていれば世界を進める前の状態に戻し、起きた全ての不整合を`Err`で返す
+///
+/// 進めた単位時間が確定した場合にだけ、そこで起きたイベントを購読者に配信する
+pub(crate) fn commit_tick<T: EventContents<U>, U: ObjectType, R>(
+  ctx: &mut Context<T, U>,
+  strict: bool,
+  tick: impl FnOnce(&mut Context<T, U>) -> (R, Vec<RunError>),
+) -> Result<R, TickError> {
+  let backup = strict.then(|| ctx.fork());
+  let (result, errors) = tick(ctx);
+  if let Some(backup) = backup {
+    if !errors.is_empty() {
+      *ctx = backup;
+      return Err(TickError { errors });
+    }
+  }
+  ctx.run_warnings = errors;
+  #[cfg(feature = "tokio")]
+  ctx.publish();
+  Ok(result)
+}
+
+/// `run`と同じく単位時間を一つだけ進めるが、途中で不整合が一つでも起きた場合は世界を進める前の状態に戻し、
+/// 起きた全ての不整合をまとめて返す
+/// 不整合とみなすのは以下のもの
+/// - 検査で弾かれたイベント
+/// - 地形によって拒否されたオブジェクトの生成
+/// - 存在しないオブジェクトの削除・移動・置き換え
+/// - 既存のオブジェクトと衝突するIDでの生成
+pub fn try_run<T: EventContents<U>, U: ObjectType>(
+  ctx: &mut Context<T, U>,
+  generate_functions: Vec<Generater<T, U>>,
+) -> Result<Vec<GeneratedData<T, U>>, TickError> {
+  try_run_with(ctx, |ctx| {
+    generate_functions
+      .iter()
+      .enumerate()
+      .map(|(i, f)| as_generator(i, || f(ctx)))
+      .collect()
+  })
+}
+
+/// `try_run`と同じく不整合が起きた場合は世界を進める前の状態に戻す
+/// generate関数の実行は`generate`に任せる
+fn try_run_with<T: EventContents<U>, U: ObjectType>(
+  ctx: &mut Context<T, U>,
+  generate: impl FnOnce(&Context<T, U>) -> Vec<GeneratedData<T, U>>,
+) -> Result<Vec<GeneratedData<T, U>>, TickError> {
+  commit_tick(ctx, true, |ctx| run_tick_with(ctx, generate))
+}
+
+/// 単位時間を一つだけ進め、`generate`で全てのgenerate関数を同じ世界に対して実行してから、その結果を順に反映する
+fn run_tick_with<T: EventContents<U>, U: ObjectType>(
+  ctx: &mut Context<T, U>,
+  generate: impl FnOnce(&Context<T, U>) -> Vec<GeneratedData<T, U>>,
+) -> (Vec<GeneratedData<T, U>>, Vec<RunError>) {
+  #[cfg(feature = "tick-metrics")]
+  let start = Instant::now();
+  let first_sequence = ctx.next_event_sequence;
+  let (now, forgotten) = begin_tick(ctx);
+  let mut buffers = std::mem::take(&mut ctx.buffers);
+  collect_velocity_moves(ctx, &mut buffers);
+  collect_forgotten_events(ctx, &mut buffers, &now, forgotten);
+  collect_injected_events(ctx, &mut buffers, &now);
+  let generated_data_lst = generate(ctx);
+  collect_generated_data_lst(ctx, &mut buffers, &now, &generated_data_lst);
+  apply_buffers(ctx, &mut buffers);
+  let errors = std::mem::take(&mut buffers.errors);
+  ctx.buffers = buffers;
+  ctx.update_chronicle(first_sequence);
+  ctx.update_timeline();
+  #[cfg(feature = "tick-metrics")]
+  ctx.tick_metrics.record(start.elapsed());
+  (generated_data_lst, errors)
+}
+
+/// `run`を`steps`回繰り返し、単位時間ごとにgenerate関数が生成した情報を返す
+/// 記憶の忘却やIDの振り方を含め、`run`を`steps`回呼んだ場合と全く同じに進む
+/// `run`が`Err`を返した場合はそこで止め、その不整合を返す
+pub fn run_n<T: EventContents<U>, U: ObjectType>(
+  ctx: &mut Context<T, U>,
+  generate_functions: &[Generater<T, U>],
+  steps: usize,
+) -> Result<Vec<Vec<GeneratedData<T, U>>>, TickError> {
+  (0..steps)
+    .map(|_| run(ctx, generate_functions.to_vec()))
+    .collect()
+}
+
+/// `run`と同じく単位時間を一つだけ進め、世界を更新した後に`hook`を呼ぶ
+/// `hook`には更新後の世界とgenerate関数が生成した情報が渡される
+/// `run`が`Err`を返した場合は`hook`を呼ばずにその不整合を返す
+pub fn run_with_hook<T, U, F>(
   ctx: &mut Context<T, U>,
   generate_functions: Vec<Generater<T, U>>,
-) -> Vec<GeneratedData<T, U>> {
+  mut hook: F,
+) -> Result<Vec<GeneratedData<T, U>>, TickError>
+where
+  T: EventContents<U>,
+  U: ObjectType,
+  F: FnMut(&Context<T, U>, &[GeneratedData<T, U>]),
+{
+  let generated_data_lst = run(ctx, generate_functions)?;
+  hook(ctx, &generated_data_lst);
+  Ok(generated_data_lst)
+}
+
+/// `run_with_hook`を`steps`回繰り返す
+/// 生成された情報は`hook`に渡すだけで集めないので、長く進めても溜まっていかない
+/// `run`が`Err`を返した場合はそこで止め、その不整合を返す
+pub fn run_n_with_hook<T, U, F>(
+  ctx: &mut Context<T, U>,
+  generate_functions: &[Generater<T, U>],
+  steps: usize,
+  mut hook: F,
+) -> Result<(), TickError>
+where
+  T: EventContents<U>,
+  U: ObjectType,
+  F: FnMut(&Context<T, U>, &[GeneratedData<T, U>]),
+{
+  for _ in 0..steps {
+    run_with_hook(ctx, generate_functions.to_vec(), &mut hook)?;
+  }
+  Ok(())
+}
+
+/// 実時間で`budget`を使い切るまで`run`を繰り返し、進めた単位時間の数を返す
+/// 経過時間は単位時間を一つ進めるごとに確かめ、途中で打ち切ることはない
+/// そのため少なくとも一単位時間は進み、最後の一単位時間の分だけ`budget`を超えることがある
+/// ただし`Context::strict`が`true`で不整合が起きた場合は、その単位時間を進めずに打ち切る
+/// generate関数が生成した情報は捨てられる
+pub fn run_within<T: EventContents<U>, U: ObjectType>(
+  ctx: &mut Context<T, U>,
+  generate_functions: Vec<Generater<T, U>>,
+  budget: Duration,
+) -> usize {
+  let start = Instant::now();
+  let mut ticks = 0;
+  loop {
+    if run(ctx, generate_functions.clone()).is_err() {
+      return ticks;
+    }
+    ticks += 1;
+    if start.elapsed() >= budget {
+      return ticks;
+    }
+  }
+}
+
+/// `run`と同じく単位時間を一つだけ進めるが、generate関数を一つ実行するごとにその結果を世界に反映し、
+/// 反映後の世界をコールバックに渡す
+/// コールバックには何番目のgenerate関数か、その関数が生成した情報、反映後の世界が渡される
+///
+/// 忘れられたイベントが起こした情報と予約されたイベントは、最初のgenerate関数の実行前に反映される
+///
+/// 後に実行されるgenerate関数は前のgenerate関数の結果が反映された世界を見るため、
+/// 全てのgenerate関数が同じ世界を見る`run`とは結果が異なることがある
+/// どのルールがどのオブジェクトを動かしたのかを一つずつ確かめるためのデバッグ用の機能である
+///
+/// 不整合が起きた場合の扱いは`run`と同じく`Context::strict`で決まる
+/// `true`で不整合が起きた場合は世界を進める前の状態に戻すが、それまでのコールバックは呼ばれた後になる
+#[cfg(feature = "step-debug")]
+pub fn step_debug<T: EventContents<U>, U: ObjectType, F>(
+  ctx: &mut Context<T, U>,
+  generate_functions: Vec<Generater<T, U>>,
+  mut callback: F,
+) -> Result<Vec<GeneratedData<T, U>>, TickError>
+where
+  F: FnMut(usize, &GeneratedData<T, U>, &Context<T, U>),
+{
+  let strict = ctx.strict;
+  commit_tick(ctx, strict, |ctx| {
+    let first_sequence = ctx.next_event_sequence;
+    let (now, forgotten) = begin_tick(ctx);
+    let mut buffers = std::mem::take(&mut ctx.buffers);
+    collect_velocity_moves(ctx, &mut buffers);
+    collect_forgotten_events(ctx, &mut buffers, &now, forgotten);
+    collect_injected_events(ctx, &mut buffers, &now);
+    apply_buffers(ctx, &mut buffers);
+    let mut generated_data_lst = Vec::with_capacity(generate_functions.len());
+    for (i, f) in generate_functions.iter().enumerate() {
+      let generated_data = as_generator(i, || f(ctx));
+      collect_generated_data(ctx, &mut buffers, &now, &generated_data);
+      apply_buffers(ctx, &mut buffers);
+      callback(i, &generated_data, ctx);
+      generated_data_lst.push(generated_data);
+    }
+    let errors = std::mem::take(&mut buffers.errors);
+    ctx.buffers = buffers;
+    ctx.update_chronicle(first_sequence);
+    ctx.update_timeline();
+    (generated_data_lst, errors)
+  })
+}
+
+/// 時間を一単位時間進め、寿命を迎えたイベントを忘れ、制御器を更新する
+/// 進めた後の時刻と忘れたイベントを返す
+fn begin_tick<T: EventContents<U>, U: ObjectType>(
+  ctx: &mut Context<T, U>,
+) -> (Time, Vec<Arc<Event<T>>>) {
+  let recycled = ctx.undo.take();
+  let mut undo = TickUndo::begin(ctx, recycled);
   ctx.time.plus_one();
   let now = ctx.time.clone();
-  let new_memory = ctx
-    .memory
-    .iter()
-    .filter(|e| {
-      if let Some(lifetime) = &e.lifetime {
-        &e.generated_time.all + &lifetime.all < now.all
-      } else {
-        // Noneの場合は永久に残るものなので残す
-        true
-      }
+  let mut forgotten = Vec::new();
+  let mut index = 0;
+  ctx.memory.retain(|e| {
+    let keep = if let Some(lifetime) = &e.lifetime {
+      // 生成から寿命の分だけ経った単位時間までは覚えている
+      now.all <= &e.generated_time.all + &lifetime.all
+    } else {
+      // Noneの場合は永久に残るものなので残す
+      true
+    };
+    if !keep {
+      forgotten.push(e.clone());
+      undo.forgotten.push((index, e.clone()));
+    }
+    index += 1;
+    keep
+  });
+  ctx.undo = Some(undo);
+  ctx.advance_weather();
+  let population = ctx.objects.len();
+  if let Some(controller) = &mut ctx.population_controller {
+    controller.update(&now, population);
+  }
+  (now, forgotten)
+}
+
+/// 速度を持つオブジェクトの移動を作業領域に積む
+fn collect_velocity_moves<T: EventContents<U>, U: ObjectType>(
+  ctx: &Context<T, U>,
+  buffers: &mut RunBuffers<T, U>,
+) {
+  velocity::collect_velocity_moves(ctx, &mut buffers.velocity, &mut buffers.moves);
+}
+
+/// 忘れられたイベントの`on_forget`が起こした情報を作業領域に積む
+fn collect_forgotten_events<T: EventContents<U>, U: ObjectType>(
+  ctx: &mut Context<T, U>,
+  buffers: &mut RunBuffers<T, U>,
+  now: &Time,
+  forgotten: Vec<Arc<Event<T>>>,
+) {
+  for event in forgotten {
+    let generated_data = event.contents.on_forget(ctx);
+    collect_generated_data(ctx, buffers, now, &generated_data);
+  }
+}
+
+/// 予約されていたイベントのうち、現在時刻に起こすべきものを作業領域に積む
+fn collect_injected_events<T: EventContents<U>, U: ObjectType>(
+  ctx: &mut Context<T, U>,
+  buffers: &mut RunBuffers<T, U>,
+  now: &Time,
+) {
+  for e in ctx.take_due_events() {
+    collect_event(ctx, buffers, now, &e);
+  }
+}
+
+/// イベントを一つ検査し、通ったものを作業領域に積む
+fn collect_event<T: EventContents<U>, U: ObjectType>(
+  ctx: &mut Context<T, U>,
+  buffers: &mut RunBuffers<T, U>,
+  now: &Time,
+  e: &T,
+) {
+  let effects = e
+    .effects()
+    .into_iter()
+    .map(|effect| match effect {
+      Effect::Move(id, point) => Effect::Move(id, ctx.wrap_point(point)),
+      effect => effect,
     })
-    .cloned()
     .collect::<Vec<_>>();
-  ctx.memory = new_memory;
-  let mut new_events = Vec::new();
-  let mut new_objects = Vec::new();
-  let mut remove_object_id = Vec::new();
-  let mut generated_data_lst = Vec::new();
-  for f in generate_functions.iter() {
-    let generated_data = f(ctx);
-    generated_data_lst.push(generated_data.clone());
-    let e_lst = generated_data.events;
-    for e in e_lst.iter() {
-      let event = Event {
-        generated_time: now.clone(),
-        lifetime: e.lifetime(),
+  if let Some(reason) = validate_event(ctx, e, &effects) {
+    ctx.record_watch(
+      &e.do_object(),
+      WatchKind::Rejected {
         contents: e.clone(),
-        do_object: e.do_object(),
-        target_object: e.target_object_opt(),
-      };
-      new_events.push(event);
-    }
-    let mut r = generated_data.remove_objects;
-    remove_object_id.append(&mut r);
-    let o_lst = generated_data.generate_objects;
-    for o in o_lst.iter() {
-      let object = Object {
-        generated_time: now.clone(),
-        point: o.generated_point(),
-        object_type: o.clone(),
-      };
-      let id = generate_object_id(&o.name(), &o.generated_point(), &now.all);
-      new_objects.push((id, object));
-    }
-  }
-  for object_id in remove_object_id.iter() {
-    ctx.objects.remove(object_id);
-  }
-  ctx.memory.append(&mut new_events);
-  for e in new_events.iter() {
-    if let Some((id, point)) = e.contents.move_object_opt() {
-      if let Some(obj) = ctx.objects.get(&id) {
-        let new_obj = Object {
-          point,
-          ..obj.clone()
-        };
-        ctx.objects.insert(id, new_obj);
+        reason: reason.clone(),
+      },
+    );
+    buffers.errors.push(RunError::RejectedEvent {
+      do_object: e.do_object(),
+      reason: reason.clone(),
+    });
+    ctx.rejected_events.push(RejectedEvent {
+      time: now.clone(),
+      contents: e.clone(),
+      reason,
+    });
+    return;
+  }
+  let do_object = e.do_object();
+  let event_id = buffers
+    .event_id
+    .generate(&do_object, &now.all, ctx.next_event_sequence);
+  let has_area = e.area_of_effect().is_some();
+  let index = buffers.new_events.len();
+  for effect in effects {
+    match effect {
+      Effect::Spawn(object_type) => {
+        collect_spawn(ctx, buffers, now, object_type, Some(event_id.clone()))
       }
+      Effect::Remove(id) => buffers.remove_object_id.push(id),
+      Effect::Move(id, point) => buffers.moves.push((id, point)),
+      Effect::Update(_, object_type) if has_area => {
+        let position = buffers.updates.len();
+        buffers.area_updates.push((position, index, object_type));
+      }
+      Effect::Patch(_, patch) if has_area => {
+        let position = buffers.patches.len();
+        buffers.area_patches.push((position, index, patch));
+      }
+      Effect::Update(id, object_type) => buffers.updates.push((id, object_type)),
+      Effect::Patch(id, patch) => buffers.patches.push((id, patch)),
+    }
+  }
+  let location = e.location().or_else(|| actor_location(ctx, &do_object));
+  let event = Event {
+    generated_time: now.clone(),
+    lifetime: e.lifetime(),
+    contents: e.clone(),
+    do_object,
+    target_object: e.target_object_opt(),
+    location,
+    affected_objects: Vec::new(),
+    sequence: ctx.next_event_sequence,
+    id: event_id,
+  };
+  ctx.next_event_sequence += 1;
+  buffers.new_events.push(event);
+}
+
+/// 同じ世界を見て実行されたgenerate関数の結果を、`event_order`に従った順に作業領域に積む
+fn collect_generated_data_lst<T: EventContents<U>, U: ObjectType>(
+  ctx: &mut Context<T, U>,
+  buffers: &mut RunBuffers<T, U>,
+  now: &Time,
+  generated_data_lst: &[GeneratedData<T, U>],
+) {
+  if ctx.event_order == EventOrder::Registration {
+    for generated_data in generated_data_lst.iter() {
+      collect_generated_data(ctx, buffers, now, generated_data);
+    }
+  } else {
+    let mut events = generated_data_lst
+      .iter()
+      .flat_map(|generated_data| generated_data.events.iter())
+      .collect::<Vec<_>>();
+    order::sort_events(ctx, ctx.event_order, &mut events);
+    for e in events {
+      collect_event(ctx, buffers, now, e);
+    }
+    for generated_data in generated_data_lst.iter() {
+      collect_generated_objects(ctx, buffers, now, generated_data);
     }
   }
-  for object_id in remove_object_id.iter() {
-    ctx.objects.remove(object_id);
+}
+
+/// generate関数が生成した情報を検査し、世界に反映するものを作業領域に積む
+fn collect_generated_data<T: EventContents<U>, U: ObjectType>(
+  ctx: &mut Context<T, U>,
+  buffers: &mut RunBuffers<T, U>,
+  now: &Time,
+  generated_data: &GeneratedData<T, U>,
+) {
+  for e in generated_data.events.iter() {
+    collect_event(ctx, buffers, now, e);
   }
-  for (object_id, object) in new_objects.iter() {
-    ctx.objects.insert(object_id.clone(), object.clone());
+  collect_generated_objects(ctx, buffers, now, generated_data);
+}
+
+/// generate関数が生成した情報のうち、削除と生成を作業領域に積む
+fn collect_generated_objects<T: EventContents<U>, U: ObjectType>(
+  ctx: &mut Context<T, U>,
+  buffers: &mut RunBuffers<T, U>,
+  now: &Time,
+  generated_data: &GeneratedData<T, U>,
+) {
+  buffers
+    .remove_object_id
+    .extend_from_slice(&generated_data.remove_objects);
+  for o in generated_data.generate_objects.iter() {
+    collect_spawn(ctx, buffers, now, o.clone(), None);
+  }
+}
+
+/// 生成するオブジェクトを地形で検査し、通ったものにIDを付けて作業領域に積む
+/// 配置の設定がある場合は、生成先が埋まっていれば近くの空いているセルに置き直す
+/// `caused_by`は生成の元になったイベントのID
+fn collect_spawn<T: EventContents<U>, U: ObjectType>(
+  ctx: &mut Context<T, U>,
+  buffers: &mut RunBuffers<T, U>,
+  now: &Time,
+  object_type: U,
+  caused_by: Option<String>,
+) {
+  let point = ctx.wrap_point(object_type.generated_point());
+  if !ctx.is_passable(&point) {
+    reject_spawn(
+      ctx,
+      buffers,
+      now,
+      object_type,
+      "generated point is not passable",
+    );
+    return;
   }
-  generated_data_lst
+  let point = match &ctx.spawn_placement {
+    None => point,
+    Some(placement) => {
+      let occupied = buffers.occupied.get_or_insert_with(|| {
+        ctx
+          .objects
+          .iter()
+          .map(|(id, object)| ctx.located_point(id, object).into_owned())
+          .chain(
+            buffers
+              .new_objects
+              .iter()
+              .map(|(_, object)| object.point.clone()),
+          )
+          .collect()
+      });
+      match placement.place(ctx, occupied, point) {
+        Some(point) => point,
+        None => {
+          reject_spawn(
+            ctx,
+            buffers,
+            now,
+            object_type,
+            "no free cell near generated point",
+          );
+          return;
+        }
+      }
+    }
+  };
+  let id = generate_object_id(
+    &object_type.name(),
+    &point,
+    &now.all,
+    ctx.next_object_number,
+  );
+  ctx.next_object_number += 1;
+  let object = Object {
+    generated_time: now.clone(),
+    last_modified: now.clone(),
+    point,
+    object_type,
+    parent_frame: None,
+    caused_by,
+  };
+  buffers.new_objects.push((id, object));
+}
+
+/// 生成を拒否したことを記録する
+fn reject_spawn<T: EventContents<U>, U: ObjectType>(
+  ctx: &mut Context<T, U>,
+  buffers: &mut RunBuffers<T, U>,
+  now: &Time,
+  object_type: U,
+  reason: &str,
+) {
+  ctx.record_spawn_failure(object_type.name(), object_type.generated_point(), reason);
+  buffers.errors.push(RunError::RejectedObject {
+    name: object_type.name(),
+    reason: reason.to_string(),
+  });
+  ctx.rejected_objects.push(RejectedObject {
+    time: now.clone(),
+    object_type,
+    reason: reason.to_string(),
+  });
+}
+
+/// 作業領域に種類ごとに積まれたものを削除・生成・移動・置き換えの順にまとめて世界に反映し、作業領域を空にする
+fn apply_buffers<T: EventContents<U>, U: ObjectType>(
+  ctx: &mut Context<T, U>,
+  buffers: &mut RunBuffers<T, U>,
+) {
+  buffers.occupied = None;
+  resolve_area_of_effect(ctx, &mut buffers.new_events);
+  buffers.remove_object_id.sort_unstable();
+  buffers.remove_object_id.dedup();
+  expand_area_effects(
+    &mut buffers.updates,
+    &mut buffers.area_updates,
+    &buffers.new_events,
+    &buffers.remove_object_id,
+  );
+  expand_area_effects(
+    &mut buffers.patches,
+    &mut buffers.area_patches,
+    &buffers.new_events,
+    &buffers.remove_object_id,
+  );
+  for event in buffers.new_events.iter() {
+    ctx.record_watched_event(event);
+    if ctx.timeline.enabled {
+      *ctx
+        .timeline
+        .events
+        .entry(event.contents.kind())
+        .or_insert(0) += 1;
+    }
+  }
+  #[cfg(feature = "tokio")]
+  ctx.stage_publish(&buffers.new_events, &mut buffers.unpublished);
+  ctx.remember(&mut buffers.new_events);
+  for object_id in buffers.remove_object_id.drain(..) {
+    let Some(object) = ctx.objects.remove(&object_id) else {
+      buffers
+        .errors
+        .push(RunError::RemoveTargetMissing { id: object_id });
+      continue;
+    };
+    ctx
+      .name_index
+      .remove(&object.object_type.name(), &object_id);
+    ctx.spatial_update(&object_id);
+    ctx.record_watch(&object_id, WatchKind::Removed);
+    ctx.push_inverse(InverseEffect::Restore(object_id, object));
+    ctx.timeline.removed += 1;
+  }
+  // 同じ単位時間に生成されたオブジェクトも移動や置き換えの対象にできるよう、先に生成する
+  for (object_id, object) in buffers.new_objects.drain(..) {
+    let name = object.object_type.name();
+    if let Some(old) = ctx.objects.insert(object_id.clone(), Arc::new(object)) {
+      ctx.name_index.remove(&old.object_type.name(), &object_id);
+      buffers.errors.push(RunError::DuplicateId {
+        id: object_id.clone(),
+      });
+      ctx.push_inverse(InverseEffect::Restore(object_id.clone(), old));
+    } else {
+      ctx.push_inverse(InverseEffect::Despawn(object_id.clone()));
+    }
+    ctx.name_index.insert(name, &object_id);
+    ctx.spatial_update(&object_id);
+    ctx.timeline.spawned += 1;
+  }
+  for (id, point) in buffers.moves.drain(..) {
+    let Some(obj) = ctx.objects.get(&id) else {
+      buffers.errors.push(RunError::MoveTargetMissing { id });
+      continue;
+    };
+    let point = match obj.object_type.max_move_per_tick() {
+      Some(limit) if ctx.squared_distance(&obj.point, &point) > &limit * &limit => {
+        match ctx.move_limit {
+          MoveLimit::Reject => {
+            let distance = ctx.distance(&obj.point, &point);
+            buffers.errors.push(RunError::MoveTooFar {
+              id,
+              distance,
+              limit,
+            });
+            continue;
+          }
+          MoveLimit::Clamp => ctx.clamp_move(&obj.point, &point, &limit),
+        }
+      }
+      _ => point,
+    };
+    let Some(obj) = ctx.objects.get_mut(&id) else {
+      continue;
+    };
+    // 移動先が現在地と同じなら何もしない
+    // 座標の比較はxから順に短絡評価されるので、動かないオブジェクトが多くても安価に済む
+    if obj.point == point {
+      continue;
+    }
+    let obj = Arc::make_mut(obj);
+    let last_modified = std::mem::replace(&mut obj.last_modified, ctx.time.clone());
+    let from = std::mem::replace(&mut obj.point, point);
+    let to = ctx.watched.contains(&id).then(|| obj.point.clone());
+    ctx.spatial_update(&id);
+    if let Some(to) = to {
+      let from = from.clone();
+      ctx.record_watch(&id, WatchKind::Moved { from, to });
+    }
+    ctx.push_inverse(InverseEffect::MoveBack {
+      id,
+      point: from,
+      last_modified,
+    });
+  }
+  // 親が動いたり削除されたりしたオブジェクトの、索引での位置を直す
+  ctx.spatial_update_framed();
+  for (id, object_type) in buffers.updates.drain(..) {
+    let Some(obj) = ctx.objects.get_mut(&id) else {
+      buffers.errors.push(RunError::UpdateTargetMissing { id });
+      continue;
+    };
+    // 中身が変わらないなら何もしない
+    if obj.object_type == object_type {
+      continue;
+    }
+    let obj = Arc::make_mut(obj);
+    let last_modified = std::mem::replace(&mut obj.last_modified, ctx.time.clone());
+    let new_name = object_type.name();
+    let object_type = std::mem::replace(&mut obj.object_type, object_type);
+    let old_name = object_type.name();
+    if old_name != new_name {
+      ctx.name_index.remove(&old_name, &id);
+      ctx.name_index.insert(new_name, &id);
+    }
+    ctx.push_inverse(InverseEffect::Revert {
+      id,
+      object_type,
+      last_modified,
+    });
+  }
+  for (id, patch) in buffers.patches.drain(..) {
+    let Some(obj) = ctx.objects.get_mut(&id) else {
+      buffers.errors.push(RunError::UpdateTargetMissing { id });
+      continue;
+    };
+    // 変更を加えても中身が変わらないなら何もしない
+    let mut patched = obj.object_type.clone();
+    patched.apply_patch(patch);
+    if obj.object_type == patched {
+      continue;
+    }
+    let obj = Arc::make_mut(obj);
+    let last_modified = std::mem::replace(&mut obj.last_modified, ctx.time.clone());
+    let object_type = std::mem::replace(&mut obj.object_type, patched);
+    let old_name = object_type.name();
+    let new_name = obj.object_type.name();
+    if old_name != new_name {
+      ctx.name_index.remove(&old_name, &id);
+      ctx.name_index.insert(new_name, &id);
+    }
+    ctx.push_inverse(InverseEffect::Revert {
+      id,
+      object_type,
+      last_modified,
+    });
+  }
+}
+
+/// 地形と登録されている検査を順に通し、最初に通らなかった検査の理由を返す
+fn validate_event<T: EventContents<U>, U: ObjectType>(
+  ctx: &Context<T, U>,
+  e: &T,
+  effects: &[Effect<U>],
+) -> Option<String> {
+  for effect in effects {
+    match effect {
+      Effect::Move(_, point) if !ctx.is_passable(point) => {
+        return Some("destination is not passable".to_string());
+      }
+      Effect::Spawn(object_type)
+        if !ctx.is_passable(&ctx.wrap_point(object_type.generated_point())) =>
+      {
+        return Some("generated point is not passable".to_string());
+      }
+      _ => {}
+    }
+  }
+  ctx
+    .event_validators
+    .iter()
+    .find_map(|validator| validator(e, ctx).err())
+}
+
+/// イベントが起きた地点を決める
+/// イベント自身が地点を持たない場合は主体のオブジェクトの現在地を使う
+fn event_location<T: EventContents<U>, U: ObjectType>(ctx: &Context<T, U>, e: &T) -> Option<Point> {
+  e.location().or_else(|| actor_location(ctx, &e.do_object()))
+}
+
+/// 主体のオブジェクトの現在地
+fn actor_location<T: EventContents<U>, U: ObjectType>(
+  ctx: &Context<T, U>,
+  do_object: &str,
+) -> Option<Point> {
+  ctx
+    .objects
+    .get(do_object)
+    .map(|object| ctx.located_point(do_object, object).into_owned())
+}
+
+/// 範囲に影響を及ぼすイベントについて、範囲内にいるオブジェクトを求める
+/// 範囲を持つイベントがある場合にだけ、最大の半径をセルの大きさとした索引を作って候補を絞り込む
+/// 親を持つオブジェクトは世界での位置で測る
+fn resolve_area_of_effect<T: EventContents<U>, U: ObjectType>(
+  ctx: &Context<T, U>,
+  events: &mut [Event<T>],
+) {
+  let areas = events
+    .iter()
+    .map(|e| e.contents.area_of_effect())
+    .collect::<Vec<_>>();
+  let Some(max_radius) = areas.iter().flatten().map(|(_, radius)| radius).max() else {
+    return;
+  };
+  let points = ctx
+    .objects
+    .iter()
+    .map(|(id, object)| (id, ctx.located_point(id, object)))
+    .collect::<FxHashMap<_, _>>();
+  let mut grid = SpatialGrid::new(max_radius.clone());
+  for (id, point) in points.iter() {
+    grid.insert((*id).clone(), point);
+  }
+  for (event, area) in events.iter_mut().zip(areas) {
+    if let Some((center, radius)) = area {
+      let squared_radius = &radius * &radius;
+      let mut affected = ctx
+        .grid_candidates(&grid, &center, &radius)
+        .into_iter()
+        .filter(|id| ctx.squared_distance(&points[*id], &center) <= squared_radius)
+        .cloned()
+        .collect::<Vec<_>>();
+      affected.sort();
+      event.affected_objects = affected;
+    }
+  }
+}
+
+/// 範囲を持つイベントによる置き換えや変更を、範囲内の各オブジェクトへのものに広げて`direct`に混ぜる
+/// イベントを積んだ順序を保つため、積んだ時点での`direct`の長さの位置に差し込む
+/// `removed`は辞書順に並んだ、この単位時間に削除されるオブジェクトのID
+fn expand_area_effects<T: EventContents<U>, U: ObjectType, V: Clone>(
+  direct: &mut Vec<(String, V)>,
+  area: &mut Vec<(usize, usize, V)>,
+  events: &[Event<T>],
+  removed: &[String],
+) {
+  if area.is_empty() {
+    return;
+  }
+  let mut area = area.drain(..).peekable();
+  let mut expanded = Vec::with_capacity(direct.len());
+  for (position, entry) in std::mem::take(direct).into_iter().enumerate() {
+    while let Some((_, index, value)) = area.next_if(|(p, _, _)| *p == position) {
+      expand_area_effect(&mut expanded, &events[index], value, removed);
+    }
+    expanded.push(entry);
+  }
+  for (_, index, value) in area {
+    expand_area_effect(&mut expanded, &events[index], value, removed);
+  }
+  *direct = expanded;
+}
+
+/// 範囲を持つイベント一つによる置き換えや変更を、範囲内の各オブジェクトに向けて積む
+fn expand_area_effect<T, V: Clone>(
+  expanded: &mut Vec<(String, V)>,
+  event: &Event<T>,
+  value: V,
+  removed: &[String],
+) {
+  expanded.extend(
+    event
+      .affected_objects
+      .iter()
+      .filter(|id| removed.binary_search(id).is_err())
+      .map(|id| (id.clone(), value.clone())),
+  );
+}
+
+/// イベントのIDを組み立てるための作業領域
+/// 同じ単位時間に起きたイベントは時刻の部分が共通なので、文字列にした時刻を使い回す
+#[derive(Debug, Clone, Default)]
+struct EventIdBuffer {
+  /// 直前に文字列にした経過単位時間
+  all: Option<BigUint>,
+  /// `all`を文字列にしたもの
+  all_label: String,
+  /// Base64にする前のID
+  raw: String,
+}
+
+impl EventIdBuffer {
+  /// イベントのIDを生成する
+  /// <主体のオブジェクトのID><起きた単位時間>#<イベントの通し番号>
+  /// で文字列生成してさらにBase64エンコード
+  fn generate(&mut self, do_object: &str, generated_time: &BigUint, sequence: u64) -> String {
+    if self.all.as_ref() != Some(generated_time) {
+      self.all_label.clear();
+      write!(self.all_label, "{generated_time:?}").expect("writing to a String never fails");
+      self.all = Some(generated_time.clone());
+    }
+    self.raw.clear();
+    self.raw.push_str(do_object);
+    self.raw.push_str(&self.all_label);
+    write!(self.raw, "#{sequence}").expect("writing to a String never fails");
+    base64::encode(self.raw.as_bytes())
+  }
+}
+
+/// オブジェクトのIDを自動で生成する
+/// <object_type><生成された地点><生成された単位時間>#<通し番号>
+/// で文字列生成してさらにBase64エンコード
+/// 通し番号はオブジェクトを生成するたびに増えるので、他の要素が全て同じでもIDは重ならない
+/// 同じ世界から同じように進めれば同じIDになる
+#[cfg(not(feature = "wall-clock-ids"))]
+fn generate_object_id(
+  object_name: &str,
+  point: &Point,
+  generate_time: &BigUint,
+  number: u64,
+) -> String {
+  let str = format!("{object_name}{point:?}{generate_time:?}#{number}");
+  base64::encode(str.as_bytes())
 }
 
 /// オブジェクトのIDを自動で生成する
-/// <object_type><生成された地点><生成された単位時間><実世界の生成されたときの時刻>
+/// <object_type><生成された地点><生成された単位時間>#<通し番号><実世界の生成されたときの時刻>
 /// で文字列生成してさらにBase64エンコード
-fn generate_object_id(object_name: &str, point: &Point, generate_time: &BigUint) -> String {
-  let now = SystemTime::now();
-  let str = format!("{object_name}{point:?}{generate_time:?}{now:?}");
+/// 実行するたびに異なるIDになる
+#[cfg(feature = "wall-clock-ids")]
+fn generate_object_id(
+  object_name: &str,
+  point: &Point,
+  generate_time: &BigUint,
+  number: u64,
+) -> String {
+  let now = std::time::SystemTime::now();
+  let str = format!("{object_name}{point:?}{generate_time:?}#{number}{now:?}");
   base64::encode(str.as_bytes())
 }
@@ -0,0 +1,78 @@
+//! 単位時間ごとの処理時間の記録
+
+use crate::{Context, EventContents, ObjectType};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// 直近の単位時間ごとの処理時間を保持するもの
+/// 計測は`tick-metrics`機能を有効にした場合だけ行われる
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TickMetrics {
+  /// 平均を取る単位時間の数
+  window: usize,
+  /// 直近の処理時間
+  /// 古いものほど前にある
+  durations: VecDeque<Duration>,
+  /// `durations`の合計
+  total: Duration,
+}
+
+impl TickMetrics {
+  /// 直近`window`個の単位時間の平均を取る記録の新たな生成
+  /// `window`が0の場合は1として扱う
+  pub fn new(window: usize) -> Self {
+    TickMetrics {
+      window: window.max(1),
+      durations: VecDeque::new(),
+      total: Duration::ZERO,
+    }
+  }
+
+  /// 平均を取る単位時間の数を変える
+  /// 数を減らした場合は古い記録から捨てる
+  pub fn set_window(&mut self, window: usize) {
+    self.window = window.max(1);
+    self.truncate();
+  }
+
+  /// 処理時間を一つ記録する
+  #[cfg_attr(not(feature = "tick-metrics"), allow(dead_code))]
+  pub(crate) fn record(&mut self, duration: Duration) {
+    self.durations.push_back(duration);
+    self.total += duration;
+    self.truncate();
+  }
+
+  /// 記録を`window`個以下に減らす
+  fn truncate(&mut self) {
+    while self.durations.len() > self.window {
+      if let Some(old) = self.durations.pop_front() {
+        self.total -= old;
+      }
+    }
+  }
+
+  /// 直近の処理時間の移動平均
+  /// 一度も記録されていない場合は0を返す
+  pub fn average(&self) -> Duration {
+    match u32::try_from(self.durations.len()) {
+      Ok(0) => Duration::ZERO,
+      Ok(len) => self.total / len,
+      Err(_) => Duration::ZERO,
+    }
+  }
+}
+
+impl Default for TickMetrics {
+  fn default() -> Self {
+    TickMetrics::new(16)
+  }
+}
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// 直近の単位時間ごとの処理時間の移動平均
+  /// `tick-metrics`機能が無効な場合は常に0を返す
+  pub fn recent_tick_duration(&self) -> Duration {
+    self.tick_metrics.average()
+  }
+}
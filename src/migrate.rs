@@ -0,0 +1,354 @@
+//! 保存したオブジェクトの構造が変わっても古い保存データを読み込めるようにするための仕組み
+
+use crate::{Context, Event, EventContents, InjectedEvent, Object, ObjectType, Point, Time};
+use crate::{EventOrder, MoveLimit, PopulationController, SpawnPlacement, Terrain, TerrainMask};
+use crate::{Torus, Weather, WeatherTable};
+use num_bigint::BigUint;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::de::{self, DeserializeOwned, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// 版を持ち、古い版の保存データから変換できる型
+pub trait Migrate: Serialize + DeserializeOwned {
+  /// 現在の版
+  const VERSION: u32;
+
+  /// `from_version`の版で保存されたデータを読み込んで現在の構造に変換する
+  /// `from_version`は`VERSION`より小さい
+  /// 古い版の構造体を`deserializer`から読み込んでから変換すると良い
+  fn migrate<'de, D: Deserializer<'de>>(
+    from_version: u32,
+    deserializer: D,
+  ) -> Result<Self, D::Error>;
+}
+
+/// 版と一緒に保存されるデータ
+/// 読み込み時に版が古ければ`Migrate::migrate`で変換し、新しければエラーにする
+/// 版はデータより先に保存されている必要がある
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Versioned<T>(pub T);
+
+impl<T: Migrate> Serialize for Versioned<T> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut state = serializer.serialize_struct("Versioned", 2)?;
+    state.serialize_field("version", &T::VERSION)?;
+    state.serialize_field("data", &self.0)?;
+    state.end()
+  }
+}
+
+impl<'de, T: Migrate> Deserialize<'de> for Versioned<T> {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    deserializer.deserialize_struct(
+      "Versioned",
+      &["version", "data"],
+      VersionedVisitor(PhantomData),
+    )
+  }
+}
+
+struct VersionedVisitor<T>(PhantomData<T>);
+
+impl<'de, T: Migrate> Visitor<'de> for VersionedVisitor<T> {
+  type Value = Versioned<T>;
+
+  fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str("struct Versioned")
+  }
+
+  fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+    let version = seq
+      .next_element()?
+      .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+    let data = seq
+      .next_element_seed(MigrateSeed::<T>(version, PhantomData))?
+      .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+    Ok(Versioned(data))
+  }
+
+  fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+    let mut version = None;
+    let mut data = None;
+    while let Some(key) = map.next_key::<String>()? {
+      match key.as_str() {
+        "version" => version = Some(map.next_value()?),
+        "data" => {
+          let version =
+            version.ok_or_else(|| de::Error::custom("`version` must come before `data`"))?;
+          data = Some(map.next_value_seed(MigrateSeed::<T>(version, PhantomData))?);
+        }
+        _ => {
+          map.next_value::<de::IgnoredAny>()?;
+        }
+      }
+    }
+    data
+      .map(Versioned)
+      .ok_or_else(|| de::Error::missing_field("data"))
+  }
+}
+
+/// 保存されていた版に応じて読み込み方を切り替える
+struct MigrateSeed<T>(u32, PhantomData<T>);
+
+impl<'de, T: Migrate> DeserializeSeed<'de> for MigrateSeed<T> {
+  type Value = T;
+
+  fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<T, D::Error> {
+    let version = self.0;
+    if version == T::VERSION {
+      T::deserialize(deserializer)
+    } else if version < T::VERSION {
+      T::migrate(version, deserializer)
+    } else {
+      Err(de::Error::custom(format!(
+        "version {version} is newer than the supported version {}",
+        T::VERSION
+      )))
+    }
+  }
+}
+
+/// 保存用のオブジェクト
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "U: Migrate")]
+pub struct SavedObject<U> {
+  /// オブジェクトのID
+  pub id: String,
+  /// 生成時刻
+  pub generated_time: Time,
+  /// 最後に変化した時刻
+  pub last_modified: Time,
+  /// 現在地
+  pub point: Point,
+  /// 版付きのオブジェクトの種類
+  pub object_type: Versioned<U>,
+  /// 座標の基準にする親のオブジェクトのID
+  #[serde(default)]
+  pub parent_frame: Option<String>,
+  /// オブジェクトを生成したイベントのID
+  #[serde(default)]
+  pub caused_by: Option<String>,
+}
+
+/// 世界の時刻とオブジェクトを保存したもの
+/// イベントの記憶や検査などは含まれない
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "U: Migrate")]
+pub struct WorldSave<U> {
+  /// 保存時の時刻
+  pub time: Time,
+  /// 保存時に存在した全てのオブジェクト
+  /// IDの辞書順に並ぶ
+  pub objects: Vec<SavedObject<U>>,
+}
+
+/// 世界の時刻とオブジェクトに加えて、イベントの記憶や予約と世界の設定も保存したもの
+/// 読み込んだ世界は保存した世界と同じように進む
+/// 検査・購読者・関数で与えた地形など関数を持つものと、年表・時系列・監視の設定と記録は含まれない
+/// `Context::rng`の乱数生成器は保存されず、読み込み時に`seed`から作り直される
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound(
+  serialize = "T: Serialize, U: Migrate",
+  deserialize = "T: DeserializeOwned, U: Migrate"
+))]
+pub struct WorldState<T, U> {
+  /// 時刻とオブジェクト
+  pub world: WorldSave<U>,
+  /// 記憶されていたイベント
+  /// 記憶された順に並ぶ
+  pub memory: Vec<Event<T>>,
+  /// 予約されていたイベント
+  pub scheduled_events: Vec<InjectedEvent<T>>,
+  /// 次に記録されるイベントの通し番号
+  pub next_event_sequence: u64,
+  /// 次に生成されるオブジェクトの通し番号
+  pub next_object_number: u64,
+  /// 天候
+  pub weather: Weather,
+  /// 天候の移り変わり方
+  #[serde(default)]
+  pub weather_table: WeatherTable,
+  /// 乱数の種の元
+  #[serde(default)]
+  pub seed: u64,
+  /// 記憶しておけるイベントの数の上限
+  #[serde(default)]
+  pub memory_capacity: Option<usize>,
+  /// 表で与えた地形
+  #[serde(default)]
+  pub terrain: Option<TerrainMask>,
+  /// 端と端がつながった世界の大きさ
+  #[serde(default)]
+  pub torus: Option<Torus>,
+  /// 近くにあるオブジェクトを探すための索引のセルの一辺の長さ
+  #[serde(default)]
+  pub spatial_cell_size: Option<BigUint>,
+  /// 生成先のセルが既に埋まっている場合の配置の設定
+  #[serde(default)]
+  pub spawn_placement: Option<SpawnPlacement>,
+  /// 配置できなかった生成を記録するかどうか
+  #[serde(default)]
+  pub record_spawn_failures: bool,
+  /// generate関数が返したイベントを処理する順序
+  #[serde(default)]
+  pub event_order: EventOrder,
+  /// 不整合が起きた時に単位時間を進めずに失敗させるかどうか
+  #[serde(default)]
+  pub strict: bool,
+  /// 一回の単位時間に移動できる距離を超える移動の扱い
+  #[serde(default)]
+  pub move_limit: MoveLimit,
+  /// オブジェクトの数を目標値に近づけるための制御器とその状態
+  #[serde(default)]
+  pub population_controller: Option<PopulationController>,
+}
+
+impl<T: EventContents<U>, U: ObjectType + Migrate> Context<T, U> {
+  /// 時刻とオブジェクトを保存用の形にする
+  pub fn save(&self) -> WorldSave<U> {
+    let mut objects = self
+      .objects
+      .iter()
+      .map(|(id, object)| SavedObject {
+        id: id.clone(),
+        generated_time: object.generated_time.clone(),
+        last_modified: object.last_modified.clone(),
+        point: object.point.clone(),
+        object_type: Versioned(object.object_type.clone()),
+        parent_frame: object.parent_frame.clone(),
+        caused_by: object.caused_by.clone(),
+      })
+      .collect::<Vec<_>>();
+    objects.sort_by(|a, b| a.id.cmp(&b.id));
+    WorldSave {
+      time: self.time.clone(),
+      objects,
+    }
+  }
+
+  /// 保存したものから時刻とオブジェクトを読み込み、今の時刻とオブジェクトを置き換える
+  /// イベントの記憶や検査などはそのまま残る
+  pub fn load(&mut self, save: WorldSave<U>) {
+    self.time = save.time;
+    self.objects = save
+      .objects
+      .into_iter()
+      .map(|saved| {
+        let object = Object {
+          generated_time: saved.generated_time,
+          last_modified: saved.last_modified,
+          point: saved.point,
+          object_type: saved.object_type.0,
+          parent_frame: saved.parent_frame,
+          caused_by: saved.caused_by,
+        };
+        (saved.id, Arc::new(object))
+      })
+      .collect();
+    self.rebuild_name_index();
+    self.rebuild_spatial_index();
+  }
+
+  /// 時刻とオブジェクトに加えて、イベントの記憶や予約と世界の設定も保存用の形にする
+  pub fn save_state(&self) -> WorldState<T, U>
+  where
+    T: Serialize + DeserializeOwned,
+  {
+    WorldState {
+      world: self.save(),
+      memory: self.memory.iter().map(|event| (**event).clone()).collect(),
+      scheduled_events: self.scheduled_events.clone(),
+      next_event_sequence: self.next_event_sequence,
+      next_object_number: self.next_object_number,
+      weather: self.weather,
+      weather_table: self.weather_table.clone(),
+      seed: self.seed,
+      memory_capacity: self.memory_capacity,
+      terrain: match &self.terrain {
+        Some(Terrain::Mask(mask)) => Some(mask.clone()),
+        _ => None,
+      },
+      torus: self.torus.clone(),
+      spatial_cell_size: self
+        .spatial_index
+        .as_ref()
+        .map(|grid| grid.cell_size().clone()),
+      spawn_placement: self.spawn_placement.clone(),
+      record_spawn_failures: self.record_spawn_failures,
+      event_order: self.event_order,
+      strict: self.strict,
+      move_limit: self.move_limit,
+      population_controller: self.population_controller.clone(),
+    }
+  }
+
+  /// `save_state`で保存したものを読み込み、今の状態と設定を置き換える
+  /// 検査や購読者などはそのまま残り、直前の単位時間を巻き戻すための記録は捨てられる
+  /// 関数で与えた地形は保存されないので、表で与えた地形が保存されていなければそのまま残る
+  pub fn load_state(&mut self, state: WorldState<T, U>)
+  where
+    T: Serialize + DeserializeOwned,
+  {
+    // 保存した世界の地点は既に範囲内に折り返されている
+    self.torus = state.torus;
+    self.load(state.world);
+    self.memory = state.memory.into_iter().map(Arc::new).collect();
+    self.scheduled_events = state.scheduled_events;
+    self.next_event_sequence = state.next_event_sequence;
+    self.next_object_number = state.next_object_number;
+    self.weather = state.weather;
+    self.weather_table = state.weather_table;
+    self.seed = state.seed;
+    self.rng = StdRng::seed_from_u64(state.seed);
+    self.memory_capacity = state.memory_capacity;
+    if let Some(mask) = state.terrain {
+      self.terrain = Some(Terrain::Mask(mask));
+    }
+    self.spawn_placement = state.spawn_placement;
+    self.record_spawn_failures = state.record_spawn_failures;
+    self.event_order = state.event_order;
+    self.strict = state.strict;
+    self.move_limit = state.move_limit;
+    self.population_controller = state.population_controller;
+    self.set_spatial_index(state.spatial_cell_size);
+    self.undo = None;
+  }
+
+  /// `save_state`で保存したものから世界を作る
+  pub fn from_state(state: WorldState<T, U>) -> Self
+  where
+    T: Serialize + DeserializeOwned,
+  {
+    let mut ctx = Context::new(state.world.time.clone(), []);
+    ctx.load_state(state);
+    ctx
+  }
+}
+
+/// `Context::save_state`の形で保存する
+impl<T, U> Serialize for Context<T, U>
+where
+  T: EventContents<U> + Serialize + DeserializeOwned,
+  U: ObjectType + Migrate,
+{
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    self.save_state().serialize(serializer)
+  }
+}
+
+/// `Context::from_state`で読み込む
+impl<'de, T, U> Deserialize<'de> for Context<T, U>
+where
+  T: EventContents<U> + Serialize + DeserializeOwned,
+  U: ObjectType + Migrate,
+{
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    WorldState::deserialize(deserializer).map(Context::from_state)
+  }
+}
@@ -0,0 +1,76 @@
+//! オブジェクトの種類の名前からIDを引くための索引
+
+use crate::{Context, EventContents, Object, ObjectType};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+/// 種類の名前ごとのオブジェクトのIDの集合
+/// `run`や`Context`のメソッドを通した変更では自動で更新される
+/// `Context::objects`を直接書き換えた場合は`Context::rebuild_name_index`を呼ぶ必要がある
+#[derive(Debug, Clone, Default)]
+pub struct NameIndex {
+  ids: FxHashMap<String, FxHashSet<String>>,
+}
+
+impl NameIndex {
+  pub(crate) fn insert(&mut self, name: String, id: &str) {
+    self.ids.entry(name).or_default().insert(id.to_string());
+  }
+
+  /// 種類の名前ごとのオブジェクトの数
+  pub(crate) fn counts(&self) -> impl Iterator<Item = (&String, usize)> {
+    self.ids.iter().map(|(name, ids)| (name, ids.len()))
+  }
+
+  pub(crate) fn remove(&mut self, name: &str, id: &str) {
+    if let Some(ids) = self.ids.get_mut(name) {
+      ids.remove(id);
+      if ids.is_empty() {
+        self.ids.remove(name);
+      }
+    }
+  }
+}
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// 指定した名前の種類のオブジェクトを全て返す
+  /// 索引を使うので、その種類のオブジェクトだけを走査する
+  /// 順序は決まっていない
+  pub fn objects_of_name<'a>(
+    &'a self,
+    name: &str,
+  ) -> impl Iterator<Item = (&'a String, &'a Object<U>)> + 'a {
+    self
+      .name_index
+      .ids
+      .get(name)
+      .into_iter()
+      .flatten()
+      .filter_map(|id| self.objects.get_key_value(id))
+      .map(|(id, object)| (id, object.as_ref()))
+  }
+
+  /// 種類の名前ごとのオブジェクトの数
+  /// 索引を使うので、オブジェクトの数ではなく種類の数に比例する時間で済む
+  /// 一つもない種類は含まれない
+  pub fn count_by_name(&self) -> FxHashMap<String, usize> {
+    self
+      .name_index
+      .counts()
+      .map(|(name, count)| (name.clone(), count))
+      .collect()
+  }
+
+  /// 指定した名前の種類のオブジェクトの数
+  pub fn count_of_name(&self, name: &str) -> usize {
+    self.name_index.ids.get(name).map_or(0, |ids| ids.len())
+  }
+
+  /// `objects`から索引を作り直す
+  pub fn rebuild_name_index(&mut self) {
+    let mut index = NameIndex::default();
+    for (id, object) in self.objects.iter() {
+      index.insert(object.object_type.name(), id);
+    }
+    self.name_index = index;
+  }
+}
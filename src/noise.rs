@@ -0,0 +1,64 @@
+//! 座標から決まった値を引くための、乱数を使わないノイズ
+
+use crate::{Context, EventContents, ObjectType, Point};
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+
+/// 最も大きい格子の間隔
+const NOISE_CELL: u64 = 16;
+/// 重ね合わせる格子の数
+/// 格子の間隔は一つごとに半分になる
+const NOISE_OCTAVES: u32 = 4;
+
+/// splitmix64の攪拌関数
+pub(crate) fn mix(mut h: u64) -> u64 {
+  h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+  h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+  h ^ (h >> 31)
+}
+
+pub(crate) fn hash_biguint(n: &BigUint, h: u64) -> u64 {
+  n.iter_u64_digits().fold(mix(h), |h, digit| mix(h ^ digit))
+}
+
+/// 格子点に割り当てられた`[0, 1)`の値
+fn lattice_value(x: &BigUint, y: &BigUint, seed: u64) -> f64 {
+  let h = hash_biguint(y, hash_biguint(x, seed));
+  (h >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn smoothstep(t: f64) -> f64 {
+  t * t * (3.0 - 2.0 * t)
+}
+
+/// 間隔`cell`の格子点の値を滑らかに補間した値
+fn octave(point: &Point, seed: u64, cell: u64) -> f64 {
+  let cell_big = BigUint::from(cell);
+  let (x0, y0) = (&point.x / &cell_big, &point.y / &cell_big);
+  let fraction = |n: &BigUint| smoothstep((n % &cell_big).to_f64().unwrap_or(0.0) / cell as f64);
+  let (tx, ty) = (fraction(&point.x), fraction(&point.y));
+  let x1 = &x0 + 1u32;
+  let y1 = &y0 + 1u32;
+  let top = lattice_value(&x0, &y0, seed) * (1.0 - tx) + lattice_value(&x1, &y0, seed) * tx;
+  let bottom = lattice_value(&x0, &y1, seed) * (1.0 - tx) + lattice_value(&x1, &y1, seed) * tx;
+  top * (1.0 - ty) + bottom * ty
+}
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// 座標とシードだけから決まる`[0, 1)`の値
+  /// 同じ座標とシードに対しては常に同じ値を返し、近い座標ほど近い値になる
+  /// 値が大きく変わるのはおよそ16マスごとである
+  ///
+  /// 乱数の消費順によらずに地形の肥沃度や気候のような場を表すのに使う
+  pub fn value_noise(&self, point: &Point, seed: u64) -> f64 {
+    let mut total = 0.0;
+    let mut weight = 1.0;
+    let mut weight_sum = 0.0;
+    for i in 0..NOISE_OCTAVES {
+      total += octave(point, mix(seed ^ u64::from(i)), NOISE_CELL >> i) * weight;
+      weight_sum += weight;
+      weight /= 2.0;
+    }
+    total / weight_sum
+  }
+}
@@ -0,0 +1,66 @@
+//! 一つの単位時間の中でイベントを処理する順序
+
+use crate::{event_location, Context, EventContents, ObjectType, Point};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// generate関数が返したイベントを処理する順序
+/// 処理順は移動や置き換えがどの順に反映されるかと、記憶される順序に影響する
+/// どの順序でも同じ順位のイベントは登録順のまま並ぶので、結果は決定的である
+///
+/// 一つの単位時間に起きるイベントは全て同じ時刻を持ち、通し番号は処理した順に振られるため、
+/// 「時刻と通し番号の順」は登録順と一致する
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum EventOrder {
+  /// generate関数を並べた順、その中ではイベントを返した順
+  #[default]
+  Registration,
+  /// `EventContents::priority`が大きいものから
+  Priority,
+  /// イベントが起きた地点のモートン順（Z順）
+  /// 地点が決まらないイベントは最後に回す
+  Spatial,
+}
+
+/// 二つの地点をモートン順で比べる
+/// x座標とy座標のビットを交互に並べた値を比べるのと同じだが、実際に並べた値は作らない
+fn morton_cmp(a: &Point, b: &Point) -> Ordering {
+  let dx = &a.x ^ &b.x;
+  let dy = &a.y ^ &b.y;
+  // 最上位の異なるビットがより上にある座標で順序が決まる
+  // 同じ位置の場合はyのビットを上位とする
+  if dy.bits() >= dx.bits() {
+    a.y.cmp(&b.y)
+  } else {
+    a.x.cmp(&b.x)
+  }
+}
+
+/// イベントを指定した順序に並べ替える
+pub(crate) fn sort_events<T: EventContents<U>, U: ObjectType>(
+  ctx: &Context<T, U>,
+  order: EventOrder,
+  events: &mut [&T],
+) {
+  match order {
+    EventOrder::Registration => {}
+    EventOrder::Priority => events.sort_by_key(|e| std::cmp::Reverse(e.priority())),
+    EventOrder::Spatial => {
+      let mut keyed = events
+        .iter()
+        .map(|e| (event_location(ctx, e), *e))
+        .collect::<Vec<_>>();
+      keyed.sort_by(|(a, _), (b, _)| match (a, b) {
+        (Some(a), Some(b)) => morton_cmp(a, b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+      });
+      for (slot, (_, e)) in events.iter_mut().zip(keyed) {
+        *slot = e;
+      }
+    }
+  }
+}
@@ -0,0 +1,43 @@
+//! rayonのスレッドプールを使った並列実行
+
+use crate::seed::as_generator;
+use crate::TickError;
+use crate::{run_with, Context, EventContents, GeneratedData, Generater, Object, ObjectType};
+use rayon::prelude::*;
+
+/// `run`と同じく単位時間を一つだけ進めるが、generate関数をrayonのスレッドプールで並列に実行する
+/// 全てのgenerate関数の実行が終わってから、その結果をgenerate関数を並べた順に反映するので、
+/// イベントの通し番号やオブジェクトのIDは実行が終わった順によらず`run`と一致する
+/// 不整合の扱いも`run`と同じく`Context::strict`で決まる
+/// generate関数の中で乱数が必要な場合は`Context::generator_seed`を使えば、並べた位置ごとに独立した乱数列が得られる
+pub fn par_run<T, U>(
+  ctx: &mut Context<T, U>,
+  generate_functions: Vec<Generater<T, U>>,
+) -> Result<Vec<GeneratedData<T, U>>, TickError>
+where
+  T: EventContents<U> + Send + Sync,
+  U: ObjectType + Send + Sync,
+  U::Patch: Send + Sync,
+{
+  run_with(ctx, |ctx| {
+    generate_functions
+      .par_iter()
+      .enumerate()
+      .map(|(i, f)| as_generator(i, || f(ctx)))
+      .collect()
+  })
+}
+
+impl<T: EventContents<U>, U: ObjectType + Send + Sync> Context<T, U> {
+  /// 全てのオブジェクトを並列に走査する
+  /// オブジェクトはIDの辞書順に並ぶので、`collect`などで集めた結果の順序はスレッドの数や実行順によらない
+  pub fn par_objects(&self) -> impl IndexedParallelIterator<Item = (&String, &Object<U>)> {
+    let mut objects = self
+      .objects
+      .iter()
+      .map(|(id, object)| (id, object.as_ref()))
+      .collect::<Vec<_>>();
+    objects.par_sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+    objects.into_par_iter()
+  }
+}
@@ -0,0 +1,154 @@
+//! セル単位での経路探索
+
+use crate::{Context, EventContents, ObjectType, Point};
+use num_bigint::BigUint;
+use rustc_hash::FxHashMap;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// 縦横の一歩の費用
+const STRAIGHT_COST: u32 = 5;
+/// 斜めの一歩の費用
+/// `STRAIGHT_COST`の約√2倍
+const DIAGONAL_COST: u32 = 7;
+
+/// 経路探索の設定
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathOptions {
+  /// 斜めに移動できるかどうか
+  /// 斜めに移動する場合でも、通行できないセルの角をすり抜けることはできない
+  pub diagonal: bool,
+  /// 調べるセルの数の上限
+  /// 座標は上方向に際限なく広がるため、辿り着けない目的地を探し続けないように打ち切る
+  pub max_visited: usize,
+}
+
+impl Default for PathOptions {
+  fn default() -> Self {
+    PathOptions {
+      diagonal: false,
+      max_visited: 65536,
+    }
+  }
+}
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// `from`から`to`までの経路をA*で探す
+  /// 通行できるかどうかは`passable`で判定し、返す経路は両端を含む
+  /// 見つからなかった場合や`max_visited`を超えた場合は`None`を返す
+  pub fn find_path(
+    &self,
+    from: &Point,
+    to: &Point,
+    options: &PathOptions,
+    passable: impl Fn(&Point) -> bool,
+  ) -> Option<Vec<Point>> {
+    if from == to {
+      return Some(vec![from.clone()]);
+    }
+    if !passable(to) {
+      return None;
+    }
+    let mut points = vec![from.clone()];
+    let mut costs = vec![BigUint::ZERO];
+    let mut parents: Vec<Option<usize>> = vec![None];
+    let mut closed = vec![false];
+    let mut indices = FxHashMap::default();
+    indices.insert(from.clone(), 0);
+    // 推定費用が同じ場合は目的地に近い方、それも同じなら先に見つけた方を優先する
+    let mut open = BinaryHeap::new();
+    let h = heuristic(from, to, options.diagonal);
+    open.push(Reverse((h.clone(), h, 0)));
+    let mut visited = 0;
+    while let Some(Reverse((_, _, index))) = open.pop() {
+      if closed[index] {
+        continue;
+      }
+      if points[index] == *to {
+        let mut path = vec![points[index].clone()];
+        let mut current = index;
+        while let Some(parent) = parents[current] {
+          path.push(points[parent].clone());
+          current = parent;
+        }
+        path.reverse();
+        return Some(path);
+      }
+      closed[index] = true;
+      visited += 1;
+      if visited > options.max_visited {
+        return None;
+      }
+      for (next, step_cost) in neighbors(&points[index], options.diagonal, &passable) {
+        let cost = &costs[index] + step_cost;
+        let next_index = match indices.get(&next) {
+          Some(&i) if closed[i] || costs[i] <= cost => continue,
+          Some(&i) => i,
+          None => {
+            points.push(next.clone());
+            costs.push(cost.clone());
+            parents.push(None);
+            closed.push(false);
+            indices.insert(next, points.len() - 1);
+            points.len() - 1
+          }
+        };
+        costs[next_index] = cost.clone();
+        parents[next_index] = Some(index);
+        let h = heuristic(&points[next_index], to, options.diagonal);
+        open.push(Reverse((cost + &h, h, next_index)));
+      }
+    }
+    None
+  }
+}
+
+/// 目的地までの費用の下限
+fn heuristic(point: &Point, to: &Point, diagonal: bool) -> BigUint {
+  let dx = crate::abs_diff(&point.x, &to.x);
+  let dy = crate::abs_diff(&point.y, &to.y);
+  if diagonal {
+    let (long, short) = if dx >= dy { (dx, dy) } else { (dy, dx) };
+    long * STRAIGHT_COST + short * (DIAGONAL_COST - STRAIGHT_COST)
+  } else {
+    (dx + dy) * STRAIGHT_COST
+  }
+}
+
+/// 一歩で移動できる通行可能なセルとその費用
+fn neighbors(
+  point: &Point,
+  diagonal: bool,
+  passable: &impl Fn(&Point) -> bool,
+) -> Vec<(Point, u32)> {
+  let shift = |value: &BigUint, d: i8| match d {
+    -1 if *value == BigUint::ZERO => None,
+    -1 => Some(value - 1u32),
+    1 => Some(value + 1u32),
+    _ => Some(value.clone()),
+  };
+  let moved = |dx: i8, dy: i8| {
+    Some(Point {
+      x: shift(&point.x, dx)?,
+      y: shift(&point.y, dy)?,
+    })
+    .filter(|p| passable(p))
+  };
+  let mut result = Vec::new();
+  for (dx, dy) in [(1, 0), (0, 1), (-1, 0), (0, -1)] {
+    if let Some(p) = moved(dx, dy) {
+      result.push((p, STRAIGHT_COST));
+    }
+  }
+  if diagonal {
+    for (dx, dy) in [(1, 1), (-1, 1), (-1, -1), (1, -1)] {
+      // 角をすり抜けないように、縦横の両方のセルが通行できる時だけ斜めに進める
+      if moved(dx, 0).is_some() && moved(0, dy).is_some() {
+        if let Some(p) = moved(dx, dy) {
+          result.push((p, DIAGONAL_COST));
+        }
+      }
+    }
+  }
+  result
+}
@@ -0,0 +1,84 @@
+//! generate関数を知覚・意思決定・行動の段階に分けて実行する仕組み
+
+use crate::seed::as_generator;
+use crate::{apply_buffers, begin_tick, collect_forgotten_events, collect_generated_data_lst};
+use crate::{
+  collect_injected_events, collect_velocity_moves, commit_tick, Context, EventContents,
+  GeneratedData, Generater, ObjectType, TickError,
+};
+#[cfg(feature = "tick-metrics")]
+use std::time::Instant;
+
+/// generate関数を実行する段階
+/// 一つの単位時間の中で`Perception`、`Decision`、`Action`の順に実行される
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Phase {
+  /// 知覚
+  /// 周りの様子を調べ、分かったことをイベントとして残す
+  Perception,
+  /// 意思決定
+  /// 知覚の段階で記憶されたイベントを見て、何をするかをイベントとして残す
+  Decision,
+  /// 行動
+  /// 意思決定の段階で記憶されたイベントを見て、実際に移動や生成・削除を行う
+  Action,
+}
+
+impl Phase {
+  /// 全ての段階を実行する順に並べたもの
+  pub const ALL: [Phase; 3] = [Phase::Perception, Phase::Decision, Phase::Action];
+}
+
+/// `run`と同じく単位時間を一つだけ進めるが、generate関数を段階ごとに分けて実行する
+///
+/// 同じ段階のgenerate関数は全て同じ世界を見て実行され、その結果は`run`と同じ規則でまとめて反映される
+/// 次の段階のgenerate関数は、前の段階までの結果が反映された世界を見て実行される
+/// そのため前の段階で起きたイベントは`Context::memory`の末尾から、移動や生成などの効果は`Context::objects`から読み取れる
+/// 同じ段階の中では先に実行されたgenerate関数が有利になることはない
+///
+/// 忘れられたイベントが起こした情報と予約されたイベントは、知覚の段階の結果と一緒に反映される
+/// 返す情報はgenerate関数を並べた順に並ぶ
+///
+/// 不整合が起きた場合の扱いは`run`と同じく`Context::strict`で決まる
+pub fn run_phased<T: EventContents<U>, U: ObjectType>(
+  ctx: &mut Context<T, U>,
+  generate_functions: Vec<(Phase, Generater<T, U>)>,
+) -> Result<Vec<GeneratedData<T, U>>, TickError> {
+  let strict = ctx.strict;
+  commit_tick(ctx, strict, |ctx| {
+    #[cfg(feature = "tick-metrics")]
+    let start = Instant::now();
+    let first_sequence = ctx.next_event_sequence;
+    let (now, forgotten) = begin_tick(ctx);
+    let mut buffers = std::mem::take(&mut ctx.buffers);
+    collect_velocity_moves(ctx, &mut buffers);
+    collect_forgotten_events(ctx, &mut buffers, &now, forgotten);
+    collect_injected_events(ctx, &mut buffers, &now);
+    let mut generated_data_lst = generate_functions.iter().map(|_| None).collect::<Vec<_>>();
+    for phase in Phase::ALL {
+      let indices = generate_functions
+        .iter()
+        .enumerate()
+        .filter(|(_, (p, _))| *p == phase)
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+      let phase_data = indices
+        .iter()
+        .map(|&i| as_generator(i, || (generate_functions[i].1)(ctx)))
+        .collect::<Vec<_>>();
+      collect_generated_data_lst(ctx, &mut buffers, &now, &phase_data);
+      apply_buffers(ctx, &mut buffers);
+      for (i, generated_data) in indices.into_iter().zip(phase_data) {
+        generated_data_lst[i] = Some(generated_data);
+      }
+    }
+    let errors = std::mem::take(&mut buffers.errors);
+    ctx.buffers = buffers;
+    ctx.update_chronicle(first_sequence);
+    ctx.update_timeline();
+    #[cfg(feature = "tick-metrics")]
+    ctx.tick_metrics.record(start.elapsed());
+    let generated_data_lst = generated_data_lst.into_iter().flatten().collect();
+    (generated_data_lst, errors)
+  })
+}
@@ -0,0 +1,67 @@
+//! 生成するオブジェクトが同じセルに重ならないようにする配置
+
+use crate::{Context, EventContents, ObjectType, Point, Torus};
+use num_bigint::BigUint;
+use rustc_hash::FxHashSet;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// 生成先のセルが既に埋まっている場合に、近くの空いているセルを探す設定
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SpawnPlacement {
+  /// 元の生成先から探す範囲
+  /// 縦横斜めのいずれの方向にもこのセル数まで離れたセルを調べる
+  pub max_distance: u32,
+}
+
+impl SpawnPlacement {
+  /// `point`から近い順に、通行でき埋まっていないセルを探す
+  /// 同じ距離のセルはyが小さい順、yが同じならxが小さい順に調べるので、探索の結果は常に同じになる
+  /// 見つかったセルは`occupied`に加えられる
+  pub(crate) fn place<T: EventContents<U>, U: ObjectType>(
+    &self,
+    ctx: &Context<T, U>,
+    occupied: &mut FxHashSet<Point>,
+    point: Point,
+  ) -> Option<Point> {
+    for distance in 0..=self.max_distance {
+      let d = i64::from(distance);
+      for dy in -d..=d {
+        for dx in -d..=d {
+          // 距離がちょうど`distance`の輪の上のセルだけを調べる
+          if dx.abs() != d && dy.abs() != d {
+            continue;
+          }
+          let Some(candidate) = offset(ctx.torus(), &point, dx, dy) else {
+            continue;
+          };
+          if ctx.is_passable(&candidate) && !occupied.contains(&candidate) {
+            occupied.insert(candidate.clone());
+            return Some(candidate);
+          }
+        }
+      }
+    }
+    None
+  }
+}
+
+/// 地点を`(dx, dy)`だけずらしたもの
+/// 端と端がつながった世界では範囲内に折り返し、それ以外で座標が負になる場合は`None`を返す
+fn offset(torus: Option<&Torus>, point: &Point, dx: i64, dy: i64) -> Option<Point> {
+  let shift = |value: &BigUint, d: i64, size: Option<&BigUint>| {
+    let magnitude = BigUint::from(d.unsigned_abs());
+    match size {
+      Some(size) if d >= 0 => Some((value + magnitude) % size),
+      Some(size) => Some((value % size + size - magnitude % size) % size),
+      None if d >= 0 => Some(value + magnitude),
+      None if *value >= magnitude => Some(value - magnitude),
+      None => None,
+    }
+  };
+  Some(Point {
+    x: shift(&point.x, dx, torus.map(Torus::width))?,
+    y: shift(&point.y, dy, torus.map(Torus::height))?,
+  })
+}
@@ -0,0 +1,54 @@
+//! generate関数ごとに独立した乱数列を、実行順によらずに作るための種
+
+use crate::noise::{hash_biguint, mix};
+use crate::{Context, EventContents, ObjectType};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::cell::Cell;
+
+thread_local! {
+  /// このスレッドで実行しているgenerate関数の、渡された並びでの位置
+  static GENERATOR_INDEX: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// 実行が終わったら、実行していたgenerate関数の位置を元に戻す
+struct GeneratorIndexGuard(Option<usize>);
+
+impl Drop for GeneratorIndexGuard {
+  fn drop(&mut self) {
+    GENERATOR_INDEX.with(|index| index.set(self.0));
+  }
+}
+
+/// `f`を`index`番目のgenerate関数の実行として扱う
+/// 実行中は`Context::generator_seed`がその位置から決まる種を返す
+pub(crate) fn as_generator<R>(index: usize, f: impl FnOnce() -> R) -> R {
+  let _guard = GeneratorIndexGuard(GENERATOR_INDEX.with(|cell| cell.replace(Some(index))));
+  f()
+}
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// `seed`と現在時刻と`stream`から決まる乱数の種
+  /// generate関数ごとに異なる`stream`を使えば、互いに独立で実行順によらない乱数列が得られる
+  pub fn stream_seed(&self, stream: u64) -> u64 {
+    mix(self.seed ^ hash_biguint(&self.time.all, mix(stream)))
+  }
+
+  /// 実行中のgenerate関数の、`run`などに渡された並びでの位置を`stream`にした`stream_seed`
+  /// 位置は自動で決まるので、generate関数ごとに`stream`を選ばなくても互いに独立な乱数列が得られる
+  /// `run`でも`par_run`でも同じ並びで実行すれば同じ値になり、スレッドの数や実行順によらない
+  ///
+  /// generate関数の外で呼んだ場合はpanicする
+  pub fn generator_seed(&self) -> u64 {
+    let index = GENERATOR_INDEX
+      .with(Cell::get)
+      .expect("generator_seed must be called from a generate function");
+    self.stream_seed(index as u64)
+  }
+
+  /// `generator_seed`を種にした乱数生成器
+  /// generate関数の中で毎回作り直して使う
+  pub fn generator_rng(&self) -> StdRng {
+    StdRng::seed_from_u64(self.generator_seed())
+  }
+}
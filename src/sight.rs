@@ -0,0 +1,34 @@
+//! 二点間の視線が遮られているかどうかの判定
+
+use crate::{Context, EventContents, ObjectType, Point};
+use rustc_hash::FxHashSet;
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// `a`から`b`への視線が通るかどうか
+  /// 二点を結ぶセルのうち両端を除いたものに、オブジェクトがあるか地形で通行できないセルがあれば遮られる
+  /// 親を持つオブジェクトは`Context::world_point`で求めた世界での位置にあるものとする
+  /// 端と端がつながった世界では、端をまたいだ近い方の線分を辿る
+  pub fn has_line_of_sight(&self, a: &Point, b: &Point) -> bool {
+    let line = match &self.torus {
+      Some(torus) => {
+        let (a, b) = torus.nearest_segment(a, b);
+        a.line_to(&b)
+          .iter()
+          .map(|point| torus.wrap(point))
+          .collect()
+      }
+      None => a.line_to(b),
+    };
+    let Some(between) = line.get(1..line.len().saturating_sub(1)) else {
+      return true;
+    };
+    if !between.iter().all(|point| self.is_passable(point)) {
+      return false;
+    }
+    let between = between.iter().collect::<FxHashSet<_>>();
+    !self
+      .objects
+      .iter()
+      .any(|(id, object)| between.contains(self.located_point(id, object).as_ref()))
+  }
+}
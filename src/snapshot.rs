@@ -0,0 +1,289 @@
+//! 連続するスナップショットの差分だけを記録するための圧縮表現
+
+use crate::{Context, EventContents, Object, ObjectType, Point, Time};
+use num_bigint::BigInt;
+use rustc_hash::{FxHashMap, FxHasher};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// 座標の差分
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PointDelta {
+  /// x座標の差分
+  pub dx: BigInt,
+  /// y座標の差分
+  pub dy: BigInt,
+}
+
+impl PointDelta {
+  /// `from`から`to`への差分
+  pub fn between(from: &Point, to: &Point) -> Self {
+    PointDelta {
+      dx: BigInt::from(to.x.clone()) - BigInt::from(from.x.clone()),
+      dy: BigInt::from(to.y.clone()) - BigInt::from(from.y.clone()),
+    }
+  }
+
+  /// 地点に差分を足した地点
+  /// 座標が負になる場合は`None`
+  pub fn apply(&self, point: &Point) -> Option<Point> {
+    let x = (BigInt::from(point.x.clone()) + &self.dx).to_biguint()?;
+    let y = (BigInt::from(point.y.clone()) + &self.dy).to_biguint()?;
+    Some(Point { x, y })
+  }
+}
+
+/// 基準となるスナップショットから変化したオブジェクトだけを記録したもの
+/// 移動したオブジェクトは座標を前のスナップショットからの差分で持つ
+/// イベントの記憶は含まれない
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SnapshotDiff<U: ObjectType> {
+  /// 新しいスナップショットの時刻
+  pub time: Time,
+  /// 新たに現れたオブジェクト
+  pub added: Vec<(String, Object<U>)>,
+  /// 消えたオブジェクトのID
+  pub removed: Vec<String>,
+  /// 移動したオブジェクトのIDと座標の差分
+  pub moved: Vec<(String, PointDelta)>,
+  /// 種類や状態が変化したオブジェクトのIDと変化後の中身
+  pub updated: Vec<(String, U)>,
+  /// 座標の基準にする親が変わったオブジェクトのIDと変化後の親のID
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub reparented: Vec<(String, Option<String>)>,
+}
+
+impl<U: ObjectType> SnapshotDiff<U> {
+  /// `base`から`next`への差分を計算する
+  /// 各リストはIDの辞書順に並ぶ
+  pub fn between<T: EventContents<U>>(base: &Context<T, U>, next: &Context<T, U>) -> Self {
+    let mut added = Vec::new();
+    let mut moved = Vec::new();
+    let mut updated = Vec::new();
+    let mut reparented = Vec::new();
+    for (id, object) in next.objects.iter() {
+      match base.objects.get(id) {
+        None => added.push((id.clone(), object.as_ref().clone())),
+        Some(old) => {
+          if old.point != object.point {
+            moved.push((id.clone(), PointDelta::between(&old.point, &object.point)));
+          }
+          if old.object_type != object.object_type {
+            updated.push((id.clone(), object.object_type.clone()));
+          }
+          if old.parent_frame != object.parent_frame {
+            reparented.push((id.clone(), object.parent_frame.clone()));
+          }
+        }
+      }
+    }
+    let mut removed = base
+      .objects
+      .keys()
+      .filter(|id| !next.objects.contains_key(*id))
+      .cloned()
+      .collect::<Vec<_>>();
+    added.sort_by(|a, b| a.0.cmp(&b.0));
+    removed.sort();
+    moved.sort_by(|a, b| a.0.cmp(&b.0));
+    updated.sort_by(|a, b| a.0.cmp(&b.0));
+    reparented.sort_by(|a, b| a.0.cmp(&b.0));
+    SnapshotDiff {
+      time: next.time.clone(),
+      added,
+      removed,
+      moved,
+      updated,
+      reparented,
+    }
+  }
+}
+
+impl<U: ObjectType> SnapshotDiff<U> {
+  /// 基準となるスナップショットのオブジェクトに差分を適用して、次のスナップショットのオブジェクトを復元する
+  /// 移動したり中身や親が変化したりしたオブジェクトの最終更新時刻は、差分の時刻になる
+  /// 差分の対象となるオブジェクトが存在しない場合や座標が負になる場合は、そのIDをエラーとして返す
+  /// 差分は複製したオブジェクトに適用してから置き換えるので、失敗した場合は`objects`を何も変えない
+  /// 複製するのはオブジェクトへの参照だけで、中身は書き換えるものだけが複製される
+  pub fn apply(&self, objects: &mut FxHashMap<String, Arc<Object<U>>>) -> Result<(), String> {
+    let mut next = objects.clone();
+    for id in self.removed.iter() {
+      next.remove(id).ok_or_else(|| id.clone())?;
+    }
+    for (id, delta) in self.moved.iter() {
+      let object = Arc::make_mut(next.get_mut(id).ok_or_else(|| id.clone())?);
+      object.point = delta.apply(&object.point).ok_or_else(|| id.clone())?;
+      object.last_modified = self.time.clone();
+    }
+    for (id, object_type) in self.updated.iter() {
+      let object = Arc::make_mut(next.get_mut(id).ok_or_else(|| id.clone())?);
+      object.object_type = object_type.clone();
+      object.last_modified = self.time.clone();
+    }
+    for (id, parent) in self.reparented.iter() {
+      let object = Arc::make_mut(next.get_mut(id).ok_or_else(|| id.clone())?);
+      object.parent_frame = parent.clone();
+      object.last_modified = self.time.clone();
+    }
+    for (id, object) in self.added.iter() {
+      next.insert(id.clone(), Arc::new(object.clone()));
+    }
+    *objects = next;
+    Ok(())
+  }
+}
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// 差分を適用して時刻とオブジェクトを次のスナップショットの状態に進める
+  /// 失敗した場合は時刻もオブジェクトも変化しない
+  pub fn apply_snapshot_diff(&mut self, diff: &SnapshotDiff<U>) -> Result<(), String> {
+    diff.apply(&mut self.objects)?;
+    self.rebuild_name_index();
+    self.rebuild_spatial_index();
+    self.time = diff.time.clone();
+    Ok(())
+  }
+}
+
+impl<T: EventContents<U> + Hash, U: ObjectType + Hash> Context<T, U> {
+  /// 時刻・オブジェクト・記憶しているイベント・通し番号から計算したハッシュ値
+  /// オブジェクトはIDの順に並べてから計算するので、`HashMap`の並び順によらない
+  /// 同じ状態なら実行ごと・スレッド数ごとに同じ値になるので、再現性の確認に使える
+  pub fn state_hash(&self) -> u64 {
+    let mut hasher = FxHasher::default();
+    self.time.hash(&mut hasher);
+    for (id, object) in self.objects_sorted() {
+      id.hash(&mut hasher);
+      object.generated_time.hash(&mut hasher);
+      object.last_modified.hash(&mut hasher);
+      object.point.hash(&mut hasher);
+      object.object_type.hash(&mut hasher);
+      object.parent_frame.hash(&mut hasher);
+      object.caused_by.hash(&mut hasher);
+    }
+    for event in self.memory.iter() {
+      event.id.hash(&mut hasher);
+      event.sequence.hash(&mut hasher);
+      event.contents.hash(&mut hasher);
+    }
+    self.next_event_sequence.hash(&mut hasher);
+    self.next_object_number.hash(&mut hasher);
+    hasher.finish()
+  }
+}
+
+/// 2つのスナップショットの間でオブジェクトに起きた変化
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectChange<'a, U: ObjectType> {
+  /// 新たに現れた
+  Added { id: &'a str, object: &'a Object<U> },
+  /// 消えた
+  Removed { id: &'a str, object: &'a Object<U> },
+  /// 移動した
+  Moved {
+    id: &'a str,
+    from: &'a Point,
+    to: &'a Point,
+  },
+  /// 種類や状態が変化した
+  Updated {
+    id: &'a str,
+    before: &'a U,
+    after: &'a U,
+  },
+  /// 座標の基準にする親が変わった
+  Reparented {
+    id: &'a str,
+    from: Option<&'a str>,
+    to: Option<&'a str>,
+  },
+}
+
+impl<U: ObjectType> ObjectChange<'_, U> {
+  /// 変化したオブジェクトのID
+  pub fn id(&self) -> &str {
+    match self {
+      ObjectChange::Added { id, .. }
+      | ObjectChange::Removed { id, .. }
+      | ObjectChange::Moved { id, .. }
+      | ObjectChange::Updated { id, .. }
+      | ObjectChange::Reparented { id, .. } => id,
+    }
+  }
+}
+
+/// 2つのスナップショットのオブジェクトの変化を順に返すイテレータ
+/// オブジェクトはIDで対応付ける
+/// 移動と状態と親の変化が同時に起きたオブジェクトは`Moved`、`Updated`、`Reparented`の順に現れる
+/// 順序はハッシュマップの走査順に従うので、決まった順序が必要な場合は並べ替えること
+pub struct ContextDiff<'a, U: ObjectType> {
+  base: &'a FxHashMap<String, Arc<Object<U>>>,
+  next: &'a FxHashMap<String, Arc<Object<U>>>,
+  base_iter: hash_map::Iter<'a, String, Arc<Object<U>>>,
+  next_iter: hash_map::Iter<'a, String, Arc<Object<U>>>,
+  /// 同じオブジェクトについて、まだ返していない変化を返す順とは逆に並べたもの
+  pending: Vec<ObjectChange<'a, U>>,
+}
+
+impl<'a, U: ObjectType> Iterator for ContextDiff<'a, U> {
+  type Item = ObjectChange<'a, U>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if let Some(change) = self.pending.pop() {
+      return Some(change);
+    }
+    for (id, old) in self.base_iter.by_ref() {
+      let Some(new) = self.next.get(id) else {
+        return Some(ObjectChange::Removed { id, object: old });
+      };
+      if old.parent_frame != new.parent_frame {
+        self.pending.push(ObjectChange::Reparented {
+          id,
+          from: old.parent_frame.as_deref(),
+          to: new.parent_frame.as_deref(),
+        });
+      }
+      if old.object_type != new.object_type {
+        self.pending.push(ObjectChange::Updated {
+          id,
+          before: &old.object_type,
+          after: &new.object_type,
+        });
+      }
+      if old.point != new.point {
+        self.pending.push(ObjectChange::Moved {
+          id,
+          from: &old.point,
+          to: &new.point,
+        });
+      }
+      if let Some(change) = self.pending.pop() {
+        return Some(change);
+      }
+    }
+    let base = self.base;
+    self
+      .next_iter
+      .by_ref()
+      .find(|(id, _)| !base.contains_key(*id))
+      .map(|(id, object)| ObjectChange::Added { id, object })
+  }
+}
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// このスナップショットから`other`までのオブジェクトの変化を列挙する
+  pub fn diff<'a>(&'a self, other: &'a Context<T, U>) -> ContextDiff<'a, U> {
+    ContextDiff {
+      base: &self.objects,
+      next: &other.objects,
+      base_iter: self.objects.iter(),
+      next_iter: other.objects.iter(),
+      pending: Vec::new(),
+    }
+  }
+}
@@ -0,0 +1,121 @@
+//! 地点の近くにあるオブジェクトを探すための、単位時間をまたいで保たれる索引
+
+use crate::grid::SpatialGrid;
+use crate::{Context, EventContents, Object, ObjectType, Point};
+use num_bigint::BigUint;
+use num_traits::identities::Zero;
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// `center`からのユークリッド距離が`radius`以下のオブジェクト
+  /// 親を持つオブジェクトは`Context::world_point`で求めた世界での位置で測る
+  /// 端と端がつながった世界では端をまたいだ先のオブジェクトも含める
+  /// 索引がある場合は近くのセルにあるものだけを調べ、ない場合は全てのオブジェクトを調べる
+  /// IDの辞書順に並ぶので、探し方によらず結果は同じになる
+  pub fn objects_within(&self, center: &Point, radius: &BigUint) -> Vec<(&String, &Object<U>)> {
+    let squared_radius = radius * radius;
+    let is_within = |id: &str, object: &Object<U>| {
+      self.squared_distance(&self.located_point(id, object), center) <= squared_radius
+    };
+    let mut objects = match &self.spatial_index {
+      Some(grid) => self
+        .grid_candidates(grid, center, radius)
+        .into_iter()
+        .filter_map(|id| self.objects.get_key_value(id))
+        .map(|(id, object)| (id, object.as_ref()))
+        .filter(|(id, object)| is_within(id, object))
+        .collect::<Vec<_>>(),
+      None => self
+        .objects
+        .iter()
+        .map(|(id, object)| (id, object.as_ref()))
+        .filter(|(id, object)| is_within(id, object))
+        .collect::<Vec<_>>(),
+    };
+    objects.sort_by(|a, b| a.0.cmp(b.0));
+    objects
+  }
+
+  /// ちょうど`point`にあるオブジェクト
+  /// 親を持つオブジェクトは`Context::world_point`で求めた世界での位置で比べる
+  /// 端と端がつながった世界では`point`を範囲内に折り返してから比べる
+  /// 索引がある場合は`point`を含むセルにあるものだけを調べ、ない場合は全てのオブジェクトを調べるのでオブジェクトの数に比例する時間がかかる
+  /// IDの辞書順に並ぶ
+  pub fn objects_at(&self, point: &Point) -> Vec<(&String, &Object<U>)> {
+    let point = self.wrap_point(point.clone());
+    let mut objects = match &self.spatial_index {
+      Some(grid) => self
+        .grid_candidates(grid, &point, &BigUint::zero())
+        .into_iter()
+        .filter_map(|id| self.objects.get_key_value(id))
+        .map(|(id, object)| (id, object.as_ref()))
+        .filter(|(id, object)| *self.located_point(id, object) == point)
+        .collect::<Vec<_>>(),
+      None => self
+        .objects
+        .iter()
+        .map(|(id, object)| (id, object.as_ref()))
+        .filter(|(id, object)| *self.located_point(id, object) == point)
+        .collect::<Vec<_>>(),
+    };
+    objects.sort_by(|a, b| a.0.cmp(b.0));
+    objects
+  }
+
+  /// 一辺が`cell_size`のセルに区切った索引を作り、以後`run`などでの変化に合わせて保つ
+  /// 親を持つオブジェクトは世界での位置のセルに置かれ、親が動くとそれに合わせて移される
+  /// `None`の場合は索引を捨てる
+  pub fn set_spatial_index(&mut self, cell_size: Option<BigUint>) {
+    self.spatial_index = cell_size.map(SpatialGrid::new);
+    self.rebuild_spatial_index();
+  }
+
+  /// `objects`から索引を作り直す
+  /// `objects`を直接書き換えた場合に呼ぶ
+  pub fn rebuild_spatial_index(&mut self) {
+    let Some(grid) = &self.spatial_index else {
+      return;
+    };
+    let mut grid = SpatialGrid::new(grid.cell_size().clone());
+    for (id, object) in self.objects.iter() {
+      grid.insert(id.clone(), &self.located_point(id, object));
+      grid.set_framed(id, object.parent_frame.is_some());
+    }
+    self.spatial_index = Some(grid);
+  }
+
+  /// 索引でのオブジェクトの位置を、今の世界での位置に合わせる
+  /// オブジェクトが存在しない場合は索引から取り除く
+  pub(crate) fn spatial_update(&mut self, id: &str) {
+    if self.spatial_index.is_none() {
+      return;
+    }
+    let located = self.objects.get(id).map(|object| {
+      let point = self.located_point(id, object).into_owned();
+      (point, object.parent_frame.is_some())
+    });
+    let Some(grid) = &mut self.spatial_index else {
+      return;
+    };
+    match located {
+      Some((point, framed)) => {
+        grid.insert(id.to_string(), &point);
+        grid.set_framed(id, framed);
+      }
+      None => grid.remove(id),
+    }
+  }
+
+  /// 親を持つオブジェクトの索引での位置を、親の移動や削除に合わせて直す
+  pub(crate) fn spatial_update_framed(&mut self) {
+    let Some(grid) = &self.spatial_index else {
+      return;
+    };
+    if grid.framed().is_empty() {
+      return;
+    }
+    let framed = grid.framed().iter().cloned().collect::<Vec<_>>();
+    for id in framed {
+      self.spatial_update(&id);
+    }
+  }
+}
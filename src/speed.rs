@@ -0,0 +1,74 @@
+//! 一回の単位時間に移動できる距離の制限
+
+use crate::{Context, EventContents, ObjectType, Point};
+use num_bigint::BigUint;
+use num_traits::identities::Zero;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// `ObjectType::max_move_per_tick`を超える移動の扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MoveLimit {
+  /// 移動させずに`RunError::MoveTooFar`として記録する
+  #[default]
+  Reject,
+  /// 移動先へ向かう直線上の、上限の距離に収まる地点まで移動させる
+  Clamp,
+}
+
+/// 軸の上での移動量と、その向きが正かどうか
+/// 端と端がつながった世界では近い方の回りで測る
+fn signed_diff(from: &BigUint, to: &BigUint, size: Option<&BigUint>) -> (BigUint, bool) {
+  match size {
+    Some(size) => {
+      let forward = (to + size - from % size) % size;
+      let backward = size - &forward;
+      if forward <= backward {
+        (forward, true)
+      } else {
+        (backward, false)
+      }
+    }
+    None if to >= from => (to - from, true),
+    None => (from - to, false),
+  }
+}
+
+/// 軸の上で`from`から`n`だけ動かした座標
+fn offset(from: &BigUint, n: BigUint, positive: bool, size: Option<&BigUint>) -> BigUint {
+  match (size, positive) {
+    (Some(size), true) => (from + n) % size,
+    (Some(size), false) => (from + size - n % size) % size,
+    (None, true) => from + n,
+    (None, false) => from - n,
+  }
+}
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// `from`から`to`へ向かう直線上で、`from`からの距離が`limit`以下に収まる地点
+  /// 格子点に丸めるため、上限ちょうどより手前で止まることがある
+  pub(crate) fn clamp_move(&self, from: &Point, to: &Point, limit: &BigUint) -> Point {
+    let width = self.torus.as_ref().map(|torus| torus.width());
+    let height = self.torus.as_ref().map(|torus| torus.height());
+    let (dx, east) = signed_diff(&from.x, &to.x, width);
+    let (dy, south) = signed_diff(&from.y, &to.y, height);
+    let squared = &dx * &dx + &dy * &dy;
+    if squared.is_zero() {
+      return from.clone();
+    }
+    // 距離を切り上げて割ることで、丸めた後の距離が上限を超えないようにする
+    let root = squared.sqrt();
+    let distance = if &root * &root < squared {
+      root + 1u32
+    } else {
+      root
+    };
+    let nx = dx * limit / &distance;
+    let ny = dy * limit / &distance;
+    Point {
+      x: offset(&from.x, nx, east, width),
+      y: offset(&from.y, ny, south, height),
+    }
+  }
+}
@@ -0,0 +1,90 @@
+//! 種類ごとのオブジェクトの集計
+
+use crate::{to_f64_saturating, Context, EventContents, ObjectType, Time};
+use num_bigint::BigUint;
+use num_traits::Zero;
+use rustc_hash::FxHashMap;
+
+/// ある種類のオブジェクトをまとめた統計
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeciesStats {
+  /// 個体数
+  pub count: usize,
+  /// 平均年齢
+  pub mean_age: f64,
+  /// 座標の平均
+  /// 親を持つオブジェクトは世界での位置で数える
+  pub centroid: (f64, f64),
+  /// 最も年齢が大きいオブジェクトのID
+  /// 同じ年齢のものが複数ある場合は辞書順で最初のもの
+  pub oldest: String,
+  /// 最も年齢が小さいオブジェクトのID
+  /// 同じ年齢のものが複数ある場合は辞書順で最初のもの
+  pub youngest: String,
+}
+
+/// 集計途中の値
+struct Accumulator<'a> {
+  count: usize,
+  age_sum: BigUint,
+  x_sum: BigUint,
+  y_sum: BigUint,
+  oldest: (BigUint, &'a String),
+  youngest: (BigUint, &'a String),
+}
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// オブジェクトを種類の名前ごとにまとめ、個体数・平均年齢・重心・最年長・最年少を求める
+  /// 年齢は`now`を基準に数え、`now`より後に生成されたものは`0`とする
+  /// 全てのオブジェクトを一度だけ走査する
+  pub fn aggregate_by_name(&self, now: &Time) -> FxHashMap<String, SpeciesStats> {
+    let mut accumulators = FxHashMap::<String, Accumulator>::default();
+    for (id, object) in self.objects.iter() {
+      let age = if object.generated_time.all > now.all {
+        BigUint::zero()
+      } else {
+        &now.all - &object.generated_time.all
+      };
+      let accumulator = accumulators
+        .entry(object.object_type.name())
+        .or_insert_with(|| Accumulator {
+          count: 0,
+          age_sum: BigUint::zero(),
+          x_sum: BigUint::zero(),
+          y_sum: BigUint::zero(),
+          oldest: (age.clone(), id),
+          youngest: (age.clone(), id),
+        });
+      accumulator.count += 1;
+      accumulator.age_sum += &age;
+      let point = self.located_point(id, object);
+      accumulator.x_sum += &point.x;
+      accumulator.y_sum += &point.y;
+      let (oldest_age, oldest_id) = &accumulator.oldest;
+      if age > *oldest_age || (age == *oldest_age && id < *oldest_id) {
+        accumulator.oldest = (age.clone(), id);
+      }
+      let (youngest_age, youngest_id) = &accumulator.youngest;
+      if age < *youngest_age || (age == *youngest_age && id < *youngest_id) {
+        accumulator.youngest = (age, id);
+      }
+    }
+    accumulators
+      .into_iter()
+      .map(|(name, accumulator)| {
+        let count = accumulator.count as f64;
+        let stats = SpeciesStats {
+          count: accumulator.count,
+          mean_age: to_f64_saturating(&accumulator.age_sum) / count,
+          centroid: (
+            to_f64_saturating(&accumulator.x_sum) / count,
+            to_f64_saturating(&accumulator.y_sum) / count,
+          ),
+          oldest: accumulator.oldest.1.clone(),
+          youngest: accumulator.youngest.1.clone(),
+        };
+        (name, stats)
+      })
+      .collect()
+  }
+}
@@ -0,0 +1,81 @@
+//! 記録されたイベントを別のタスクから非同期に購読するための仕組み
+//! `tokio`機能を有効にした場合に使える
+
+use crate::{Context, Event, EventContents, ObjectType};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+/// 購読者が受け取るイベントを選ぶ関数
+pub type EventFilter<T> = Box<dyn Fn(&T) -> bool + Send + Sync>;
+
+/// 購読者ごとの配信先
+pub struct EventChannel<T> {
+  sender: broadcast::Sender<Arc<Event<T>>>,
+  filter: Option<EventFilter<T>>,
+}
+
+impl<T> EventChannel<T> {
+  /// イベントを購読者に送る
+  /// 選ぶ関数を通らなかったイベントは送らない
+  fn send(&self, event: &Arc<Event<T>>) {
+    if self
+      .filter
+      .as_ref()
+      .is_some_and(|filter| !filter(&event.contents))
+    {
+      return;
+    }
+    // 受け取る側が全て破棄されていれば送れないが、その配信先は次の配信で取り除く
+    let _ = self.sender.send(event.clone());
+  }
+}
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// 記録されたイベントを購読する
+  /// 返した受け取り口から`recv`で待つか、`try_recv`で待たずに取り出す
+  /// `filter`が与えられた場合は、それが`true`を返すイベントだけを受け取る
+  ///
+  /// 購読者は最大`capacity`個のイベントを溜めておき、それを超えると古いものから捨てる
+  /// 捨てられた場合は次の受け取りで`Lagged`とその数が返り、その後は残っているイベントのうち最も古いものから受け取れる
+  /// そのため遅い購読者がいても`run`が待たされることはない
+  /// 配信側の世界が全て破棄されると、残りのイベントを受け取った後に`Closed`が返る
+  ///
+  /// イベントは単位時間が確定してから届くので、`try_run`などで巻き戻された単位時間のイベントは届かない
+  /// 購読は`fork`した世界にも引き継がれ、どちらの世界のイベントも届く
+  pub fn subscribe(
+    &mut self,
+    capacity: usize,
+    filter: Option<EventFilter<T>>,
+  ) -> broadcast::Receiver<Arc<Event<T>>> {
+    let (sender, receiver) = broadcast::channel(capacity.max(1));
+    self
+      .subscribers
+      .push(Arc::new(EventChannel { sender, filter }));
+    receiver
+  }
+
+  /// 記録されたイベントを、単位時間が確定するまで配信を待つものとして積む
+  pub(crate) fn stage_publish(&self, events: &[Event<T>], unpublished: &mut Vec<Arc<Event<T>>>) {
+    if self.subscribers.is_empty() {
+      return;
+    }
+    unpublished.extend(events.iter().cloned().map(Arc::new));
+  }
+
+  /// 確定した単位時間のイベントを購読者に配信する
+  /// 受け取る側が全て破棄された購読者はここで取り除く
+  pub(crate) fn publish(&mut self) {
+    if self.buffers.unpublished.is_empty() {
+      return;
+    }
+    self
+      .subscribers
+      .retain(|channel| channel.sender.receiver_count() > 0);
+    for channel in self.subscribers.iter() {
+      for event in self.buffers.unpublished.iter() {
+        channel.send(event);
+      }
+    }
+    self.buffers.unpublished.clear();
+  }
+}
@@ -0,0 +1,51 @@
+//! ユーザのイベントとは別に記録される、世界の仕組みそのものが起こしたイベント
+
+use crate::{Context, EventContents, ObjectType, Point, Time};
+
+/// 世界の仕組みそのものが起こしたイベント
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SystemEvent {
+  /// オブジェクトの生成に失敗した
+  SpawnFailed {
+    /// 生成されるはずだったオブジェクトの種類の名前
+    name: String,
+    /// 生成されるはずだった地点
+    intended_point: Point,
+    /// 失敗した理由
+    reason: String,
+  },
+}
+
+/// 記録されたシステムイベント
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemEventRecord {
+  /// 起きた時刻
+  pub time: Time,
+  /// 起きたこと
+  pub event: SystemEvent,
+}
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// 記録されたシステムイベントのうち、生成の失敗だけを古い順に返す
+  pub fn spawn_failures(&self) -> impl Iterator<Item = &SystemEventRecord> {
+    self
+      .system_events
+      .iter()
+      .filter(|record| matches!(record.event, SystemEvent::SpawnFailed { .. }))
+  }
+
+  /// 生成の失敗を記録する設定になっていれば記録する
+  pub(crate) fn record_spawn_failure(&mut self, name: String, intended_point: Point, reason: &str) {
+    if !self.record_spawn_failures {
+      return;
+    }
+    self.system_events.push(SystemEventRecord {
+      time: self.time.clone(),
+      event: SystemEvent::SpawnFailed {
+        name,
+        intended_point,
+        reason: reason.to_string(),
+      },
+    });
+  }
+}
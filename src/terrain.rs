@@ -0,0 +1,124 @@
+//! オブジェクトが入ることのできない領域を表す地形
+
+use crate::Point;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+/// 地点ごとにオブジェクトが入れるかどうかを決める地形
+#[derive(Clone)]
+pub enum Terrain {
+  /// セルごとの通行可否の表
+  Mask(TerrainMask),
+  /// 地点を受け取って通行できるかどうかを返す関数
+  Custom(Arc<dyn Fn(&Point) -> bool + Send + Sync>),
+}
+
+impl Terrain {
+  /// その地点にオブジェクトが入れるかどうか
+  pub fn is_passable(&self, point: &Point) -> bool {
+    match self {
+      Terrain::Mask(mask) => mask.is_passable(point),
+      Terrain::Custom(f) => f(point),
+    }
+  }
+}
+
+impl fmt::Debug for Terrain {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Terrain::Mask(mask) => f.debug_tuple("Mask").field(mask).finish(),
+      Terrain::Custom(_) => f.write_str("Custom(..)"),
+    }
+  }
+}
+
+/// 座標`(x, y)`を一つのセルとして、セルごとに通行できるかどうかを持つ表
+/// 表の範囲外の地点は通行できないものとして扱う
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TerrainMask {
+  /// 表の幅
+  width: usize,
+  /// 表の高さ
+  height: usize,
+  /// 行ごとに並べた各セルの通行可否
+  passable: Vec<bool>,
+}
+
+impl TerrainMask {
+  /// 全てのセルが通行できる表の新たな生成
+  pub fn new(width: usize, height: usize) -> Self {
+    TerrainMask {
+      width,
+      height,
+      passable: vec![true; width * height],
+    }
+  }
+
+  /// 表の幅
+  pub fn width(&self) -> usize {
+    self.width
+  }
+
+  /// 表の高さ
+  pub fn height(&self) -> usize {
+    self.height
+  }
+
+  /// セルの通行可否を設定する
+  /// 表の範囲外の場合は何もしない
+  pub fn set(&mut self, x: usize, y: usize, passable: bool) {
+    if x < self.width && y < self.height {
+      self.passable[y * self.width + x] = passable;
+    }
+  }
+
+  /// その地点にオブジェクトが入れるかどうか
+  pub fn is_passable(&self, point: &Point) -> bool {
+    match (usize::try_from(&point.x), usize::try_from(&point.y)) {
+      (Ok(x), Ok(y)) if x < self.width && y < self.height => self.passable[y * self.width + x],
+      _ => false,
+    }
+  }
+
+  /// 文字で描かれた地図から表を作る
+  /// 一行が一つのyに対応し、`.`が通行できるセル、`#`が通行できないセルを表す
+  /// 行の長さが揃っていない場合や、それ以外の文字が含まれる場合はエラーになる
+  pub fn from_text(text: &str) -> Result<Self, String> {
+    let lines = text
+      .lines()
+      .filter(|line| !line.is_empty())
+      .collect::<Vec<_>>();
+    let width = lines.first().map_or(0, |line| line.chars().count());
+    let mut passable = Vec::with_capacity(width * lines.len());
+    for (y, line) in lines.iter().enumerate() {
+      if line.chars().count() != width {
+        return Err(format!("line {y} has a different width"));
+      }
+      for (x, c) in line.chars().enumerate() {
+        match c {
+          '.' => passable.push(true),
+          '#' => passable.push(false),
+          _ => return Err(format!("unexpected character {c:?} at ({x}, {y})")),
+        }
+      }
+    }
+    Ok(TerrainMask {
+      width,
+      height: lines.len(),
+      passable,
+    })
+  }
+
+  /// 文字で描かれた地図のファイルから表を読み込む
+  /// 書式は`from_text`と同じ
+  pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    let text = fs::read_to_string(path)?;
+    TerrainMask::from_text(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+  }
+}
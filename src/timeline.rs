@@ -0,0 +1,192 @@
+//! 単位時間ごとの件数や個体数を時系列として蓄積する仕組み
+
+use crate::{Context, EventContents, ObjectType, Time};
+use num_bigint::BigUint;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+/// 生成されたオブジェクトの数の系列の名前
+const SPAWNED: &str = "spawned";
+/// 削除されたオブジェクトの数の系列の名前
+const REMOVED: &str = "removed";
+/// 全てのオブジェクトの数の系列の名前
+const POPULATION: &str = "population";
+
+/// 単位時間ごとの統計を時系列として蓄積するもの
+/// `enabled`を`true`にすると`run`ごとに一点ずつ追記する
+///
+/// 系列の名前は以下の通り
+/// - `spawned`：その単位時間に生成されたオブジェクトの数
+/// - `removed`：その単位時間に削除されたオブジェクトの数
+/// - `population`：単位時間を進めた後のオブジェクトの数
+/// - `population:<種類の名前>`：単位時間を進めた後の種類ごとのオブジェクトの数
+/// - `events:<イベントの種別>`：その単位時間に記録された種別ごとのイベントの数
+///
+/// 途中で現れた系列は、現れた単位時間から点を持つ
+/// 一度現れた系列は、その後は該当するものがなくても0として記録される
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+  /// 記録するかどうか
+  pub enabled: bool,
+  /// 保持する点の数の上限
+  /// 超えると一つおきに古い点から間引くので、長く実行するほど過去の点の間隔が広がる
+  /// `None`の場合は間引かない
+  pub max_points: Option<usize>,
+  /// 記録した時刻
+  /// 古いものほど前にある
+  times: Vec<Time>,
+  /// 名前ごとの系列
+  series: BTreeMap<String, Vec<(Time, f64)>>,
+  /// 今の単位時間に生成されたオブジェクトの数
+  pub(crate) spawned: usize,
+  /// 今の単位時間に削除されたオブジェクトの数
+  pub(crate) removed: usize,
+  /// 今の単位時間に記録された種別ごとのイベントの数
+  pub(crate) events: FxHashMap<String, usize>,
+}
+
+impl Timeline {
+  /// 記録した時刻
+  /// 古いものほど前にある
+  pub fn times(&self) -> &[Time] {
+    &self.times
+  }
+
+  /// 系列の名前
+  /// 辞書順に並ぶ
+  pub fn names(&self) -> impl Iterator<Item = &str> {
+    self.series.keys().map(|name| name.as_str())
+  }
+
+  /// 指定した名前の系列
+  /// 存在しない場合は空になる
+  pub fn series(&self, name: &str) -> &[(Time, f64)] {
+    self
+      .series
+      .get(name)
+      .map_or(&[], |points| points.as_slice())
+  }
+
+  /// 全ての系列を一行に一時刻ずつ並べたCSVを書き出す
+  /// 一列目は通算の単位時間で、残りの列は系列の名前の辞書順に並ぶ
+  /// 系列がまだ現れていない時刻の値は空になる
+  pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+    write!(writer, "time")?;
+    for name in self.series.keys() {
+      write!(writer, ",{}", csv_field(name))?;
+    }
+    writeln!(writer)?;
+    // 系列の点は`times`の部分列なので、系列ごとに読み進める位置を覚えておけば良い
+    let mut cursors = vec![0; self.series.len()];
+    for time in self.times.iter() {
+      write!(writer, "{}", time.all)?;
+      for (points, cursor) in self.series.values().zip(cursors.iter_mut()) {
+        match points.get(*cursor) {
+          Some((t, value)) if t.all == time.all => {
+            write!(writer, ",{value}")?;
+            *cursor += 1;
+          }
+          _ => write!(writer, ",")?,
+        }
+      }
+      writeln!(writer)?;
+    }
+    Ok(())
+  }
+
+  /// 一点を追記する
+  fn push(&mut self, time: Time, values: BTreeMap<String, f64>) {
+    for (name, points) in self.series.iter_mut() {
+      if !values.contains_key(name) {
+        points.push((time.clone(), 0.0));
+      }
+    }
+    for (name, value) in values {
+      self
+        .series
+        .entry(name)
+        .or_default()
+        .push((time.clone(), value));
+    }
+    self.times.push(time);
+    if let Some(max_points) = self.max_points {
+      if self.times.len() > max_points.max(1) {
+        self.thin();
+      }
+    }
+  }
+
+  /// 最新の点を残しつつ、一つおきに点を捨てる
+  fn thin(&mut self) {
+    let last = self.times.len() - 1;
+    let mut index = 0;
+    self.times.retain(|_| {
+      let keep = (last - index).is_multiple_of(2);
+      index += 1;
+      keep
+    });
+    let kept = self
+      .times
+      .iter()
+      .map(|time| &time.all)
+      .collect::<FxHashSet<&BigUint>>();
+    for points in self.series.values_mut() {
+      points.retain(|(time, _)| kept.contains(&time.all));
+    }
+    self.series.retain(|_, points| !points.is_empty());
+  }
+
+  /// 最新の点を取り除く
+  pub(crate) fn pop(&mut self) {
+    let Some(time) = self.times.pop() else {
+      return;
+    };
+    for points in self.series.values_mut() {
+      if points.last().is_some_and(|(t, _)| t.all == time.all) {
+        points.pop();
+      }
+    }
+    self.series.retain(|_, points| !points.is_empty());
+  }
+}
+
+/// CSVの一つの欄として書き出せるように、必要なら引用符で囲む
+fn csv_field(field: &str) -> String {
+  if field.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", field.replace('"', "\"\""))
+  } else {
+    field.to_string()
+  }
+}
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// 蓄積された時系列
+  pub fn timeline(&self) -> &Timeline {
+    &self.timeline
+  }
+
+  /// 単位時間を進めた後の世界から時系列に一点を追記する
+  pub(crate) fn update_timeline(&mut self) {
+    let spawned = std::mem::take(&mut self.timeline.spawned);
+    let removed = std::mem::take(&mut self.timeline.removed);
+    let events = std::mem::take(&mut self.timeline.events);
+    if !self.timeline.enabled {
+      return;
+    }
+    let mut values = BTreeMap::new();
+    values.insert(SPAWNED.to_string(), spawned as f64);
+    values.insert(REMOVED.to_string(), removed as f64);
+    values.insert(POPULATION.to_string(), self.objects.len() as f64);
+    for (name, count) in self.name_index.counts() {
+      values.insert(format!("{POPULATION}:{name}"), count as f64);
+    }
+    for (kind, count) in events {
+      values.insert(format!("events:{kind}"), count as f64);
+    }
+    self.timeline.push(self.time.clone(), values);
+    if let Some(undo) = &mut self.undo {
+      undo.timeline_recorded = true;
+    }
+  }
+}
@@ -0,0 +1,200 @@
+//! 端と端がつながった有限の世界
+
+use crate::grid::SpatialGrid;
+use crate::{Context, EventContents, ObjectType, Point};
+use num_bigint::BigUint;
+use num_traits::identities::{One, Zero};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// 東の端と西の端、北の端と南の端がつながった、幅`width`・高さ`height`の世界
+/// 座標はそれぞれ`0`以上`width`未満、`0`以上`height`未満に折り返される
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Torus {
+  /// 世界の幅
+  width: BigUint,
+  /// 世界の高さ
+  height: BigUint,
+}
+
+impl Torus {
+  /// 幅と高さを指定した世界の新たな生成
+  /// `0`の場合は`1`として扱う
+  pub fn new(width: BigUint, height: BigUint) -> Self {
+    let at_least_one = |n: BigUint| if n.is_zero() { BigUint::one() } else { n };
+    Torus {
+      width: at_least_one(width),
+      height: at_least_one(height),
+    }
+  }
+
+  /// 世界の幅
+  pub fn width(&self) -> &BigUint {
+    &self.width
+  }
+
+  /// 世界の高さ
+  pub fn height(&self) -> &BigUint {
+    &self.height
+  }
+
+  /// 地点を世界の範囲内に折り返す
+  pub fn wrap(&self, point: &Point) -> Point {
+    Point {
+      x: &point.x % &self.width,
+      y: &point.y % &self.height,
+    }
+  }
+
+  /// 端をまたぐ経路も含めた、二点間のユークリッド距離の二乗
+  pub fn squared_distance(&self, a: &Point, b: &Point) -> BigUint {
+    let dx = wrapped_diff(&a.x, &b.x, &self.width);
+    let dy = wrapped_diff(&a.y, &b.y, &self.height);
+    &dx * &dx + &dy * &dy
+  }
+
+  /// 端をまたぐ経路も含めた近い方で`a`と`b`を結ぶ線分の、折り返す前の両端
+  /// 座標が負にならないよう、`a`を幅と高さの分だけずらした地点から始める
+  pub(crate) fn nearest_segment(&self, a: &Point, b: &Point) -> (Point, Point) {
+    let (ax, bx) = nearest_on_axis(&a.x, &b.x, &self.width);
+    let (ay, by) = nearest_on_axis(&a.y, &b.y, &self.height);
+    (Point { x: ax, y: ay }, Point { x: bx, y: by })
+  }
+
+  /// `center`を中心とする一辺`2 * radius`の正方形を折り返した時に、その正方形が覆う矩形
+  /// 端をまたぐ場合は軸ごとに二つに分かれるので、最大で四つになる
+  fn covering_rects(&self, center: &Point, radius: &BigUint) -> Vec<(Point, Point)> {
+    let center = self.wrap(center);
+    let xs = wrapped_ranges(&center.x, radius, &self.width);
+    let ys = wrapped_ranges(&center.y, radius, &self.height);
+    xs.iter()
+      .flat_map(|(min_x, max_x)| {
+        ys.iter().map(move |(min_y, max_y)| {
+          (
+            Point {
+              x: min_x.clone(),
+              y: min_y.clone(),
+            },
+            Point {
+              x: max_x.clone(),
+              y: max_y.clone(),
+            },
+          )
+        })
+      })
+      .collect()
+  }
+}
+
+/// 長さ`size`で折り返す軸の上での、二つの座標の近い方の回りでの差
+fn wrapped_diff(a: &BigUint, b: &BigUint, size: &BigUint) -> BigUint {
+  let d = crate::abs_diff(&(a % size), &(b % size));
+  let around = size - &d;
+  d.min(around)
+}
+
+/// 長さ`size`で折り返す軸の上で、`a`から近い方の回りで`b`へ向かう時の、折り返す前の両端
+/// `a`は`size`だけずらすので、逆向きに進んでも負にならない
+fn nearest_on_axis(a: &BigUint, b: &BigUint, size: &BigUint) -> (BigUint, BigUint) {
+  let a = a % size;
+  let forward = (b % size + size - &a) % size;
+  let backward = size - &forward;
+  let start = a + size;
+  if forward <= backward {
+    let end = &start + forward;
+    (start, end)
+  } else {
+    let end = &start - backward;
+    (start, end)
+  }
+}
+
+/// 長さ`size`で折り返す軸の上で、`center`からの差が`radius`以下になる座標の範囲
+/// 両端を含む範囲で、端をまたぐ場合は二つに分かれる
+fn wrapped_ranges(center: &BigUint, radius: &BigUint, size: &BigUint) -> Vec<(BigUint, BigUint)> {
+  let last = size - 1u32;
+  if radius * 2u32 >= last {
+    return vec![(BigUint::zero(), last)];
+  }
+  let max = center + radius;
+  if center < radius {
+    vec![(BigUint::zero(), max), (size - (radius - center), last)]
+  } else if max > last {
+    vec![(center - radius, last), (BigUint::zero(), max - size)]
+  } else {
+    vec![(center - radius, max)]
+  }
+}
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// 端と端がつながった世界の大きさ
+  /// `None`の場合は果てのない世界
+  pub fn torus(&self) -> Option<&Torus> {
+    self.torus.as_ref()
+  }
+
+  /// 端と端がつながった世界にする
+  /// 既にあるオブジェクトの地点は範囲内に折り返される
+  /// 以後は`objects_within`と`distance`が端をまたいで測り、移動先や生成先の地点は範囲内に折り返される
+  /// `None`の場合は果てのない世界に戻す
+  pub fn set_torus(&mut self, torus: Option<Torus>) {
+    self.torus = torus;
+    if let Some(torus) = &self.torus {
+      for object in self.objects.values_mut() {
+        let point = torus.wrap(&object.point);
+        if point != object.point {
+          Arc::make_mut(object).point = point;
+        }
+      }
+      self.rebuild_spatial_index();
+    }
+  }
+
+  /// 地点を世界の範囲内に折り返す
+  /// 果てのない世界ではそのまま返す
+  pub fn wrap_point(&self, point: Point) -> Point {
+    match &self.torus {
+      Some(torus) => torus.wrap(&point),
+      None => point,
+    }
+  }
+
+  /// 二点間のユークリッド距離の二乗
+  /// 端と端がつながった世界では端をまたぐ経路も含めた近い方で測る
+  pub fn squared_distance(&self, a: &Point, b: &Point) -> BigUint {
+    match &self.torus {
+      Some(torus) => torus.squared_distance(a, b),
+      None => a.squared_euclidean_distance(b),
+    }
+  }
+
+  /// 二点間のユークリッド距離の小数点以下を切り捨てたもの
+  /// 端と端がつながった世界では端をまたぐ経路も含めた近い方で測る
+  pub fn distance(&self, a: &Point, b: &Point) -> BigUint {
+    self.squared_distance(a, b).sqrt()
+  }
+
+  /// `center`を中心とする一辺`2 * radius`の正方形と重なるセルにあるオブジェクトのID
+  /// 端と端がつながった世界では、端をまたいだ先のセルも含める
+  pub(crate) fn grid_candidates<'a>(
+    &self,
+    grid: &'a SpatialGrid,
+    center: &Point,
+    radius: &BigUint,
+  ) -> Vec<&'a String> {
+    let Some(torus) = &self.torus else {
+      return grid.candidates(center, radius);
+    };
+    let mut ids = torus
+      .covering_rects(center, radius)
+      .iter()
+      .flat_map(|(min, max)| grid.candidates_in_rect(min, max))
+      .collect::<Vec<_>>();
+    // セルが世界より大きい場合は、分かれた矩形が同じセルに重なることがある
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+  }
+}
@@ -0,0 +1,218 @@
+//! 直前の単位時間を巻き戻すための記録
+
+use crate::{Context, Event, EventContents, InjectedEvent, Object, ObjectType, Point};
+use crate::{PopulationController, Time, Weather};
+use rustc_hash::FxHashMap;
+use std::sync::Arc;
+
+/// 世界に反映された効果を打ち消す操作
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InverseEffect<U: ObjectType> {
+  /// 削除されたか、同じIDで置き換えられたオブジェクトを元に戻す
+  Restore(String, Arc<Object<U>>),
+  /// 生成されたオブジェクトを削除する
+  Despawn(String),
+  /// 移動したオブジェクトを元の地点に戻す
+  MoveBack {
+    /// オブジェクトのID
+    id: String,
+    /// 移動前の地点
+    point: Point,
+    /// 移動前の最終更新時刻
+    last_modified: Time,
+  },
+  /// 中身が置き換えられたオブジェクトを元の中身に戻す
+  Revert {
+    /// オブジェクトのID
+    id: String,
+    /// 置き換えられる前の中身
+    object_type: U,
+    /// 置き換えられる前の最終更新時刻
+    last_modified: Time,
+  },
+}
+
+/// 一つの単位時間の間に世界に加えられた変化を打ち消すための記録
+/// `run`が単位時間ごとに記録し直し、`Context::undo_last_tick`が使う
+#[derive(Debug, Clone)]
+pub struct TickUndo<T: EventContents<U>, U: ObjectType> {
+  /// 単位時間を進める前の時刻
+  pub(crate) time: Time,
+  /// 単位時間を進める前の次に記録されるイベントの通し番号
+  pub(crate) next_event_sequence: u64,
+  /// 単位時間を進める前の次に生成されるオブジェクトの通し番号
+  pub(crate) next_object_number: u64,
+  /// 単位時間を進める前の制御器
+  pub(crate) population_controller: Option<PopulationController>,
+  /// 単位時間を進める前の天候
+  pub(crate) weather: Weather,
+  /// 寿命を迎えて忘れられたイベントと、単位時間を進める前の記憶の中での位置
+  pub(crate) forgotten: Vec<(usize, Arc<Event<T>>)>,
+  /// 記憶の上限を超えたために記憶の先頭から取り除かれたイベント
+  /// 取り除かれた順に並ぶ
+  pub(crate) evicted: Vec<Arc<Event<T>>>,
+  /// 予約イベントを取り出す前の予約
+  /// 取り出されたものがなかった場合は`None`
+  pub(crate) scheduled_events: Option<Vec<InjectedEvent<T>>>,
+  /// 反映された効果を打ち消す操作
+  /// 反映された順に並ぶ
+  pub(crate) inverse_effects: Vec<InverseEffect<U>>,
+  /// 単位時間を進める前の`rejected_events`の長さ
+  pub(crate) rejected_events_len: usize,
+  /// 単位時間を進める前の`rejected_objects`の長さ
+  pub(crate) rejected_objects_len: usize,
+  /// 単位時間を進める前の`watch_log`の長さ
+  pub(crate) watch_log_len: usize,
+  /// 単位時間を進める前の`system_events`の長さ
+  pub(crate) system_events_len: usize,
+  /// 単位時間を進める前の年表の項目の数
+  pub(crate) chronicle_len: usize,
+  /// 年表を作るために数えた、単位時間を進める前の種類ごとのオブジェクトの数
+  /// 数え直さなかった場合は`None`
+  pub(crate) chronicle_counts: Option<Option<FxHashMap<String, usize>>>,
+  /// 統計の時系列に点を追記したかどうか
+  pub(crate) timeline_recorded: bool,
+}
+
+impl<T: EventContents<U>, U: ObjectType> TickUndo<T, U> {
+  /// 単位時間を進める直前の世界から記録を始める
+  /// 前の単位時間の記録`recycled`がある場合は、中身を捨てて確保済みの領域だけを使い回す
+  pub(crate) fn begin(ctx: &Context<T, U>, recycled: Option<Self>) -> Self {
+    let (mut forgotten, mut evicted, mut inverse_effects) = match recycled {
+      Some(undo) => (undo.forgotten, undo.evicted, undo.inverse_effects),
+      None => (Vec::new(), Vec::new(), Vec::new()),
+    };
+    forgotten.clear();
+    evicted.clear();
+    inverse_effects.clear();
+    TickUndo {
+      time: ctx.time.clone(),
+      next_event_sequence: ctx.next_event_sequence,
+      next_object_number: ctx.next_object_number,
+      population_controller: ctx.population_controller.clone(),
+      weather: ctx.weather,
+      forgotten,
+      evicted,
+      scheduled_events: None,
+      inverse_effects,
+      rejected_events_len: ctx.rejected_events.len(),
+      rejected_objects_len: ctx.rejected_objects.len(),
+      watch_log_len: ctx.watch_log.len(),
+      system_events_len: ctx.system_events.len(),
+      chronicle_len: ctx.chronicler.entries.len(),
+      chronicle_counts: None,
+      timeline_recorded: false,
+    }
+  }
+
+  /// 反映された効果を打ち消す操作
+  /// 反映された順に並ぶ
+  pub fn inverse_effects(&self) -> &[InverseEffect<U>] {
+    &self.inverse_effects
+  }
+}
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// 反映した効果を打ち消す操作を記録する
+  pub(crate) fn push_inverse(&mut self, inverse: InverseEffect<U>) {
+    if let Some(undo) = &mut self.undo {
+      undo.inverse_effects.push(inverse);
+    }
+  }
+
+  /// 直前の`run`で進めた単位時間を巻き戻す
+  /// オブジェクト・記憶・時刻・予約・制御器・天候・年表・各種の記録が単位時間を進める前の状態に戻る
+  /// 購読者に既に送られたイベントは取り消されない
+  /// 統計の時系列からは追記した点が取り除かれるが、その時に間引かれた点は戻らない
+  /// 巻き戻せるのは直前の一単位時間だけで、巻き戻すものがなかった場合は`false`を返す
+  /// `run`の後に世界を直接書き換えていた場合、その変更と矛盾しない形で戻るとは限らない
+  pub fn undo_last_tick(&mut self) -> bool {
+    let Some(undo) = self.undo.take() else {
+      return false;
+    };
+    for inverse in undo.inverse_effects.into_iter().rev() {
+      match inverse {
+        InverseEffect::Restore(id, object) => {
+          let name = object.object_type.name();
+          if let Some(old) = self.objects.insert(id.clone(), object) {
+            self.name_index.remove(&old.object_type.name(), &id);
+          }
+          self.name_index.insert(name, &id);
+          self.spatial_update(&id);
+        }
+        InverseEffect::Despawn(id) => {
+          if let Some(old) = self.objects.remove(&id) {
+            self.name_index.remove(&old.object_type.name(), &id);
+            self.spatial_update(&id);
+          }
+        }
+        InverseEffect::MoveBack {
+          id,
+          point,
+          last_modified,
+        } => {
+          if let Some(object) = self.objects.get_mut(&id) {
+            let object = Arc::make_mut(object);
+            object.point = point;
+            object.last_modified = last_modified;
+            self.spatial_update(&id);
+          }
+        }
+        InverseEffect::Revert {
+          id,
+          object_type,
+          last_modified,
+        } => {
+          if let Some(object) = self.objects.get_mut(&id) {
+            let object = Arc::make_mut(object);
+            let old_name = object.object_type.name();
+            let new_name = object_type.name();
+            object.object_type = object_type;
+            object.last_modified = last_modified;
+            if old_name != new_name {
+              self.name_index.remove(&old_name, &id);
+              self.name_index.insert(new_name, &id);
+            }
+          }
+        }
+      }
+    }
+    self.spatial_update_framed();
+    // 新たに記憶されたイベントは通し番号が単位時間を進める前の番号以降になっている
+    while self
+      .memory
+      .back()
+      .is_some_and(|e| e.sequence >= undo.next_event_sequence)
+    {
+      self.memory.pop_back();
+    }
+    for event in undo.evicted.into_iter().rev() {
+      if event.sequence < undo.next_event_sequence {
+        self.memory.push_front(event);
+      }
+    }
+    for (index, event) in undo.forgotten {
+      self.memory.insert(index, event);
+    }
+    self.time = undo.time;
+    self.next_event_sequence = undo.next_event_sequence;
+    self.next_object_number = undo.next_object_number;
+    self.population_controller = undo.population_controller;
+    self.weather = undo.weather;
+    if let Some(scheduled_events) = undo.scheduled_events {
+      self.scheduled_events = scheduled_events;
+    }
+    self.rejected_events.truncate(undo.rejected_events_len);
+    self.rejected_objects.truncate(undo.rejected_objects_len);
+    self.watch_log.truncate(undo.watch_log_len);
+    self.system_events.truncate(undo.system_events_len);
+    self.chronicler.entries.truncate(undo.chronicle_len);
+    if let Some(counts) = undo.chronicle_counts {
+      self.chronicler.counts = counts;
+    }
+    if undo.timeline_recorded {
+      self.timeline.pop();
+    }
+    true
+  }
+}
@@ -0,0 +1,132 @@
+//! 速度を持つオブジェクトの単位時間ごとの自動的な移動
+
+use crate::{Context, EventContents, ObjectType, Point};
+use num_bigint::{BigInt, BigUint};
+use num_traits::{ToPrimitive, Zero};
+
+/// 速度で動くオブジェクトの座標を軸ごとの配列に並べたもの
+/// 速度の加算を一つのループでまとめて行うために使い、単位時間をまたいで領域を使い回す
+#[derive(Debug, Clone, Default)]
+pub(crate) struct VelocityBatch {
+  /// オブジェクトのID
+  ids: Vec<String>,
+  /// x座標
+  xs: Vec<u64>,
+  /// y座標
+  ys: Vec<u64>,
+  /// x方向の速度
+  dxs: Vec<i64>,
+  /// y方向の速度
+  dys: Vec<i64>,
+}
+
+/// 加算しても`u64`をあふれない座標の上限
+/// 速度は`i64`に収まるため、これ以下の座標に足しても上にはあふれない
+const BATCH_LIMIT: u64 = i64::MAX as u64;
+
+impl VelocityBatch {
+  fn clear(&mut self) {
+    self.ids.clear();
+    self.xs.clear();
+    self.ys.clear();
+    self.dxs.clear();
+    self.dys.clear();
+  }
+
+  /// 座標が小さい場合に限って積み、積めたかどうかを返す
+  fn push(&mut self, id: &str, point: &Point, (dx, dy): (i64, i64)) -> bool {
+    let (Some(x), Some(y)) = (point.x.to_u64(), point.y.to_u64()) else {
+      return false;
+    };
+    if x > BATCH_LIMIT || y > BATCH_LIMIT {
+      return false;
+    }
+    self.ids.push(id.to_string());
+    self.xs.push(x);
+    self.ys.push(y);
+    self.dxs.push(dx);
+    self.dys.push(dy);
+    true
+  }
+
+  /// 全ての座標に速度を足す
+  /// `size`が与えられた場合はその幅と高さで折り返し、それ以外は負の方向へは`0`で止まる
+  fn advance(&mut self, size: Option<(u64, u64)>) {
+    match size {
+      Some((width, height)) => {
+        let wrap =
+          |n: u64, d: i64, size: u64| (n as i128 + d as i128).rem_euclid(size as i128) as u64;
+        for (x, dx) in self.xs.iter_mut().zip(&self.dxs) {
+          *x = wrap(*x, *dx, width);
+        }
+        for (y, dy) in self.ys.iter_mut().zip(&self.dys) {
+          *y = wrap(*y, *dy, height);
+        }
+      }
+      None => {
+        for (x, dx) in self.xs.iter_mut().zip(&self.dxs) {
+          *x = x.saturating_add_signed(*dx);
+        }
+        for (y, dy) in self.ys.iter_mut().zip(&self.dys) {
+          *y = y.saturating_add_signed(*dy);
+        }
+      }
+    }
+  }
+}
+
+/// 軸の上で`from`に`d`を足した座標
+/// `size`が与えられた場合はその長さで折り返し、それ以外は負の方向へは`0`で止まる
+fn offset(from: &BigUint, d: i64, size: Option<&BigUint>) -> BigUint {
+  let n = BigInt::from(from.clone()) + d;
+  let n = match size {
+    Some(size) => {
+      let size = BigInt::from(size.clone());
+      (n % &size + &size) % size
+    }
+    None => n,
+  };
+  n.to_biguint().unwrap_or_else(BigUint::zero)
+}
+
+/// 速度を持つ全てのオブジェクトについて、速度の分だけ動かした移動先を`moves`に積む
+/// 座標が小さいオブジェクトは`batch`にまとめて計算し、大きいものは一つずつ`BigUint`で計算する
+/// 端と端がつながった世界では移動先を折り返す
+pub(crate) fn collect_velocity_moves<T: EventContents<U>, U: ObjectType>(
+  ctx: &Context<T, U>,
+  batch: &mut VelocityBatch,
+  moves: &mut Vec<(String, Point)>,
+) {
+  batch.clear();
+  let (width, height) = match &ctx.torus {
+    Some(torus) => (Some(torus.width()), Some(torus.height())),
+    None => (None, None),
+  };
+  // 折り返す幅と高さが`u64`に収まらない場合はまとめて計算しない
+  let size = match (width, height) {
+    (Some(width), Some(height)) => width.to_u64().zip(height.to_u64()).map(Some),
+    _ => Some(None),
+  };
+  for (id, object) in &ctx.objects {
+    let Some(velocity) = object.object_type.velocity() else {
+      continue;
+    };
+    if velocity == (0, 0) || size.is_some() && batch.push(id, &object.point, velocity) {
+      continue;
+    }
+    let (dx, dy) = velocity;
+    let point = Point {
+      x: offset(&object.point.x, dx, width),
+      y: offset(&object.point.y, dy, height),
+    };
+    moves.push((id.clone(), point));
+  }
+  batch.advance(size.flatten());
+  for ((id, x), y) in batch.ids.drain(..).zip(&batch.xs).zip(&batch.ys) {
+    let point = Point {
+      x: BigUint::from(*x),
+      y: BigUint::from(*y),
+    };
+    moves.push((id, point));
+  }
+}
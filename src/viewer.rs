@@ -0,0 +1,85 @@
+//! 外部のビューアに単位時間ごとの世界を渡すためのJSON
+
+use crate::{Context, EventContents, ObjectType, Point};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// 書き出すJSONの形式の版
+const FRAME_SCHEMA: u32 = 1;
+
+/// 地点をJSONにする
+/// 座標は`BigUint`なので、桁あふれしないよう10進数の文字列にする
+fn point_json(point: &Point) -> Value {
+  json!({ "x": point.x.to_string(), "y": point.y.to_string() })
+}
+
+impl<T, U> Context<T, U>
+where
+  T: EventContents<U> + Serialize,
+  U: ObjectType + Serialize,
+{
+  /// 今の世界を一コマ分のJSONにする
+  /// 形式は以下の通りで、形式を変えた場合は`schema`の値を上げる
+  ///
+  /// ```text
+  /// {
+  ///   "schema": 1,
+  ///   "time": { "all", "year", "day", "day_of_year", "time_of_day", "one_day_of_time" },
+  ///   "objects": [{ "id", "name", "point": { "x", "y" }, "parent_frame", "data" }],
+  ///   "events": [{ "id", "sequence", "kind", "do_object", "target_object", "location", "affected_objects", "data" }]
+  /// }
+  /// ```
+  ///
+  /// - 時刻や座標などの整数は10進数の文字列になる
+  /// - `objects`はIDの辞書順に並び、`point`は`Context::world_point`で求めた世界での座標になる
+  ///   親を辿ると循環している場合は`point`が`null`になる
+  /// - `events`は今の単位時間に起きて記憶されているイベントで、記憶された順に並ぶ
+  /// - `data`はオブジェクトやイベントの中身をそのままJSONにしたもの
+  pub fn to_frame_json(&self) -> String {
+    let time = json!({
+      "all": self.time.all.to_string(),
+      "year": self.time.year.to_string(),
+      "day": self.time.day.to_string(),
+      "day_of_year": self.time.remainder_day.to_string(),
+      "time_of_day": self.time.remainder_time.to_string(),
+      "one_day_of_time": self.time.one_day_of_time.to_string(),
+    });
+    let objects = self
+      .objects_sorted()
+      .into_iter()
+      .map(|(id, object)| {
+        json!({
+          "id": id,
+          "name": object.object_type.name(),
+          "point": self.world_point(id).as_ref().map(point_json),
+          "parent_frame": object.parent_frame,
+          "data": serde_json::to_value(&object.object_type).unwrap_or(Value::Null),
+        })
+      })
+      .collect::<Vec<_>>();
+    let events = self
+      .memory
+      .iter()
+      .filter(|e| e.generated_time.all == self.time.all)
+      .map(|e| {
+        json!({
+          "id": e.id,
+          "sequence": e.sequence,
+          "kind": e.contents.kind(),
+          "do_object": e.do_object,
+          "target_object": e.target_object,
+          "location": e.location.as_ref().map(point_json),
+          "affected_objects": e.affected_objects,
+          "data": serde_json::to_value(&e.contents).unwrap_or(Value::Null),
+        })
+      })
+      .collect::<Vec<_>>();
+    json!({
+      "schema": FRAME_SCHEMA,
+      "time": time,
+      "objects": objects,
+      "events": events,
+    })
+    .to_string()
+  }
+}
@@ -0,0 +1,83 @@
+//! 特定のオブジェクトに注目して、それに関わる出来事を全て記録する仕組み
+
+use crate::{Context, Event, EventContents, ObjectType, Point, Time};
+
+/// 注目しているオブジェクトに起きた出来事の記録
+#[derive(Debug, Clone)]
+pub struct WatchRecord<T> {
+  /// 出来事が起きた時刻
+  pub time: Time,
+  /// 注目しているオブジェクトのID
+  pub object_id: String,
+  /// 起きた出来事
+  pub kind: WatchKind<T>,
+}
+
+/// 注目しているオブジェクトに起きた出来事の種類
+#[derive(Debug, Clone)]
+pub enum WatchKind<T> {
+  /// 主体・対象・影響範囲のいずれかとしてイベントに関わった
+  Event(T),
+  /// 主体として起こそうとしたイベントが検査で弾かれた
+  Rejected {
+    /// 弾かれたイベントの中身
+    contents: T,
+    /// 弾かれた理由
+    reason: String,
+  },
+  /// 移動した
+  Moved {
+    /// 移動前の地点
+    from: Point,
+    /// 移動後の地点
+    to: Point,
+  },
+  /// 削除された
+  Removed,
+}
+
+impl<T: EventContents<U>, U: ObjectType> Context<T, U> {
+  /// オブジェクトに注目し、以降そのオブジェクトに関わる出来事を`watch_log`に記録する
+  /// 複数のオブジェクトに同時に注目できる
+  pub fn watch(&mut self, id: &str) {
+    self.watched.insert(id.to_string());
+  }
+
+  /// オブジェクトへの注目をやめる
+  /// これまでの記録は残る
+  pub fn unwatch(&mut self, id: &str) {
+    self.watched.remove(id);
+  }
+
+  /// オブジェクトに注目しているかどうか
+  pub fn is_watched(&self, id: &str) -> bool {
+    self.watched.contains(id)
+  }
+
+  /// 注目しているオブジェクトに起きた出来事を記録する
+  pub(crate) fn record_watch(&mut self, id: &str, kind: WatchKind<T>) {
+    if self.is_watched(id) {
+      self.watch_log.push(WatchRecord {
+        time: self.time.clone(),
+        object_id: id.to_string(),
+        kind,
+      });
+    }
+  }
+
+  /// イベントに関わっている注目中のオブジェクトについて、イベントを記録する
+  pub(crate) fn record_watched_event(&mut self, event: &Event<T>) {
+    if self.watched.is_empty() {
+      return;
+    }
+    let involved = std::iter::once(&event.do_object)
+      .chain(event.target_object.iter())
+      .chain(event.affected_objects.iter())
+      .filter(|id| self.watched.contains(*id))
+      .cloned()
+      .collect::<Vec<_>>();
+    for id in involved {
+      self.record_watch(&id, WatchKind::Event(event.contents.clone()));
+    }
+  }
+}
@@ -0,0 +1,50 @@
+mod common;
+
+use common::*;
+use hakoniwa::run;
+
+fn health(ctx: &World, id: &str) -> i64 {
+  ctx.objects[id].object_type.health
+}
+
+#[test]
+fn area_patch_applies_to_every_object_in_range() {
+  let mut ctx = world([
+    ("a", tree("oak", 0, 0)),
+    ("b", tree("pine", 1, 1)),
+    ("c", tree("elm", 10, 10)),
+  ]);
+  let blight = Act {
+    patch: Some(("a".to_string(), -3)),
+    area: Some((pt(0, 0), 2)),
+    ..act("a")
+  };
+  run(&mut ctx, vec![emit(vec![blight])]).unwrap();
+  assert_eq!(health(&ctx, "a"), -3);
+  assert_eq!(health(&ctx, "b"), -3);
+  assert_eq!(health(&ctx, "c"), 0);
+  assert!(ctx.run_warnings.is_empty());
+  let event = ctx.memory.back().unwrap();
+  assert_eq!(
+    event.affected_objects,
+    vec!["a".to_string(), "b".to_string()]
+  );
+}
+
+#[test]
+fn area_patch_skips_objects_removed_in_the_same_tick() {
+  let mut ctx = world([("a", tree("oak", 0, 0)), ("b", tree("pine", 1, 1))]);
+  let blight = Act {
+    patch: Some(("a".to_string(), -1)),
+    area: Some((pt(0, 0), 2)),
+    ..act("a")
+  };
+  let fell = Act {
+    remove: vec!["b".to_string()],
+    ..act("a")
+  };
+  run(&mut ctx, vec![emit(vec![blight, fell])]).unwrap();
+  assert_eq!(health(&ctx, "a"), -1);
+  assert!(!ctx.objects.contains_key("b"));
+  assert!(ctx.run_warnings.is_empty());
+}
@@ -0,0 +1,25 @@
+#![cfg(feature = "bevy")]
+
+mod common;
+
+use bevy_ecs::system::RunSystemOnce;
+use common::*;
+use hakoniwa::bevy::{sync_objects_system, HakoniwaObject, ObjectBridge};
+
+#[test]
+fn context_is_synced_as_a_resource() {
+  let mut app = bevy_ecs::world::World::new();
+  app.insert_resource(world([("a", tree("oak", 0, 0)), ("b", tree("pine", 5, 5))]));
+  app.init_resource::<ObjectBridge>();
+  app
+    .run_system_once(sync_objects_system::<Act, Tree>)
+    .unwrap();
+  assert_eq!(app.resource::<ObjectBridge>().len(), 2);
+  app.resource_mut::<World>().objects.remove("b");
+  app
+    .run_system_once(sync_objects_system::<Act, Tree>)
+    .unwrap();
+  let mut query = app.query::<&HakoniwaObject>();
+  let ids = query.iter(&app).map(|o| o.id.clone()).collect::<Vec<_>>();
+  assert_eq!(ids, vec!["a".to_string()]);
+}
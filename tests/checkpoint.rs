@@ -0,0 +1,51 @@
+#![cfg(feature = "compression")]
+
+mod common;
+
+use bincode::Options;
+use common::*;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use num_bigint::BigUint;
+
+/// 版1の時刻の並び
+/// `day_periods`を持たない
+fn legacy_time(all: u64) -> [BigUint; 7] {
+  let day = all / DAY;
+  [all, DAY, day, all % DAY, YEAR, day / YEAR, day % YEAR].map(BigUint::from)
+}
+
+#[test]
+fn loads_version_1_checkpoint_without_day_periods() {
+  let options = bincode::DefaultOptions::new();
+  let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+  options
+    .serialize_into(&mut encoder, &(*b"HKNW", 1u32, legacy_time(123), 1u64))
+    .unwrap();
+  let oak = tree("oak", 4, 2);
+  let saved = ("a", legacy_time(5), legacy_time(7), pt(4, 2), (1u32, &oak));
+  options.serialize_into(&mut encoder, &saved).unwrap();
+  let bytes = encoder.finish().unwrap();
+
+  let mut ctx = world([]);
+  ctx.load_compressed(bytes.as_slice()).unwrap();
+  assert_eq!(ctx.time, time(123));
+  let object = &ctx.objects["a"];
+  assert_eq!(object.generated_time, time(5));
+  assert_eq!(object.last_modified, time(7));
+  assert_eq!(object.point, pt(4, 2));
+  assert_eq!(object.object_type, oak);
+  assert_eq!(object.parent_frame, None);
+}
+
+#[test]
+fn checkpoint_round_trips_current_version() {
+  let mut ctx = world([("a", tree("oak", 0, 0)), ("b", tree("pine", 5, 5))]);
+  ctx.time = time(42);
+  let mut bytes = Vec::new();
+  ctx.save_compressed(&mut bytes).unwrap();
+  let mut loaded = world([]);
+  loaded.load_compressed(bytes.as_slice()).unwrap();
+  assert_eq!(loaded.time, ctx.time);
+  assert_eq!(objects(&loaded), objects(&ctx));
+}
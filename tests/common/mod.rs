@@ -0,0 +1,210 @@
+//! 結合テストで共通して使う世界
+#![allow(dead_code)]
+
+use hakoniwa::{Context, EventContents, GeneratedData, Generater, Object, ObjectType, Point, Time};
+use num_bigint::BigUint;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// 一日の単位時間
+pub const DAY: u64 = 10;
+/// 一年の日数
+pub const YEAR: u64 = 10;
+
+/// 木
+/// 体力を変える部分的な変更を受け付ける
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Tree {
+  pub name: String,
+  pub x: u64,
+  pub y: u64,
+  pub health: i64,
+  /// 一回の単位時間に移動できる距離
+  pub speed: Option<u64>,
+  /// 一回の単位時間ごとに自動で動く量
+  pub velocity: Option<(i64, i64)>,
+}
+
+impl ObjectType for Tree {
+  type Patch = i64;
+  fn name(&self) -> String {
+    self.name.clone()
+  }
+  fn generated_point(&self) -> Point {
+    pt(self.x, self.y)
+  }
+  fn max_move_per_tick(&self) -> Option<BigUint> {
+    self.speed.map(BigUint::from)
+  }
+  fn velocity(&self) -> Option<(i64, i64)> {
+    self.velocity
+  }
+  fn apply_patch(&mut self, patch: i64) {
+    self.health += patch;
+  }
+}
+
+#[cfg(feature = "serde")]
+impl hakoniwa::Migrate for Tree {
+  const VERSION: u32 = 1;
+  fn migrate<'de, D: serde::Deserializer<'de>>(
+    from_version: u32,
+    _deserializer: D,
+  ) -> Result<Self, D::Error> {
+    Err(serde::de::Error::custom(format!(
+      "unknown tree version {from_version}"
+    )))
+  }
+}
+
+/// 木に起きる出来事
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Act {
+  pub actor: String,
+  pub target: Option<String>,
+  pub spawn: Vec<Tree>,
+  pub remove: Vec<String>,
+  pub moves: Option<(String, Point)>,
+  pub patch: Option<(String, i64)>,
+  pub lifetime: Option<u64>,
+  pub location: Option<Point>,
+  pub area: Option<(Point, u64)>,
+  pub priority: i64,
+}
+
+impl EventContents<Tree> for Act {
+  fn generate_objects(&self) -> Vec<Tree> {
+    self.spawn.clone()
+  }
+  fn remove_object_opt(&self) -> Option<String> {
+    None
+  }
+  fn remove_objects(&self) -> Vec<String> {
+    self.remove.clone()
+  }
+  fn move_object_opt(&self) -> Option<(String, Point)> {
+    self.moves.clone()
+  }
+  fn update_object_opt(&self) -> Option<(String, i64)> {
+    self.patch.clone()
+  }
+  fn lifetime(&self) -> Option<Time> {
+    self.lifetime.map(time)
+  }
+  fn do_object(&self) -> String {
+    self.actor.clone()
+  }
+  fn target_object_opt(&self) -> Option<String> {
+    self.target.clone()
+  }
+  fn location(&self) -> Option<Point> {
+    self.location.clone()
+  }
+  fn area_of_effect(&self) -> Option<(Point, BigUint)> {
+    self
+      .area
+      .clone()
+      .map(|(center, radius)| (center, BigUint::from(radius)))
+  }
+  fn priority(&self) -> i64 {
+    self.priority
+  }
+}
+
+pub type World = Context<Act, Tree>;
+
+/// 経過した単位時間が`all`の時刻
+pub fn time(all: u64) -> Time {
+  Time::new(all.into(), DAY.into(), YEAR.into())
+}
+
+pub fn pt(x: u64, y: u64) -> Point {
+  Point::new(x.into(), y.into())
+}
+
+pub fn tree(name: &str, x: u64, y: u64) -> Tree {
+  Tree {
+    name: name.to_string(),
+    x,
+    y,
+    ..Tree::default()
+  }
+}
+
+/// 時刻`all`に`number`番目に生成された`tree`に振られるID
+/// `wall-clock-ids`機能を有効にした場合は実行ごとに変わるので使えない
+pub fn spawned_id(tree: &Tree, all: u64, number: u64) -> String {
+  let str = format!(
+    "{}{:?}{:?}#{number}",
+    tree.name,
+    tree.generated_point(),
+    BigUint::from(all)
+  );
+  base64::encode(str.as_bytes())
+}
+
+/// 時刻`0`に生成された、`tree`の生成地点にあるオブジェクト
+pub fn object(tree: Tree) -> Object<Tree> {
+  Object {
+    generated_time: time(0),
+    last_modified: time(0),
+    point: tree.generated_point(),
+    object_type: tree,
+    parent_frame: None,
+    caused_by: None,
+  }
+}
+
+/// 時刻`0`から始まり、IDと中身の組をオブジェクトとして持つ世界
+pub fn world(trees: impl IntoIterator<Item = (&'static str, Tree)>) -> World {
+  Context::new(
+    time(0),
+    trees
+      .into_iter()
+      .map(|(id, tree)| (id.to_string(), object(tree))),
+  )
+}
+
+/// `actor`が起こす何もしない出来事
+pub fn act(actor: &str) -> Act {
+  Act {
+    actor: actor.to_string(),
+    ..Act::default()
+  }
+}
+
+/// 世界を見て情報を作るgenerate関数
+pub fn generator(
+  f: impl Fn(&World) -> GeneratedData<Act, Tree> + Send + Sync + 'static,
+) -> Generater<Act, Tree> {
+  Arc::new(f)
+}
+
+/// 毎回同じイベントを起こすgenerate関数
+pub fn emit(events: Vec<Act>) -> Generater<Act, Tree> {
+  generator(move |_| GeneratedData {
+    events: events.clone(),
+    ..GeneratedData::empty()
+  })
+}
+
+/// IDの順に並べたオブジェクトの地点と中身
+pub fn objects(ctx: &World) -> Vec<(String, Point, Tree)> {
+  ctx
+    .objects_sorted()
+    .into_iter()
+    .map(|(id, object)| (id.clone(), object.point.clone(), object.object_type.clone()))
+    .collect()
+}
+
+/// 記憶されているイベントのIDと中身
+pub fn memory(ctx: &World) -> Vec<(String, Act)> {
+  ctx
+    .memory
+    .iter()
+    .map(|event| (event.id.clone(), event.contents.clone()))
+    .collect()
+}
@@ -0,0 +1,138 @@
+mod common;
+
+use common::*;
+use hakoniwa::{run, EventOrder, MoveLimit, SimConfig, SpawnPlacement, Torus};
+use rand::Rng;
+
+fn config() -> SimConfig {
+  SimConfig::new(DAY.into(), YEAR.into())
+}
+
+#[test]
+fn from_config_rejects_invalid_settings() {
+  let mut zero_day = config();
+  zero_day.one_day_of_time = 0u64.into();
+  assert!(World::from_config(zero_day, []).is_err());
+  let duplicated = [
+    ("a".to_string(), object(tree("oak", 0, 0))),
+    ("a".to_string(), object(tree("pine", 1, 1))),
+  ];
+  assert!(World::from_config(config(), duplicated).is_err());
+}
+
+#[test]
+fn from_config_applies_every_setting() {
+  let mut config = config();
+  config.start_time = 25u64.into();
+  config.memory_capacity = Some(3);
+  config.seed = 7;
+  config.spatial_cell_size = Some(4u64.into());
+  let mut ctx = World::from_config(config, [("a".to_string(), object(tree("oak", 2, 2)))]).unwrap();
+  assert_eq!(ctx.time, time(25));
+  assert_eq!(ctx.memory_capacity, Some(3));
+  assert_eq!(ctx.seed, 7);
+  assert_eq!(ctx.objects_within(&pt(0, 0), &3u64.into()).len(), 1);
+  let mut reseeded = world([]);
+  config_with_seed(7).apply(&mut reseeded);
+  assert_eq!(ctx.rng().gen::<u64>(), reseeded.rng().gen::<u64>());
+}
+
+fn config_with_seed(seed: u64) -> SimConfig {
+  SimConfig { seed, ..config() }
+}
+
+#[test]
+fn apply_keeps_calendar_and_objects() {
+  let mut ctx = world([("a", tree("oak", 0, 0))]);
+  ctx.time = time(13);
+  let mut config = config_with_seed(3);
+  config.one_day_of_time = 99u64.into();
+  config.memory_capacity = Some(1);
+  config.apply(&mut ctx);
+  assert_eq!(ctx.time, time(13));
+  assert_eq!(ctx.objects.len(), 1);
+  assert_eq!(ctx.memory_capacity, Some(1));
+  assert_eq!(ctx.seed, 3);
+}
+
+#[test]
+fn spawn_placement_from_config_separates_spawns() {
+  let mut config = config();
+  config.spawn_placement = Some(SpawnPlacement { max_distance: 1 });
+  let mut ctx = World::from_config(config, [("a".to_string(), object(tree("oak", 3, 3)))]).unwrap();
+  let sow = Act {
+    spawn: vec![tree("sapling", 3, 3)],
+    ..act("a")
+  };
+  run(&mut ctx, vec![emit(vec![sow])]).unwrap();
+  let points = objects(&ctx)
+    .into_iter()
+    .map(|(_, point, _)| point)
+    .collect::<std::collections::HashSet<_>>();
+  assert_eq!(points.len(), 2);
+}
+
+#[test]
+fn event_order_from_config_sorts_by_priority() {
+  let mut config = config();
+  config.event_order = EventOrder::Priority;
+  let mut ctx = World::from_config(config, [("a".to_string(), object(tree("oak", 0, 0)))]).unwrap();
+  let low = act("low");
+  let high = Act {
+    priority: 5,
+    ..act("high")
+  };
+  run(&mut ctx, vec![emit(vec![low, high])]).unwrap();
+  let actors = memory(&ctx)
+    .into_iter()
+    .map(|(_, e)| e.actor)
+    .collect::<Vec<_>>();
+  assert_eq!(actors, vec!["high".to_string(), "low".to_string()]);
+}
+
+#[test]
+fn strict_from_config_rejects_inconsistent_ticks() {
+  let mut config = config();
+  config.strict = true;
+  let mut ctx = World::from_config(config, [("a".to_string(), object(tree("oak", 0, 0)))]).unwrap();
+  let remove_ghost = Act {
+    remove: vec!["ghost".to_string()],
+    ..act("a")
+  };
+  assert!(run(&mut ctx, vec![emit(vec![remove_ghost])]).is_err());
+  assert_eq!(ctx.time, time(0));
+}
+
+#[test]
+fn torus_from_config_wraps_objects() {
+  let mut config = config();
+  config.torus = Some(Torus::new(10u64.into(), 10u64.into()));
+  config.spatial_cell_size = Some(4u64.into());
+  let ctx = World::from_config(
+    config,
+    [
+      ("a".to_string(), object(tree("oak", 13, 0))),
+      ("b".to_string(), object(tree("oak", 9, 0))),
+    ],
+  )
+  .unwrap();
+  assert_eq!(ctx.objects["a"].point, pt(3, 0));
+  assert_eq!(ctx.objects_within(&pt(0, 0), &1u64.into()).len(), 1);
+}
+
+#[test]
+fn move_limit_from_config_clamps_moves() {
+  let mut config = config();
+  config.move_limit = MoveLimit::Clamp;
+  let walker = Tree {
+    speed: Some(5),
+    ..tree("walker", 0, 0)
+  };
+  let mut ctx = World::from_config(config, [("a".to_string(), object(walker))]).unwrap();
+  let step = Act {
+    moves: Some(("a".to_string(), pt(30, 40))),
+    ..act("a")
+  };
+  run(&mut ctx, vec![emit(vec![step])]).unwrap();
+  assert_eq!(ctx.objects["a"].point, pt(3, 4));
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use common::*;
+use hakoniwa::{run, run_phased, Phase, RunError};
+
+fn move_ghost() -> Act {
+  Act {
+    moves: Some(("ghost".to_string(), pt(1, 1))),
+    ..act("a")
+  }
+}
+
+fn remove_ghost() -> Act {
+  Act {
+    remove: vec!["ghost".to_string()],
+    ..act("a")
+  }
+}
+
+/// 不整合を起こすイベントを一つだけ起こした時の、寛容な扱いでの警告と厳格な扱いでの不整合
+fn lenient_and_strict(e: Act) -> (Vec<RunError>, Vec<RunError>) {
+  let mut lenient = world([("a", tree("oak", 0, 0))]);
+  run(&mut lenient, vec![emit(vec![e.clone()])]).unwrap();
+  assert_eq!(lenient.time, time(1));
+  let mut strict = world([("a", tree("oak", 0, 0))]);
+  strict.strict = true;
+  let errors = run(&mut strict, vec![emit(vec![e])]).unwrap_err().errors;
+  assert_eq!(strict.time, time(0));
+  assert!(strict.memory.is_empty());
+  (lenient.run_warnings, errors)
+}
+
+#[test]
+fn missing_move_target() {
+  let expected = vec![RunError::MoveTargetMissing {
+    id: "ghost".to_string(),
+  }];
+  assert_eq!(
+    lenient_and_strict(move_ghost()),
+    (expected.clone(), expected)
+  );
+}
+
+#[test]
+fn missing_remove_target() {
+  let expected = vec![RunError::RemoveTargetMissing {
+    id: "ghost".to_string(),
+  }];
+  assert_eq!(
+    lenient_and_strict(remove_ghost()),
+    (expected.clone(), expected)
+  );
+}
+
+#[cfg(not(feature = "wall-clock-ids"))]
+#[test]
+fn duplicate_generated_id() {
+  let sapling = tree("sapling", 1, 1);
+  let taken = spawned_id(&sapling, 1, 0);
+  let sow = Act {
+    spawn: vec![sapling.clone()],
+    ..act("a")
+  };
+  let mut lenient = world([("a", tree("oak", 0, 0))]);
+  lenient.objects.insert(
+    taken.clone(),
+    std::sync::Arc::new(object(tree("pine", 5, 5))),
+  );
+  let mut strict = lenient.fork();
+  strict.strict = true;
+  run(&mut lenient, vec![emit(vec![sow.clone()])]).unwrap();
+  assert_eq!(
+    lenient.run_warnings,
+    vec![RunError::DuplicateId { id: taken.clone() }]
+  );
+  assert_eq!(lenient.objects[&taken].object_type.name, "sapling");
+  let errors = run(&mut strict, vec![emit(vec![sow])]).unwrap_err().errors;
+  assert_eq!(errors, vec![RunError::DuplicateId { id: taken }]);
+  assert_eq!(strict.time, time(0));
+}
+
+#[test]
+fn warnings_are_replaced_every_tick() {
+  let mut ctx = world([("a", tree("oak", 0, 0))]);
+  run(&mut ctx, vec![emit(vec![remove_ghost()])]).unwrap();
+  assert_eq!(ctx.run_warnings.len(), 1);
+  run(&mut ctx, vec![]).unwrap();
+  assert!(ctx.run_warnings.is_empty());
+}
+
+#[test]
+fn run_phased_follows_strict() {
+  let phased = || vec![(Phase::Action, emit(vec![remove_ghost()]))];
+  let mut lenient = world([("a", tree("oak", 0, 0))]);
+  run_phased(&mut lenient, phased()).unwrap();
+  assert_eq!(lenient.time, time(1));
+  assert_eq!(
+    lenient.run_warnings,
+    vec![RunError::RemoveTargetMissing {
+      id: "ghost".to_string()
+    }]
+  );
+  let mut strict = world([("a", tree("oak", 0, 0))]);
+  strict.strict = true;
+  assert!(run_phased(&mut strict, phased()).is_err());
+  assert_eq!(strict.time, time(0));
+}
+
+#[cfg(feature = "step-debug")]
+#[test]
+fn step_debug_follows_strict() {
+  use hakoniwa::step_debug;
+  let mut lenient = world([("a", tree("oak", 0, 0))]);
+  step_debug(&mut lenient, vec![emit(vec![move_ghost()])], |_, _, _| {}).unwrap();
+  assert_eq!(
+    lenient.run_warnings,
+    vec![RunError::MoveTargetMissing {
+      id: "ghost".to_string()
+    }]
+  );
+  let mut strict = world([("a", tree("oak", 0, 0))]);
+  strict.strict = true;
+  let mut calls = 0;
+  let result = step_debug(&mut strict, vec![emit(vec![move_ghost()])], |_, _, _| {
+    calls += 1
+  });
+  assert!(result.is_err());
+  assert_eq!(calls, 1);
+  assert_eq!(strict.time, time(0));
+}
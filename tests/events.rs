@@ -0,0 +1,118 @@
+mod common;
+
+use common::*;
+use hakoniwa::{run, run_n, GeneratedData};
+
+#[test]
+fn spawned_child_records_the_causing_event() {
+  let mut ctx = world([("parent", tree("oak", 0, 0))]);
+  let fruit = Act {
+    spawn: vec![tree("seed", 1, 1)],
+    ..act("parent")
+  };
+  let direct = generator(|_| GeneratedData {
+    generate_objects: vec![tree("stray", 2, 2)],
+    ..GeneratedData::empty()
+  });
+  run(&mut ctx, vec![emit(vec![fruit]), direct]).unwrap();
+  let event_id = ctx.memory[0].id.clone();
+  let cause = |name: &str| {
+    ctx
+      .objects
+      .values()
+      .find(|object| object.object_type.name == name)
+      .unwrap()
+      .caused_by
+      .clone()
+  };
+  assert_eq!(cause("seed"), Some(event_id));
+  assert_eq!(cause("stray"), None);
+  assert_eq!(ctx.objects["parent"].caused_by, None);
+}
+
+/// 同じ主体が毎回二つずつイベントを起こす世界を`ticks`回進めた時の、記憶されたイベントのID
+fn event_ids(seed: u64, ticks: usize) -> Vec<String> {
+  let mut ctx = world([("a", tree("oak", 0, 0))]);
+  ctx.seed = seed;
+  run_n(&mut ctx, &[emit(vec![act("a"), act("a")])], ticks).unwrap();
+  ctx.memory.iter().map(|e| e.id.clone()).collect()
+}
+
+#[test]
+fn events_in_one_tick_get_distinct_ids() {
+  let ids = event_ids(0, 1);
+  assert_eq!(ids.len(), 2);
+  assert_ne!(ids[0], ids[1]);
+}
+
+#[test]
+fn event_ids_are_reproducible() {
+  let ids = event_ids(277, 3);
+  assert_eq!(ids.len(), 6);
+  assert_eq!(ids, event_ids(277, 3));
+  assert_eq!(
+    ids.iter().collect::<std::collections::HashSet<_>>().len(),
+    6
+  );
+}
+
+fn towards(actor: &str, target: &str) -> Act {
+  Act {
+    target: Some(target.to_string()),
+    ..act(actor)
+  }
+}
+
+/// 記憶されたイベントを、主体と対象の組で表したもの
+fn pairs(events: Vec<&hakoniwa::Event<Act>>) -> Vec<(String, Option<String>)> {
+  events
+    .into_iter()
+    .map(|e| (e.do_object.clone(), e.target_object.clone()))
+    .collect()
+}
+
+#[test]
+fn memory_is_partitioned_by_actor_and_target() {
+  let mut ctx = world([
+    ("a", tree("oak", 0, 0)),
+    ("b", tree("oak", 1, 0)),
+    ("c", tree("oak", 2, 0)),
+  ]);
+  let events = vec![
+    towards("a", "b"),
+    towards("b", "a"),
+    act("a"),
+    towards("c", "b"),
+  ];
+  run(&mut ctx, vec![emit(events)]).unwrap();
+  let pair = |actor: &str, target: Option<&str>| (actor.to_string(), target.map(str::to_string));
+  assert_eq!(
+    pairs(ctx.events_by_actor("a")),
+    vec![pair("a", Some("b")), pair("a", None)]
+  );
+  assert_eq!(pairs(ctx.events_by_actor("b")), vec![pair("b", Some("a"))]);
+  assert_eq!(
+    pairs(ctx.events_targeting("b")),
+    vec![pair("a", Some("b")), pair("c", Some("b"))]
+  );
+  assert_eq!(pairs(ctx.events_targeting("a")), vec![pair("b", Some("a"))]);
+  assert!(ctx.events_targeting("c").is_empty());
+  assert!(ctx.events_by_actor("ghost").is_empty());
+}
+
+#[test]
+fn events_between_includes_start_and_excludes_end() {
+  let mut ctx = world([("a", tree("oak", 0, 0))]);
+  run_n(&mut ctx, &[emit(vec![act("a")])], 4).unwrap();
+  let times = |start: u64, end: u64| {
+    ctx
+      .events_between(&time(start), &time(end))
+      .into_iter()
+      .map(|e| e.generated_time.clone())
+      .collect::<Vec<_>>()
+  };
+  assert_eq!(times(2, 4), vec![time(2), time(3)]);
+  assert_eq!(times(0, 5), vec![time(1), time(2), time(3), time(4)]);
+  assert!(times(3, 3).is_empty());
+  assert!(times(4, 2).is_empty());
+}
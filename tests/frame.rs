@@ -0,0 +1,117 @@
+mod common;
+
+use common::*;
+use hakoniwa::{run, SpawnPlacement};
+use std::sync::Arc;
+
+/// 親`p`が(10, 10)にあり、子`c`が親から(1, 1)ずれた所にある世界
+fn carried(indexed: bool) -> World {
+  let mut ctx = world([("p", tree("cart", 10, 10))]);
+  let mut child = object(tree("seed", 1, 1));
+  child.parent_frame = Some("p".to_string());
+  ctx.objects.insert("c".to_string(), Arc::new(child));
+  ctx.rebuild_name_index();
+  if indexed {
+    ctx.set_spatial_index(Some(4u64.into()));
+  }
+  ctx
+}
+
+fn ids_at(ctx: &World, x: u64, y: u64) -> Vec<String> {
+  ctx
+    .objects_at(&pt(x, y))
+    .into_iter()
+    .map(|(id, _)| id.clone())
+    .collect()
+}
+
+#[test]
+fn spatial_queries_use_world_points() {
+  for indexed in [false, true] {
+    let mut ctx = carried(indexed);
+    assert_eq!(ids_at(&ctx, 11, 11), vec!["c".to_string()]);
+    assert!(ids_at(&ctx, 1, 1).is_empty());
+    assert_eq!(ctx.objects_within(&pt(12, 12), &2u64.into()).len(), 1);
+    let pull = Act {
+      moves: Some(("p".to_string(), pt(20, 20))),
+      ..act("p")
+    };
+    run(&mut ctx, vec![emit(vec![pull])]).unwrap();
+    assert_eq!(ids_at(&ctx, 21, 21), vec!["c".to_string()]);
+    assert!(ids_at(&ctx, 11, 11).is_empty());
+  }
+}
+
+#[test]
+fn area_of_effect_and_rect_removal_use_world_points() {
+  let mut ctx = carried(false);
+  let frost = Act {
+    patch: Some(("c".to_string(), -1)),
+    area: Some((pt(11, 11), 0)),
+    ..act("p")
+  };
+  run(&mut ctx, vec![emit(vec![frost])]).unwrap();
+  assert_eq!(ctx.objects["c"].object_type.health, -1);
+  assert_eq!(ctx.objects["p"].object_type.health, 0);
+  assert_eq!(
+    ctx.remove_in_rect(&pt(11, 11), &pt(11, 11)),
+    vec!["c".to_string()]
+  );
+}
+
+#[test]
+fn clusters_and_sight_use_world_points() {
+  let mut ctx = carried(false);
+  ctx
+    .objects
+    .insert("far".to_string(), Arc::new(object(tree("oak", 3, 0))));
+  let components = ctx.connected_components(&2u64.into());
+  assert_eq!(
+    components,
+    vec![
+      vec!["c".to_string(), "p".to_string()],
+      vec!["far".to_string()]
+    ]
+  );
+  assert!(!ctx.has_line_of_sight(&pt(9, 9), &pt(13, 13)));
+  assert!(ctx.has_line_of_sight(&pt(0, 0), &pt(2, 2)));
+}
+
+#[test]
+fn spawn_placement_avoids_world_points() {
+  let mut ctx = carried(false);
+  ctx.spawn_placement = Some(SpawnPlacement { max_distance: 1 });
+  let sow = Act {
+    spawn: vec![tree("sapling", 11, 11)],
+    ..act("p")
+  };
+  run(&mut ctx, vec![emit(vec![sow])]).unwrap();
+  let sapling = ctx
+    .objects
+    .values()
+    .find(|object| object.object_type.name == "sapling")
+    .unwrap();
+  assert_ne!(sapling.point, pt(11, 11));
+}
+
+#[test]
+fn set_parent_frame_reparents_live_objects() {
+  let mut ctx = carried(true);
+  ctx
+    .objects
+    .insert("q".to_string(), Arc::new(object(tree("cart", 30, 30))));
+  ctx.rebuild_spatial_index();
+  ctx.set_parent_frame("c", Some("q")).unwrap();
+  assert_eq!(ctx.world_point("c"), Some(pt(31, 31)));
+  assert_eq!(ids_at(&ctx, 31, 31), vec!["c".to_string()]);
+  ctx.set_parent_frame("c", None).unwrap();
+  assert_eq!(ctx.world_point("c"), Some(pt(1, 1)));
+  assert_eq!(ids_at(&ctx, 1, 1), vec!["c".to_string()]);
+  assert!(ids_at(&ctx, 31, 31).is_empty());
+  assert!(ctx.set_parent_frame("c", Some("missing")).is_err());
+  assert!(ctx.set_parent_frame("missing", Some("p")).is_err());
+  ctx.set_parent_frame("c", Some("p")).unwrap();
+  assert!(ctx.set_parent_frame("p", Some("c")).is_err());
+  assert!(ctx.set_parent_frame("p", Some("p")).is_err());
+  assert!(ctx.frame_cycles().is_empty());
+}
@@ -0,0 +1,36 @@
+mod common;
+
+use common::*;
+use hakoniwa::GeneratedData;
+
+#[test]
+fn empty_has_nothing() {
+  let empty = GeneratedData::<Act, Tree>::empty();
+  assert!(empty.events.is_empty());
+  assert!(empty.generate_objects.is_empty());
+  assert!(empty.remove_objects.is_empty());
+  assert_eq!(empty, GeneratedData::default());
+}
+
+#[test]
+fn merge_appends_every_part() {
+  let mut first = GeneratedData {
+    events: vec![act("a")],
+    generate_objects: vec![tree("oak", 0, 0)],
+    ..GeneratedData::empty()
+  };
+  let second = GeneratedData {
+    events: vec![act("b")],
+    remove_objects: vec!["c".to_string()],
+    ..GeneratedData::empty()
+  };
+  first.merge(second);
+  assert_eq!(
+    first,
+    GeneratedData {
+      events: vec![act("a"), act("b")],
+      generate_objects: vec![tree("oak", 0, 0)],
+      remove_objects: vec!["c".to_string()],
+    }
+  );
+}
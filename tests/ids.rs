@@ -0,0 +1,60 @@
+mod common;
+
+use common::*;
+use hakoniwa::{run, run_n};
+
+/// 毎回木を二本ずつ植える世界を`ticks`回進めた時のIDの集合
+#[cfg(not(feature = "wall-clock-ids"))]
+fn planted_ids(ticks: usize) -> Vec<String> {
+  let mut ctx = world([("a", tree("oak", 0, 0))]);
+  let sow = Act {
+    spawn: vec![tree("sapling", 1, 1), tree("sapling", 2, 2)],
+    ..act("a")
+  };
+  run_n(&mut ctx, &[emit(vec![sow])], ticks).unwrap();
+  ctx
+    .objects_sorted()
+    .into_iter()
+    .map(|(id, _)| id.clone())
+    .collect()
+}
+
+// 実時間から作るIDは実行ごとに変わるので、`wall-clock-ids`では比べられない
+#[cfg(not(feature = "wall-clock-ids"))]
+#[test]
+fn same_world_produces_same_ids() {
+  let first = planted_ids(3);
+  assert_eq!(first.len(), 7);
+  assert_eq!(first, planted_ids(3));
+}
+
+#[cfg(not(feature = "wall-clock-ids"))]
+#[test]
+fn ids_follow_the_documented_scheme() {
+  let ids = planted_ids(1);
+  assert!(ids.contains(&spawned_id(&tree("sapling", 1, 1), 1, 0)));
+  assert!(ids.contains(&spawned_id(&tree("sapling", 2, 2), 1, 1)));
+}
+
+#[test]
+fn identical_spawns_in_one_tick_get_distinct_ids() {
+  let mut ctx = world([("a", tree("oak", 0, 0))]);
+  let sow = Act {
+    spawn: vec![tree("sapling", 1, 1); 1000],
+    ..act("a")
+  };
+  run_n(&mut ctx, &[emit(vec![sow])], 1).unwrap();
+  assert_eq!(ctx.objects.len(), 1 + 1000);
+}
+
+#[test]
+fn event_ids_follow_actor_time_and_sequence() {
+  let mut ctx = world([("a", tree("oak", 0, 0)), ("b", tree("pine", 1, 1))]);
+  ctx.memory_capacity = None;
+  for _ in 0..2 {
+    run(&mut ctx, vec![emit(vec![act("a"), act("b")])]).unwrap();
+  }
+  let ids = ctx.memory.iter().map(|e| e.id.clone()).collect::<Vec<_>>();
+  let expected = ["a1#0", "b1#1", "a2#2", "b2#3"].map(|raw| base64::encode(raw.as_bytes()));
+  assert_eq!(ids, expected);
+}
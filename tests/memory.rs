@@ -0,0 +1,62 @@
+mod common;
+
+use common::*;
+use hakoniwa::run;
+
+/// 寿命`lifetime`のイベントを一つ起こしてから、何も起きない単位時間を`ticks`回進めた世界
+fn after_ticks(lifetime: u64, ticks: usize) -> World {
+  let mut ctx = world([("a", tree("oak", 0, 0))]);
+  let e = Act {
+    lifetime: Some(lifetime),
+    ..act("a")
+  };
+  run(&mut ctx, vec![emit(vec![e])]).unwrap();
+  for _ in 0..ticks {
+    run(&mut ctx, Vec::new()).unwrap();
+  }
+  ctx
+}
+
+#[test]
+fn event_survives_until_its_lifetime_has_passed() {
+  // 時刻1に起きた寿命3のイベントは時刻4まで覚えている
+  let ctx = after_ticks(3, 3);
+  assert_eq!(ctx.time, time(4));
+  assert_eq!(ctx.memory.len(), 1);
+}
+
+#[test]
+fn event_is_forgotten_after_its_lifetime() {
+  let ctx = after_ticks(3, 4);
+  assert_eq!(ctx.time, time(5));
+  assert!(ctx.memory.is_empty());
+}
+
+#[test]
+fn event_without_lifetime_is_never_forgotten() {
+  let mut ctx = world([("a", tree("oak", 0, 0))]);
+  run(&mut ctx, vec![emit(vec![act("a")])]).unwrap();
+  for _ in 0..20 {
+    run(&mut ctx, Vec::new()).unwrap();
+  }
+  assert_eq!(ctx.memory.len(), 1);
+}
+
+#[test]
+fn memory_capacity_keeps_only_the_newest_events() {
+  let mut ctx = world([("a", tree("oak", 0, 0))]);
+  ctx.memory_capacity = Some(10);
+  let mut remembered = Vec::new();
+  for i in 0..100 {
+    let e = Act {
+      priority: i,
+      ..act("a")
+    };
+    run(&mut ctx, vec![emit(vec![e])]).unwrap();
+    remembered.push(memory(&ctx).pop().unwrap());
+  }
+  assert_eq!(ctx.memory.len(), 10);
+  assert_eq!(memory(&ctx), remembered[90..]);
+  let priorities = ctx.memory.iter().map(|e| e.contents.priority);
+  assert!(priorities.eq(90..100));
+}
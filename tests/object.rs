@@ -0,0 +1,71 @@
+mod common;
+
+use common::*;
+use num_bigint::BigUint;
+
+#[test]
+fn age_of_object_generated_at_zero() {
+  let oak = object(tree("oak", 0, 0));
+  assert_eq!(oak.age(&time(500)), BigUint::from(500u32));
+  // 一年は`DAY * YEAR`単位時間
+  assert_eq!(oak.age_years(&time(500)), BigUint::from(500 / (DAY * YEAR)));
+  assert_eq!(oak.age_years(&time(99)), BigUint::from(0u32));
+}
+
+#[test]
+fn age_before_generation_is_zero() {
+  let mut oak = object(tree("oak", 0, 0));
+  oak.generated_time = time(500);
+  assert_eq!(oak.age(&time(100)), BigUint::from(0u32));
+}
+
+#[test]
+fn age_of_follows_the_world_time() {
+  let mut ctx = world([("a", tree("oak", 0, 0))]);
+  ctx.time = time(500);
+  assert_eq!(ctx.age_of(&ctx.objects["a"]), BigUint::from(500u32));
+}
+
+#[test]
+fn world_started_later_can_hold_an_aged_tree() {
+  let one_year = DAY * YEAR;
+  let mut old = object(tree("oak", 0, 0));
+  old.generated_time = time(10 * one_year);
+  old.last_modified = time(10 * one_year);
+  let mut ctx =
+    World::new(time(0), [("old".to_string(), old)]).with_start_time(BigUint::from(50 * one_year));
+  assert_eq!(ctx.time, time(50 * one_year));
+  assert_eq!(
+    ctx.objects["old"].age_years(&ctx.time),
+    BigUint::from(40u32)
+  );
+  hakoniwa::run(&mut ctx, vec![]).unwrap();
+  assert_eq!(
+    ctx.age_of(&ctx.objects["old"]),
+    BigUint::from(40 * one_year + 1)
+  );
+}
+
+#[test]
+fn counts_follow_spawns_and_removals() {
+  let mut ctx = world([
+    ("a", tree("桜", 0, 0)),
+    ("b", tree("桜", 1, 0)),
+    ("c", tree("松", 2, 0)),
+  ]);
+  assert_eq!(ctx.count_of_name("桜"), 2);
+  assert_eq!(ctx.count_of_name("松"), 1);
+  assert_eq!(ctx.count_of_name("梅"), 0);
+  let change = Act {
+    spawn: vec![tree("梅", 3, 0), tree("松", 4, 0)],
+    remove: vec!["a".to_string()],
+    ..act("c")
+  };
+  hakoniwa::run(&mut ctx, vec![emit(vec![change])]).unwrap();
+  let counts = ctx.count_by_name();
+  assert_eq!(counts.len(), 3);
+  assert_eq!(counts["桜"], 1);
+  assert_eq!(counts["松"], 2);
+  assert_eq!(counts["梅"], 1);
+  assert_eq!(ctx.count_of_name("松"), 2);
+}
@@ -0,0 +1,101 @@
+// 実時間から作るIDは実行ごとに変わるので、`wall-clock-ids`では比べられない
+#![cfg(all(feature = "rayon", not(feature = "wall-clock-ids")))]
+
+mod common;
+
+use common::*;
+use hakoniwa::{par_run, run, GeneratedData, Generater};
+
+/// generate関数ごとに独立した乱数で、木を動かしたり増やしたり弱らせたりするgenerate関数
+fn wander() -> Generater<Act, Tree> {
+  generator(move |ctx| {
+    let seed = ctx.generator_seed();
+    let mut events = Vec::new();
+    for (i, (id, object)) in ctx.objects_sorted().into_iter().enumerate() {
+      let r = seed.rotate_left(i as u32 % 64) ^ (i as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+      let (x, y) = (object.object_type.x, object.object_type.y);
+      let mut e = act(id);
+      match r % 4 {
+        0 => e.moves = Some((id.clone(), pt(x + r % 3, y + r / 3 % 3))),
+        1 => e.spawn = vec![tree("sapling", x + 1, y + seed % 5)],
+        2 => e.patch = Some((id.clone(), -1)),
+        _ => continue,
+      }
+      events.push(e);
+    }
+    GeneratedData {
+      events,
+      ..GeneratedData::empty()
+    }
+  })
+}
+
+fn forest() -> World {
+  let mut ctx = world([("a", tree("oak", 0, 0)), ("b", tree("pine", 5, 5))]);
+  ctx.seed = 42;
+  ctx
+}
+
+#[test]
+fn par_run_matches_run() {
+  let generators = (0..8).map(|_| wander()).collect::<Vec<_>>();
+  let mut serial = forest();
+  let mut parallel = forest();
+  for _ in 0..6 {
+    let a = run(&mut serial, generators.clone()).unwrap();
+    let b = par_run(&mut parallel, generators.clone()).unwrap();
+    assert_eq!(a, b);
+  }
+  assert_eq!(objects(&serial), objects(&parallel));
+  assert_eq!(memory(&serial), memory(&parallel));
+  assert!(serial.objects.len() > 2);
+}
+
+/// `threads`個のスレッドで`ticks`回`par_run`した後の状態のハッシュ値
+fn hash_with_threads(threads: usize, ticks: usize) -> u64 {
+  let pool = rayon::ThreadPoolBuilder::new()
+    .num_threads(threads)
+    .build()
+    .unwrap();
+  let generators = (0..8).map(|_| wander()).collect::<Vec<_>>();
+  let mut ctx = forest();
+  pool.install(|| {
+    for _ in 0..ticks {
+      par_run(&mut ctx, generators.clone()).unwrap();
+    }
+  });
+  ctx.state_hash()
+}
+
+#[test]
+fn state_hash_does_not_depend_on_thread_count() {
+  let single = hash_with_threads(1, 6);
+  assert_eq!(single, hash_with_threads(8, 6));
+  assert_eq!(single, hash_with_threads(8, 6));
+  assert_ne!(single, hash_with_threads(1, 5));
+}
+
+#[test]
+fn generators_get_distinct_seeds_from_their_position() {
+  let seeds = std::sync::Arc::new(std::sync::Mutex::new(vec![0; 4]));
+  let record = |i: usize| {
+    let seeds = seeds.clone();
+    generator(move |ctx| {
+      seeds.lock().unwrap()[i] = ctx.generator_seed();
+      GeneratedData::empty()
+    })
+  };
+  let generators = (0..4).map(record).collect::<Vec<_>>();
+  let mut ctx = forest();
+  par_run(&mut ctx, generators.clone()).unwrap();
+  let parallel = seeds.lock().unwrap().clone();
+  let mut ctx = forest();
+  run(&mut ctx, generators).unwrap();
+  let serial = seeds.lock().unwrap().clone();
+  assert_eq!(parallel, serial);
+  assert_eq!(serial[2], ctx.stream_seed(2));
+  let mut distinct = serial.clone();
+  distinct.sort();
+  distinct.dedup();
+  assert_eq!(distinct.len(), 4);
+}
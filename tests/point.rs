@@ -0,0 +1,48 @@
+use hakoniwa::Point;
+use num_bigint::BigUint;
+
+fn pt(x: u64, y: u64) -> Point {
+  Point::new(x.into(), y.into())
+}
+
+#[test]
+fn manhattan_distance_with_crossed_axes() {
+  // xは一方が大きく、yはもう一方が大きい
+  let a = pt(7, 2);
+  let b = pt(3, 10);
+  assert_eq!(a.manhattan_distance(&b), BigUint::from(12u32));
+  assert_eq!(b.manhattan_distance(&a), BigUint::from(12u32));
+}
+
+#[test]
+fn squared_euclidean_distance_with_crossed_axes() {
+  let a = pt(7, 2);
+  let b = pt(4, 6);
+  assert_eq!(a.squared_euclidean_distance(&b), BigUint::from(25u32));
+  assert_eq!(b.squared_euclidean_distance(&a), BigUint::from(25u32));
+}
+
+#[test]
+fn distances_to_self_are_zero() {
+  let a = pt(5, 5);
+  assert_eq!(a.manhattan_distance(&a), BigUint::from(0u32));
+  assert_eq!(a.squared_euclidean_distance(&a), BigUint::from(0u32));
+}
+
+#[test]
+fn u64_and_big_integer_points_agree() {
+  let small = (Point::<u64>::new(7, 2), Point::<u64>::new(3, 10));
+  let large = (pt(7, 2), pt(3, 10));
+  assert_eq!(
+    BigUint::from(small.0.manhattan_distance(&small.1)),
+    large.0.manhattan_distance(&large.1)
+  );
+  assert_eq!(
+    BigUint::from(small.0.squared_euclidean_distance(&small.1)),
+    large.0.squared_euclidean_distance(&large.1)
+  );
+  assert_eq!(
+    BigUint::from(small.0.euclidean_distance(&small.1)),
+    large.0.euclidean_distance(&large.1)
+  );
+}
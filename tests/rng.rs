@@ -0,0 +1,117 @@
+mod common;
+
+use common::*;
+use hakoniwa::{run, Context, GeneratedData, Generater};
+use rand::Rng;
+
+/// 乱数で選んだ地点に木を植え、乱数で選んだ木を動かすgenerate関数
+fn scatter() -> Generater<Act, Tree> {
+  generator(|ctx| {
+    let mut rng = ctx.generator_rng();
+    let mut ids = ctx.objects.keys().cloned().collect::<Vec<_>>();
+    ids.sort();
+    let id = ids[rng.gen_range(0..ids.len())].clone();
+    GeneratedData {
+      events: vec![Act {
+        moves: Some((id.clone(), pt(rng.gen_range(0..100), rng.gen_range(0..100)))),
+        ..act(&id)
+      }],
+      generate_objects: vec![tree(
+        "sapling",
+        rng.gen_range(0..100),
+        rng.gen_range(0..100),
+      )],
+      ..GeneratedData::empty()
+    }
+  })
+}
+
+/// 種`seed`の世界を`ticks`回進めた時に、単位時間ごとに生成された情報
+fn history(seed: u64, ticks: usize) -> Vec<Vec<GeneratedData<Act, Tree>>> {
+  let mut ctx = Context::new_seeded(
+    time(0),
+    [("a".to_string(), object(tree("oak", 0, 0)))],
+    seed,
+  );
+  let x = ctx.rng().gen_range(0..100);
+  ctx.objects.insert(
+    "b".to_string(),
+    std::sync::Arc::new(object(tree("oak", x, 0))),
+  );
+  ctx.rebuild_name_index();
+  (0..ticks)
+    .map(|_| run(&mut ctx, vec![scatter(), scatter()]).unwrap())
+    .collect()
+}
+
+// 実時間から作るIDは実行ごとに変わるので、`wall-clock-ids`では比べられない
+#[cfg(not(feature = "wall-clock-ids"))]
+#[test]
+fn same_seed_produces_same_generated_data() {
+  assert_eq!(history(265, 5), history(265, 5));
+}
+
+#[test]
+fn different_seeds_produce_different_generated_data() {
+  assert_ne!(history(265, 5), history(266, 5));
+}
+
+#[test]
+fn rng_follows_the_seed() {
+  let draw = |seed| {
+    let mut ctx: World = Context::new_seeded(time(0), [], seed);
+    (0..4).map(|_| ctx.rng().gen::<u64>()).collect::<Vec<_>>()
+  };
+  assert_eq!(draw(1), draw(1));
+  assert_ne!(draw(1), draw(2));
+}
+
+/// IDの順にオブジェクトを見て、乱数で選んだものに出来事を起こさせるgenerate関数
+#[cfg(not(feature = "wall-clock-ids"))]
+fn stir() -> Generater<Act, Tree> {
+  generator(|ctx| {
+    let mut rng = ctx.generator_rng();
+    let mut events = Vec::new();
+    for (id, object) in ctx.objects_sorted() {
+      if rng.gen_bool(0.5) {
+        events.push(Act {
+          spawn: vec![tree(
+            "sapling",
+            object.object_type.x + 1,
+            rng.gen_range(0..10),
+          )],
+          ..act(id)
+        });
+      }
+    }
+    GeneratedData {
+      events,
+      ..GeneratedData::empty()
+    }
+  })
+}
+
+/// `names`の順にオブジェクトを入れた、種`seed`の世界を進めた時に記憶されたイベント
+#[cfg(not(feature = "wall-clock-ids"))]
+fn stirred(names: &[&'static str], seed: u64) -> Vec<(String, Act)> {
+  let mut ctx = world(names.iter().map(|&name| (name, tree("oak", 0, 0))));
+  ctx.seed = seed;
+  ctx.memory_capacity = None;
+  for _ in 0..4 {
+    run(&mut ctx, vec![stir()]).unwrap();
+  }
+  memory(&ctx)
+}
+
+// 生まれた木のIDが実時間を含むので、`wall-clock-ids`では比べられない
+#[cfg(not(feature = "wall-clock-ids"))]
+#[test]
+fn sorted_iteration_reproduces_event_sequences() {
+  let names = ["a", "b", "c", "d", "e", "f", "g", "h"];
+  let mut reversed = names;
+  reversed.reverse();
+  let events = stirred(&names, 283);
+  assert!(!events.is_empty());
+  assert_eq!(events, stirred(&names, 283));
+  assert_eq!(events, stirred(&reversed, 283));
+}
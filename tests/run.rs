@@ -0,0 +1,176 @@
+mod common;
+
+use common::*;
+use hakoniwa::{run, run_n_with_hook, run_with_hook};
+
+#[test]
+fn no_op_patch_leaves_object_untouched() {
+  let mut ctx = world([("a", tree("oak", 0, 0))]);
+  let before = ctx.fork();
+  let rest = Act {
+    patch: Some(("a".to_string(), 0)),
+    ..act("a")
+  };
+  run(&mut ctx, vec![emit(vec![rest])]).unwrap();
+  assert_eq!(ctx.objects["a"].last_modified, time(0));
+  assert_eq!(before.diff(&ctx).count(), 0);
+  assert!(std::sync::Arc::ptr_eq(
+    &ctx.objects["a"],
+    &before.objects["a"]
+  ));
+}
+
+#[test]
+fn patch_that_changes_object_updates_last_modified() {
+  let mut ctx = world([("a", tree("oak", 0, 0))]);
+  let blight = Act {
+    patch: Some(("a".to_string(), -1)),
+    ..act("a")
+  };
+  run(&mut ctx, vec![emit(vec![blight])]).unwrap();
+  assert_eq!(ctx.objects["a"].last_modified, time(1));
+  assert_eq!(ctx.objects["a"].object_type.health, -1);
+}
+
+#[cfg(not(feature = "wall-clock-ids"))]
+#[test]
+fn removal_does_not_hit_object_spawned_in_the_same_tick() {
+  let mut ctx = world([("a", tree("oak", 0, 0))]);
+  let sapling = tree("sapling", 1, 1);
+  let fresh = spawned_id(&sapling, 1, 0);
+  let fell = Act {
+    remove: vec![fresh.clone()],
+    ..act("a")
+  };
+  let sow = Act {
+    spawn: vec![sapling],
+    ..act("a")
+  };
+  run(&mut ctx, vec![emit(vec![fell]), emit(vec![sow])]).unwrap();
+  assert!(ctx.objects.contains_key(&fresh));
+  assert_eq!(
+    ctx.run_warnings,
+    vec![hakoniwa::RunError::RemoveTargetMissing { id: fresh }]
+  );
+}
+
+#[cfg(not(feature = "wall-clock-ids"))]
+#[test]
+fn move_applies_to_object_spawned_in_the_same_tick() {
+  let mut ctx = world([("a", tree("oak", 0, 0))]);
+  let sapling = tree("sapling", 1, 1);
+  let fresh = spawned_id(&sapling, 1, 0);
+  let sow = Act {
+    spawn: vec![sapling],
+    ..act("a")
+  };
+  let carry = Act {
+    moves: Some((fresh.clone(), pt(4, 4))),
+    ..act("a")
+  };
+  run(&mut ctx, vec![emit(vec![sow, carry])]).unwrap();
+  assert_eq!(ctx.objects[&fresh].point, pt(4, 4));
+  assert!(ctx.run_warnings.is_empty());
+}
+
+// 実時間から作るIDは実行ごとに変わるので、`wall-clock-ids`では比べられない
+#[cfg(not(feature = "wall-clock-ids"))]
+#[test]
+fn run_n_matches_successive_runs() {
+  let mut ctx = world([("a", tree("oak", 0, 0))]);
+  let grow = Act {
+    spawn: vec![tree("sapling", 1, 1)],
+    lifetime: Some(1),
+    ..act("a")
+  };
+  let generators = vec![emit(vec![grow])];
+  let mut stepped = ctx.fork();
+  let expected = (0..3)
+    .map(|_| run(&mut stepped, generators.clone()).unwrap())
+    .collect::<Vec<_>>();
+  assert_eq!(hakoniwa::run_n(&mut ctx, &generators, 3).unwrap(), expected);
+  assert_eq!(objects(&ctx), objects(&stepped));
+  assert_eq!(memory(&ctx), memory(&stepped));
+  assert_eq!(ctx.state_hash(), stepped.state_hash());
+  assert_eq!(ctx.objects.len(), 4);
+}
+
+#[test]
+fn one_event_spawns_every_child() {
+  let mut ctx = world([("a", tree("oak", 0, 0))]);
+  let children = (1..=5).map(|i| tree("seed", i, i)).collect::<Vec<_>>();
+  let fruit = Act {
+    spawn: children.clone(),
+    ..act("a")
+  };
+  run(&mut ctx, vec![emit(vec![fruit])]).unwrap();
+  assert_eq!(ctx.objects.len(), 1 + 5);
+  let mut spawned = objects(&ctx)
+    .into_iter()
+    .map(|(_, _, tree)| tree)
+    .filter(|tree| tree.name == "seed")
+    .collect::<Vec<_>>();
+  spawned.sort_by_key(|tree| tree.x);
+  assert_eq!(spawned, children);
+}
+
+#[test]
+fn one_event_removes_every_target() {
+  let mut ctx = world([
+    ("a", tree("oak", 0, 0)),
+    ("b", tree("oak", 1, 0)),
+    ("c", tree("oak", 2, 0)),
+    ("d", tree("oak", 3, 0)),
+  ]);
+  let fire = Act {
+    remove: vec!["b".to_string(), "c".to_string(), "d".to_string()],
+    ..act("a")
+  };
+  run(&mut ctx, vec![emit(vec![fire])]).unwrap();
+  assert_eq!(ctx.time, time(1));
+  assert!(ctx.run_warnings.is_empty());
+  let ids = objects(&ctx)
+    .into_iter()
+    .map(|(id, _, _)| id)
+    .collect::<Vec<_>>();
+  assert_eq!(ids, vec!["a".to_string()]);
+}
+
+#[test]
+fn hook_sees_the_updated_world() {
+  let mut ctx = world([("a", tree("oak", 0, 0))]);
+  let sow = Act {
+    spawn: vec![tree("sapling", 1, 1)],
+    ..act("a")
+  };
+  let mut seen = Vec::new();
+  run_with_hook(&mut ctx, vec![emit(vec![sow.clone()])], |ctx, data| {
+    seen.push((ctx.time.clone(), ctx.objects.len(), data.len()));
+  })
+  .unwrap();
+  assert_eq!(seen, vec![(time(1), 2, 1)]);
+  run_n_with_hook(&mut ctx, &[emit(vec![sow])], 2, |ctx, data| {
+    seen.push((ctx.time.clone(), ctx.objects.len(), data.len()));
+  })
+  .unwrap();
+  assert_eq!(
+    seen,
+    vec![(time(1), 2, 1), (time(2), 3, 1), (time(3), 4, 1)]
+  );
+}
+
+#[test]
+fn patches_persist_across_ticks() {
+  let mut ctx = world([("a", tree("oak", 0, 0))]);
+  let feed = |amount| Act {
+    patch: Some(("a".to_string(), amount)),
+    ..act("a")
+  };
+  run(&mut ctx, vec![emit(vec![feed(3), feed(4)])]).unwrap();
+  assert_eq!(ctx.objects["a"].object_type.health, 7);
+  run(&mut ctx, vec![]).unwrap();
+  assert_eq!(ctx.objects["a"].object_type.health, 7);
+  run(&mut ctx, vec![emit(vec![feed(-2)])]).unwrap();
+  assert_eq!(ctx.objects["a"].object_type.health, 5);
+  assert_eq!(ctx.objects["a"].point, pt(0, 0));
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::*;
+use hakoniwa::{ObjectChange, PointDelta, SnapshotDiff};
+
+#[test]
+fn failed_snapshot_diff_leaves_context_unchanged() {
+  let mut ctx = world([("a", tree("oak", 0, 0)), ("b", tree("pine", 5, 5))]);
+  let before = objects(&ctx);
+  let diff = SnapshotDiff {
+    time: time(3),
+    added: vec![("c".to_string(), object(tree("elm", 1, 1)))],
+    removed: vec!["b".to_string()],
+    moved: vec![
+      ("a".to_string(), PointDelta::between(&pt(0, 0), &pt(2, 2))),
+      (
+        "missing".to_string(),
+        PointDelta::between(&pt(0, 0), &pt(1, 1)),
+      ),
+    ],
+    updated: Vec::new(),
+    reparented: Vec::new(),
+  };
+  assert_eq!(ctx.apply_snapshot_diff(&diff), Err("missing".to_string()));
+  assert_eq!(objects(&ctx), before);
+  assert_eq!(ctx.time, time(0));
+  assert_eq!(ctx.objects_at(&pt(0, 0)).len(), 1);
+  assert!(ctx.objects_at(&pt(2, 2)).is_empty());
+}
+
+#[test]
+fn snapshot_diff_restores_next_snapshot() {
+  let base = world([("a", tree("oak", 0, 0)), ("b", tree("pine", 5, 5))]);
+  let mut next = base.fork();
+  next.time = time(3);
+  next.objects.remove("b");
+  let mut ctx = base.fork();
+  ctx
+    .apply_snapshot_diff(&SnapshotDiff::between(&base, &next))
+    .unwrap();
+  assert_eq!(objects(&ctx), objects(&next));
+  assert_eq!(ctx.time, time(3));
+}
+
+#[test]
+fn snapshot_diff_carries_parent_frame_changes() {
+  let base = world([("a", tree("oak", 2, 2)), ("b", tree("pine", 3, 3))]);
+  let mut next = base.fork();
+  next.time = time(3);
+  next.set_parent_frame("b", Some("a")).unwrap();
+  let changes = base.diff(&next).collect::<Vec<_>>();
+  assert_eq!(
+    changes,
+    vec![ObjectChange::Reparented {
+      id: "b",
+      from: None,
+      to: Some("a"),
+    }]
+  );
+  let diff = SnapshotDiff::between(&base, &next);
+  assert_eq!(
+    diff.reparented,
+    vec![("b".to_string(), Some("a".to_string()))]
+  );
+  let mut ctx = base.fork();
+  ctx.apply_snapshot_diff(&diff).unwrap();
+  assert_eq!(ctx.world_point("b"), Some(pt(5, 5)));
+  assert_eq!(ctx.world_point("b"), next.world_point("b"));
+  assert_eq!(ctx.diff(&next).count(), 0);
+}
@@ -0,0 +1,168 @@
+mod common;
+
+use common::*;
+use hakoniwa::run;
+use hakoniwa::ObjectType;
+use num_bigint::BigUint;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::Arc;
+
+/// 中心`(10, 10)`から半径`5`の境界の内外に置いた世界
+fn ring() -> World {
+  world([
+    ("center", tree("oak", 10, 10)),
+    ("edge", tree("oak", 13, 14)),
+    ("inside", tree("oak", 14, 12)),
+    ("outside", tree("oak", 14, 14)),
+    ("far", tree("oak", 16, 10)),
+  ])
+}
+
+fn ids_within(ctx: &World, center: &hakoniwa::Point, radius: u32) -> Vec<String> {
+  ctx
+    .objects_within(center, &BigUint::from(radius))
+    .into_iter()
+    .map(|(id, _)| id.clone())
+    .collect()
+}
+
+#[test]
+fn objects_within_includes_the_boundary() {
+  let ctx = ring();
+  assert_eq!(
+    ids_within(&ctx, &pt(10, 10), 5),
+    vec!["center", "edge", "inside"]
+  );
+}
+
+#[test]
+fn objects_within_is_the_same_with_an_index() {
+  let mut ctx = ring();
+  ctx.set_spatial_index(Some(BigUint::from(3u32)));
+  assert_eq!(
+    ids_within(&ctx, &pt(10, 10), 5),
+    vec!["center", "edge", "inside"]
+  );
+}
+
+#[test]
+fn index_with_a_radius_far_larger_than_its_cells_stays_fast() {
+  let mut ctx = ring();
+  ctx.objects.insert(
+    "distant".to_string(),
+    Arc::new(object(tree("oak", 1 << 40, 1 << 40))),
+  );
+  ctx.set_spatial_index(Some(BigUint::from(1u32)));
+  // 矩形のセルを一つずつ調べると約`6.4e19`個になり終わらない
+  assert_eq!(
+    ids_within(&ctx, &pt(10, 10), 4_000_000_000),
+    vec!["center", "edge", "far", "inside", "outside"]
+  );
+}
+
+/// 全てのオブジェクトを調べて求めた、`center`から`radius`以内のオブジェクトのID
+fn brute_force(ctx: &World, center: &hakoniwa::Point, radius: u32) -> Vec<String> {
+  let squared_radius = BigUint::from(radius) * radius;
+  let mut ids = ctx
+    .objects
+    .iter()
+    .filter(|(_, object)| object.point.squared_euclidean_distance(center) <= squared_radius)
+    .map(|(id, _)| id.clone())
+    .collect::<Vec<_>>();
+  ids.sort();
+  ids
+}
+
+#[test]
+fn grid_matches_brute_force_through_moves_spawns_and_removals() {
+  let mut rng = StdRng::seed_from_u64(263);
+  let mut ctx = world([]);
+  for i in 0..2000 {
+    let (x, y) = (rng.gen_range(0..500), rng.gen_range(0..500));
+    ctx
+      .objects
+      .insert(format!("t{i}"), Arc::new(object(tree("oak", x, y))));
+  }
+  ctx.set_spatial_index(Some(BigUint::from(16u32)));
+  for _ in 0..20 {
+    let ids = ctx.objects.keys().cloned().collect::<Vec<_>>();
+    let mut events = Vec::new();
+    for _ in 0..100 {
+      let id = &ids[rng.gen_range(0..ids.len())];
+      let to = pt(rng.gen_range(0..500), rng.gen_range(0..500));
+      events.push(Act {
+        moves: Some((id.clone(), to)),
+        ..act(id)
+      });
+    }
+    for _ in 0..20 {
+      let id = &ids[rng.gen_range(0..ids.len())];
+      events.push(Act {
+        remove: vec![id.clone()],
+        spawn: vec![tree(
+          "sapling",
+          rng.gen_range(0..500),
+          rng.gen_range(0..500),
+        )],
+        ..act(id)
+      });
+    }
+    run(&mut ctx, vec![emit(events)]).unwrap();
+    for radius in [0, 7, 16, 50, 130] {
+      let center = pt(rng.gen_range(0..500), rng.gen_range(0..500));
+      assert_eq!(
+        ids_within(&ctx, &center, radius),
+        brute_force(&ctx, &center, radius)
+      );
+    }
+  }
+  assert!(ctx
+    .objects
+    .values()
+    .any(|object| object.object_type.name == "sapling"));
+  assert!(ctx
+    .objects
+    .values()
+    .any(|object| object.point != object.object_type.generated_point()));
+}
+
+#[test]
+fn objects_at_returns_exactly_the_stack() {
+  let mut ctx = world([
+    ("a", tree("oak", 4, 4)),
+    ("b", tree("pine", 4, 4)),
+    ("c", tree("oak", 4, 4)),
+    ("d", tree("oak", 4, 5)),
+  ]);
+  let at = |ctx: &World| {
+    ctx
+      .objects_at(&pt(4, 4))
+      .into_iter()
+      .map(|(id, _)| id.clone())
+      .collect::<Vec<_>>()
+  };
+  assert_eq!(at(&ctx), vec!["a", "b", "c"]);
+  ctx.set_spatial_index(Some(BigUint::from(3u32)));
+  assert_eq!(at(&ctx), vec!["a", "b", "c"]);
+  assert!(ctx.objects_at(&pt(5, 4)).is_empty());
+}
+
+#[test]
+fn remove_in_rect_keeps_the_index_in_step() {
+  let mut plain = ring();
+  plain.rebuild_name_index();
+  let mut indexed = plain.fork();
+  indexed.set_spatial_index(Some(BigUint::from(3u32)));
+  let removed = vec!["edge", "inside", "outside"];
+  assert_eq!(plain.remove_in_rect(&pt(13, 12), &pt(14, 14)), removed);
+  assert_eq!(indexed.remove_in_rect(&pt(13, 12), &pt(14, 14)), removed);
+  assert_eq!(objects(&indexed), objects(&plain));
+  assert_eq!(indexed.count_of_name("oak"), 2);
+  assert_eq!(
+    ids_within(&indexed, &pt(10, 10), 100),
+    vec!["center", "far"]
+  );
+  assert!(indexed.objects_at(&pt(14, 14)).is_empty());
+  assert!(indexed.remove_in_rect(&pt(13, 12), &pt(14, 14)).is_empty());
+}
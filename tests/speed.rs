@@ -0,0 +1,62 @@
+mod common;
+
+use common::*;
+use hakoniwa::{run, MoveLimit, RunError};
+use num_bigint::BigUint;
+
+/// 一回に`5`まで動ける木を原点から`to`へ動かした世界
+fn walked(limit: MoveLimit, to: hakoniwa::Point) -> World {
+  let walker = Tree {
+    speed: Some(5),
+    ..tree("walker", 0, 0)
+  };
+  let mut ctx = world([("a", walker)]);
+  ctx.move_limit = limit;
+  let step = Act {
+    moves: Some(("a".to_string(), to)),
+    ..act("a")
+  };
+  run(&mut ctx, vec![emit(vec![step])]).unwrap();
+  ctx
+}
+
+#[test]
+fn move_within_the_limit_is_allowed() {
+  for limit in [MoveLimit::Reject, MoveLimit::Clamp] {
+    let ctx = walked(limit, pt(3, 4));
+    assert_eq!(ctx.objects["a"].point, pt(3, 4));
+    assert!(ctx.run_warnings.is_empty());
+  }
+}
+
+#[test]
+fn move_beyond_the_limit_is_clamped() {
+  let ctx = walked(MoveLimit::Clamp, pt(30, 40));
+  assert_eq!(ctx.objects["a"].point, pt(3, 4));
+  assert!(ctx.run_warnings.is_empty());
+}
+
+#[test]
+fn move_beyond_the_limit_is_rejected() {
+  let ctx = walked(MoveLimit::Reject, pt(30, 40));
+  assert_eq!(ctx.objects["a"].point, pt(0, 0));
+  assert_eq!(
+    ctx.run_warnings,
+    vec![RunError::MoveTooFar {
+      id: "a".to_string(),
+      distance: BigUint::from(50u32),
+      limit: BigUint::from(5u32),
+    }]
+  );
+}
+
+#[test]
+fn move_without_a_limit_is_not_checked() {
+  let mut ctx = world([("a", tree("oak", 0, 0))]);
+  let leap = Act {
+    moves: Some(("a".to_string(), pt(300, 400))),
+    ..act("a")
+  };
+  run(&mut ctx, vec![emit(vec![leap])]).unwrap();
+  assert_eq!(ctx.objects["a"].point, pt(300, 400));
+}
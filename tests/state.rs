@@ -0,0 +1,114 @@
+#![cfg(feature = "serde")]
+
+mod common;
+
+use common::*;
+use hakoniwa::{
+  run_n, EventOrder, GeneratedData, Generater, MoveLimit, PopulationController, SpawnPlacement,
+  Torus,
+};
+use num_bigint::BigUint;
+use rand::Rng;
+
+/// 木を少しずつ動かし、個体数の補正係数に応じて苗木を植えるgenerate関数
+fn wander_and_sow() -> Generater<Act, Tree> {
+  generator(|ctx| {
+    let mut rng = ctx.generator_rng();
+    let mut ids = ctx.objects.keys().cloned().collect::<Vec<_>>();
+    ids.sort();
+    let mut events = ids
+      .iter()
+      .map(|id| {
+        let point = &ctx.objects[id].point;
+        let step = |n: &BigUint, d: u64| n + d;
+        Act {
+          moves: Some((
+            id.clone(),
+            hakoniwa::Point::new(
+              step(point.x(), rng.gen_range(0..3)),
+              step(point.y(), rng.gen_range(0..3)),
+            ),
+          )),
+          lifetime: Some(2),
+          priority: rng.gen_range(0..3),
+          ..act(id)
+        }
+      })
+      .collect::<Vec<_>>();
+    if rng.gen_bool(ctx.population_factor().min(1.0)) {
+      events.push(Act {
+        spawn: vec![tree("sapling", rng.gen_range(0..20), rng.gen_range(0..20))],
+        ..act(&ids[0])
+      });
+    }
+    GeneratedData {
+      events,
+      ..GeneratedData::empty()
+    }
+  })
+}
+
+/// 全ての設定を既定から変えた世界
+fn configured() -> World {
+  let mut ctx = world([
+    ("a", tree("oak", 1, 1)),
+    ("b", tree("oak", 5, 5)),
+    ("c", tree("pine", 9, 2)),
+  ]);
+  ctx.seed = 272;
+  ctx.memory_capacity = Some(6);
+  ctx.set_torus(Some(Torus::new(20u32.into(), 20u32.into())));
+  ctx.set_spatial_index(Some(4u32.into()));
+  ctx.spawn_placement = Some(SpawnPlacement { max_distance: 2 });
+  ctx.record_spawn_failures = true;
+  ctx.event_order = EventOrder::Priority;
+  ctx.move_limit = MoveLimit::Clamp;
+  ctx.population_controller = Some(PopulationController::new(8, 0.5, 0.1, 0.0));
+  ctx
+}
+
+#[test]
+fn json_round_trip_restores_the_state() {
+  let mut ctx = configured();
+  run_n(&mut ctx, &[wander_and_sow()], 5).unwrap();
+  let json = serde_json::to_string(&ctx).unwrap();
+  let restored: World = serde_json::from_str(&json).unwrap();
+  assert_eq!(restored.state_hash(), ctx.state_hash());
+  assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+}
+
+// 実時間から作るIDは実行ごとに変わるので、`wall-clock-ids`では進めた後を比べられない
+#[cfg(not(feature = "wall-clock-ids"))]
+#[test]
+fn json_round_trip_keeps_running_identically() {
+  let mut ctx = configured();
+  run_n(&mut ctx, &[wander_and_sow()], 5).unwrap();
+  let json = serde_json::to_string(&ctx).unwrap();
+  let mut restored: World = serde_json::from_str(&json).unwrap();
+  run_n(&mut ctx, &[wander_and_sow()], 10).unwrap();
+  run_n(&mut restored, &[wander_and_sow()], 10).unwrap();
+  assert_eq!(restored.state_hash(), ctx.state_hash());
+  assert_eq!(objects(&restored), objects(&ctx));
+}
+
+#[test]
+fn load_state_restores_the_configuration() {
+  let ctx = configured();
+  let mut plain = world([]);
+  plain.load_state(ctx.save_state());
+  assert_eq!(plain.seed, 272);
+  assert_eq!(plain.memory_capacity, Some(6));
+  assert_eq!(plain.torus(), ctx.torus());
+  assert_eq!(plain.spawn_placement, ctx.spawn_placement);
+  assert!(plain.record_spawn_failures);
+  assert_eq!(plain.event_order, EventOrder::Priority);
+  assert_eq!(plain.move_limit, MoveLimit::Clamp);
+  assert_eq!(
+    plain
+      .population_controller
+      .as_ref()
+      .map(|controller| controller.target),
+    Some(8)
+  );
+  assert_eq!(plain.objects_within(&pt(19, 19), &3u32.into()).len(), 1);
+}
@@ -0,0 +1,45 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use common::*;
+use hakoniwa::{run, try_run};
+use tokio::sync::broadcast::error::TryRecvError;
+
+#[test]
+fn subscribers_receive_committed_events() {
+  let mut ctx = world([("a", tree("oak", 0, 0)), ("b", tree("pine", 5, 5))]);
+  let mut all = ctx.subscribe(16, None);
+  let mut only_b = ctx.subscribe(16, Some(Box::new(|e: &Act| e.actor == "b")));
+  run(&mut ctx, vec![emit(vec![act("a"), act("b")])]).unwrap();
+  assert_eq!(all.try_recv().unwrap().contents.actor, "a");
+  assert_eq!(all.try_recv().unwrap().contents.actor, "b");
+  assert_eq!(all.try_recv().unwrap_err(), TryRecvError::Empty);
+  assert_eq!(only_b.try_recv().unwrap().contents.actor, "b");
+  assert_eq!(only_b.try_recv().unwrap_err(), TryRecvError::Empty);
+}
+
+#[test]
+fn rolled_back_ticks_are_not_published() {
+  let mut ctx = world([("a", tree("oak", 0, 0))]);
+  let mut receiver = ctx.subscribe(16, None);
+  let broken = Act {
+    remove: vec!["missing".to_string()],
+    ..act("a")
+  };
+  assert!(try_run(&mut ctx, vec![emit(vec![broken])]).is_err());
+  assert_eq!(receiver.try_recv().unwrap_err(), TryRecvError::Empty);
+  try_run(&mut ctx, vec![emit(vec![act("a")])]).unwrap();
+  assert_eq!(receiver.try_recv().unwrap().contents.actor, "a");
+}
+
+#[test]
+fn slow_subscriber_is_told_how_many_events_it_missed() {
+  let mut ctx = world([("a", tree("oak", 0, 0))]);
+  let mut receiver = ctx.subscribe(1, None);
+  run(&mut ctx, vec![emit(vec![act("a"), act("a"), act("a")])]).unwrap();
+  assert_eq!(receiver.try_recv().unwrap_err(), TryRecvError::Lagged(2));
+  assert_eq!(receiver.try_recv().unwrap().sequence, 2);
+  drop(ctx);
+  assert_eq!(receiver.try_recv().unwrap_err(), TryRecvError::Closed);
+}
@@ -0,0 +1,159 @@
+use hakoniwa::Time;
+use num_bigint::BigUint;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+#[test]
+fn remainder_day_counts_days_into_the_year() {
+  let time = Time::new(
+    BigUint::from(400u32),
+    BigUint::from(10u32),
+    BigUint::from(30u32),
+  );
+  assert_eq!(*time.day(), BigUint::from(40u32));
+  assert_eq!(*time.year(), BigUint::from(1u32));
+  assert_eq!(*time.day_of_year(), BigUint::from(10u32));
+}
+
+#[test]
+fn constructor_agrees_with_counting_up() {
+  let mut time = Time::zero(BigUint::from(10u32), BigUint::from(30u32));
+  for _ in 0..400 {
+    time.plus_one();
+  }
+  assert_eq!(
+    time,
+    Time::new(
+      BigUint::from(400u32),
+      BigUint::from(10u32),
+      BigUint::from(30u32),
+    )
+  );
+}
+
+fn at(all: u32) -> Time {
+  Time::new(
+    BigUint::from(all),
+    BigUint::from(10u32),
+    BigUint::from(30u32),
+  )
+}
+
+#[test]
+fn duration_since_equal_time_is_zero() {
+  assert_eq!(at(40).duration_since(&at(40)), Some(BigUint::from(0u32)));
+  assert_eq!(at(40).saturating_sub(&at(40)), BigUint::from(0u32));
+}
+
+#[test]
+fn duration_since_earlier_time() {
+  assert_eq!(at(40).duration_since(&at(15)), Some(BigUint::from(25u32)));
+  assert_eq!(at(40).saturating_sub(&at(15)), BigUint::from(25u32));
+}
+
+#[test]
+fn duration_since_later_time() {
+  assert_eq!(at(15).duration_since(&at(40)), None);
+  assert_eq!(at(15).saturating_sub(&at(40)), BigUint::from(0u32));
+}
+
+#[test]
+fn zero_starts_at_the_beginning() {
+  let time = Time::zero(BigUint::from(24u32), BigUint::from(365u32));
+  assert_eq!(*time.all(), BigUint::from(0u32));
+  assert_eq!(*time.day(), BigUint::from(0u32));
+  assert_eq!(*time.year(), BigUint::from(0u32));
+  assert_eq!(
+    time,
+    Time::new(
+      BigUint::from(0u32),
+      BigUint::from(24u32),
+      BigUint::from(365u32)
+    )
+  );
+}
+
+#[test]
+fn display_uses_the_calendar_of_the_time() {
+  assert_eq!(at(437).to_string(), "Year 1, Day 13, 7/10");
+  let hours = Time::new(
+    BigUint::from(24u32 * 365 * 3 + 24 * 12 + 7),
+    BigUint::from(24u32),
+    BigUint::from(365u32),
+  );
+  assert_eq!(hours.to_string(), "Year 3, Day 12, 7/24");
+  assert_eq!(Time::<u64>::new(5, 3, 2).to_string(), "Year 0, Day 1, 2/3");
+}
+
+#[test]
+fn u64_and_big_integer_calendars_agree() {
+  let big = |n: u64| BigUint::from(n);
+  for (day, year) in [(24, 365), (10, 30), (1, 1), (7, 3)] {
+    let mut small = Time::<u64>::zero(day, year);
+    let mut large = Time::zero(big(day), big(year));
+    for all in [0, 1, 23, 24, 365 * 24, 400, 12_345, 1_000_003] {
+      let fixed = Time::<u64>::new(all, day, year);
+      let arbitrary = Time::new(big(all), big(day), big(year));
+      for level in 0..fixed.levels() {
+        assert_eq!(fixed.unit(level).map(big), arbitrary.unit(level));
+      }
+      assert_eq!(big(*fixed.day()), *arbitrary.day());
+    }
+    for _ in 0..500 {
+      small.plus_one();
+      large.plus_one();
+    }
+    small.plus(1_000);
+    large.plus(big(1_000));
+    assert_eq!(small.to_string(), large.to_string());
+    assert_eq!(big(*small.year()), *large.year());
+  }
+}
+
+#[test]
+fn minus_rewinds_to_the_same_calendar() {
+  let mut time = at(437);
+  time.minus(BigUint::from(37u32));
+  assert_eq!(time, at(400));
+  assert_eq!(time.checked_minus(BigUint::from(400u32)), Some(()));
+  assert_eq!(time, at(0));
+}
+
+#[test]
+fn checked_minus_refuses_to_go_below_zero() {
+  let mut time = at(15);
+  assert_eq!(time.checked_minus(BigUint::from(16u32)), None);
+  assert_eq!(time, at(15));
+}
+
+#[test]
+#[should_panic]
+fn minus_below_zero_panics() {
+  at(15).minus(BigUint::from(16u32));
+}
+
+/// 経過した単位時間と日数、年数などの関係が崩れていないかを確かめる
+fn assert_invariants(time: &Time<u64>, one_day: u64, one_year: u64) {
+  assert_eq!(*time.day(), time.all() / one_day);
+  assert_eq!(*time.time_of_day(), time.all() % one_day);
+  assert_eq!(*time.year(), time.day() / one_year);
+  assert_eq!(*time.day_of_year(), time.day() % one_year);
+  assert!(time.is_consistent());
+}
+
+#[test]
+fn change_rule_keeps_the_calendar_invariants() {
+  let mut rng = StdRng::seed_from_u64(293);
+  for _ in 0..2_000 {
+    let all = rng.gen_range(0..10_000_000);
+    let mut time = Time::<u64>::new(all, rng.gen_range(1..100), rng.gen_range(1..400));
+    time.plus(rng.gen_range(0..100_000));
+    let (one_day, one_year) = (rng.gen_range(1..100), rng.gen_range(1..400));
+    let before = *time.all();
+    time.change_rule(one_day, one_year);
+    assert_eq!(*time.all(), before);
+    assert_invariants(&time, one_day, one_year);
+    time.plus(rng.gen_range(0..100_000));
+    assert_invariants(&time, one_day, one_year);
+  }
+}
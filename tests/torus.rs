@@ -0,0 +1,119 @@
+mod common;
+
+use common::*;
+use hakoniwa::{run, SpawnPlacement, Torus};
+use num_bigint::BigUint;
+
+/// 一辺`size`の、端と端がつながった世界
+fn wrapped(size: u32, trees: impl IntoIterator<Item = (&'static str, Tree)>) -> World {
+  let mut ctx = world(trees);
+  ctx.set_torus(Some(Torus::new(size.into(), size.into())));
+  ctx
+}
+
+fn ids(found: Vec<(&String, &hakoniwa::Object<Tree>)>) -> Vec<String> {
+  found.into_iter().map(|(id, _)| id.clone()).collect()
+}
+
+#[test]
+fn objects_near_opposite_edges_are_neighbors_only_when_wrapped() {
+  let trees = [("east", tree("oak", 99, 50)), ("west", tree("oak", 0, 50))];
+  let radius = BigUint::from(2u32);
+  let unbounded = world(trees.clone());
+  assert_eq!(
+    ids(unbounded.objects_within(&pt(0, 50), &radius)),
+    vec!["west"]
+  );
+  let mut torus = wrapped(100, trees);
+  assert_eq!(
+    ids(torus.objects_within(&pt(0, 50), &radius)),
+    vec!["east", "west"]
+  );
+  torus.set_spatial_index(Some(BigUint::from(8u32)));
+  assert_eq!(
+    ids(torus.objects_within(&pt(0, 50), &radius)),
+    vec!["east", "west"]
+  );
+}
+
+#[test]
+fn moves_out_of_range_wrap() {
+  let mut ctx = wrapped(10, [("a", tree("oak", 9, 9))]);
+  let leap = Act {
+    moves: Some(("a".to_string(), pt(12, 25))),
+    ..act("a")
+  };
+  run(&mut ctx, vec![emit(vec![leap])]).unwrap();
+  assert_eq!(ctx.objects["a"].point, pt(2, 5));
+}
+
+#[test]
+fn components_join_across_the_edge() {
+  let trees = [
+    ("a", tree("oak", 0, 5)),
+    ("b", tree("oak", 9, 5)),
+    ("c", tree("oak", 5, 5)),
+  ];
+  let adjacency = BigUint::from(1u32);
+  assert_eq!(
+    world(trees.clone()).connected_components(&adjacency),
+    vec![vec!["a"], vec!["b"], vec!["c"]]
+  );
+  assert_eq!(
+    wrapped(10, trees).connected_components(&adjacency),
+    vec![vec!["a", "b"], vec!["c"]]
+  );
+}
+
+#[test]
+fn placement_wraps_at_both_edges() {
+  // 生成先`(9, 9)`の周りで、端をまたいだ`(0, 8)`を含めて先に調べるセルを埋めておく
+  let mut ctx = wrapped(
+    10,
+    [
+      ("a", tree("oak", 9, 9)),
+      ("b", tree("oak", 8, 8)),
+      ("c", tree("oak", 9, 8)),
+      ("d", tree("oak", 0, 8)),
+    ],
+  );
+  ctx.spawn_placement = Some(SpawnPlacement { max_distance: 1 });
+  let sow = Act {
+    spawn: vec![tree("sapling", 9, 9)],
+    ..act("a")
+  };
+  run(&mut ctx, vec![emit(vec![sow])]).unwrap();
+  let sapling = objects(&ctx)
+    .into_iter()
+    .find(|(_, _, tree)| tree.name == "sapling")
+    .unwrap();
+  assert_eq!(sapling.1, pt(8, 9));
+}
+
+#[test]
+fn placement_wraps_below_zero() {
+  let mut ctx = wrapped(10, [("a", tree("oak", 0, 0))]);
+  ctx.spawn_placement = Some(SpawnPlacement { max_distance: 1 });
+  let sow = Act {
+    spawn: vec![tree("sapling", 0, 0)],
+    ..act("a")
+  };
+  run(&mut ctx, vec![emit(vec![sow])]).unwrap();
+  let sapling = objects(&ctx)
+    .into_iter()
+    .find(|(_, _, tree)| tree.name == "sapling")
+    .unwrap();
+  assert_eq!(sapling.1, pt(9, 9));
+}
+
+#[test]
+fn line_of_sight_follows_the_wrapped_segment() {
+  let trees = [("edge", tree("oak", 9, 5)), ("middle", tree("oak", 4, 5))];
+  let (a, b) = (pt(1, 5), pt(8, 5));
+  let unbounded = world(trees.clone());
+  assert!(!unbounded.has_line_of_sight(&a, &b));
+  let mut torus = wrapped(10, trees);
+  assert!(!torus.has_line_of_sight(&a, &b));
+  torus.remove_object("edge").unwrap();
+  assert!(torus.has_line_of_sight(&a, &b));
+}
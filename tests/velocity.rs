@@ -0,0 +1,68 @@
+mod common;
+
+use common::*;
+use hakoniwa::{run, Point, Torus};
+use num_bigint::BigUint;
+
+fn drifting(vx: i64, vy: i64) -> Tree {
+  Tree {
+    velocity: Some((vx, vy)),
+    ..tree("seed", 5, 5)
+  }
+}
+
+#[test]
+fn velocity_moves_object_every_tick() {
+  let mut ctx = world([("a", drifting(2, -1)), ("b", tree("oak", 5, 5))]);
+  for _ in 0..3 {
+    run(&mut ctx, vec![]).unwrap();
+  }
+  assert_eq!(ctx.objects["a"].point, pt(11, 2));
+  assert_eq!(ctx.objects["a"].last_modified, time(3));
+  assert_eq!(ctx.objects["b"].point, pt(5, 5));
+  let at = ctx.objects_at(&pt(11, 2));
+  assert_eq!(at.len(), 1);
+  assert_eq!(at[0].0, "a");
+}
+
+#[test]
+fn velocity_stops_at_zero() {
+  let mut ctx = world([("a", drifting(-3, -10))]);
+  for _ in 0..2 {
+    run(&mut ctx, vec![]).unwrap();
+  }
+  assert_eq!(ctx.objects["a"].point, pt(0, 0));
+}
+
+#[test]
+fn velocity_wraps_on_torus() {
+  let mut ctx = world([("a", drifting(4, -7))]);
+  ctx.set_torus(Some(Torus::new(8u32.into(), 8u32.into())));
+  run(&mut ctx, vec![]).unwrap();
+  assert_eq!(ctx.objects["a"].point, pt(1, 6));
+}
+
+#[test]
+fn velocity_on_big_coordinates_falls_back_to_big_integers() {
+  let far: BigUint = BigUint::from(1u32) << 70;
+  let mut ctx = world([("a", drifting(3, -2)), ("b", drifting(3, -2))]);
+  std::sync::Arc::make_mut(ctx.objects.get_mut("a").unwrap()).point =
+    Point::new(far.clone(), far.clone());
+  ctx.rebuild_spatial_index();
+  run(&mut ctx, vec![]).unwrap();
+  assert_eq!(ctx.objects["a"].point, Point::new(&far + 3u32, &far - 2u32));
+  assert_eq!(ctx.objects["b"].point, pt(8, 3));
+}
+
+#[test]
+fn event_move_applies_after_velocity() {
+  let mut ctx = world([("a", drifting(1, 1))]);
+  let push = Act {
+    moves: Some(("a".to_string(), pt(20, 20))),
+    ..act("a")
+  };
+  run(&mut ctx, vec![emit(vec![push])]).unwrap();
+  assert_eq!(ctx.objects["a"].point, pt(20, 20));
+  run(&mut ctx, vec![]).unwrap();
+  assert_eq!(ctx.objects["a"].point, pt(21, 21));
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "serde")]
+
+mod common;
+
+use common::*;
+use hakoniwa::run;
+use serde_json::Value;
+
+#[test]
+fn frame_json_has_the_documented_keys() {
+  let mut ctx = world([("a", tree("oak", 3, 4)), ("b", tree("pine", 0, 0))]);
+  let nudge = Act {
+    target: Some("b".to_string()),
+    ..act("a")
+  };
+  run(&mut ctx, vec![emit(vec![nudge])]).unwrap();
+  let frame: Value = serde_json::from_str(&ctx.to_frame_json()).unwrap();
+  assert_eq!(frame["schema"], 1);
+  for key in [
+    "all",
+    "year",
+    "day",
+    "day_of_year",
+    "time_of_day",
+    "one_day_of_time",
+  ] {
+    assert!(frame["time"].get(key).is_some(), "time.{key}");
+  }
+  assert_eq!(frame["time"]["all"], "1");
+  let objects = frame["objects"].as_array().unwrap();
+  assert_eq!(objects.len(), 2);
+  for key in ["id", "name", "point", "parent_frame", "data"] {
+    assert!(objects[0].get(key).is_some(), "objects[].{key}");
+  }
+  assert_eq!(objects[0]["id"], "a");
+  assert_eq!(objects[0]["name"], "oak");
+  assert_eq!(objects[0]["point"]["x"], "3");
+  assert_eq!(objects[0]["point"]["y"], "4");
+  let events = frame["events"].as_array().unwrap();
+  assert_eq!(events.len(), 1);
+  for key in [
+    "id",
+    "sequence",
+    "kind",
+    "do_object",
+    "target_object",
+    "location",
+    "affected_objects",
+    "data",
+  ] {
+    assert!(events[0].get(key).is_some(), "events[].{key}");
+  }
+  assert_eq!(events[0]["do_object"], "a");
+  assert_eq!(events[0]["target_object"], "b");
+}